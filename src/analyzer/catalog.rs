@@ -0,0 +1,753 @@
+//! Validation of a parse tree against the database catalog, catching mistakes
+//! like misspelled columns before any rows are inserted.
+
+use super::error::{SchemaError, SchemaErrorKind, SchemaErrors};
+use super::{table_scope, types, ValidatedParseTree};
+use crate::catalog::{self, Catalog};
+use crate::parser::nodes::*;
+use crate::Position;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+/// A table from the parse tree and its counterpart in the catalog, if found.
+struct ResolvedTable<'a> {
+    qualified_name: String,
+    table: Option<&'a catalog::Table>,
+}
+
+/// Validates data files in the order they are loaded, so that references to records
+/// declared in earlier files are checked against their tables too.
+pub struct Validator<'a> {
+    catalog: &'a Catalog,
+    errors: Vec<SchemaError>,
+    /// Named records keyed by their reference scope, eg. `myschema.mytable.myrecord`
+    records: HashSet<String>,
+    /// Tables keyed by their reference scope, eg. `myschema.mytable`
+    scopes: HashMap<String, ResolvedTable<'a>>,
+    /// Values of the variables declared so far, so that they can be checked in place
+    variables: HashMap<&'a str, &'a Value>,
+}
+
+pub fn validate<'a>(
+    tree: &'a ValidatedParseTree,
+    catalog: &'a Catalog,
+) -> Result<(), SchemaErrors> {
+    Validator::new(catalog).validate(tree)
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(catalog: &'a Catalog) -> Self {
+        Self {
+            catalog,
+            errors: Vec::new(),
+            records: HashSet::new(),
+            scopes: HashMap::new(),
+            variables: HashMap::new(),
+        }
+    }
+
+    /// Returns every problem found in the tree, with everything declared in the trees
+    /// validated so far in scope.
+    pub fn validate(&mut self, tree: &'a ValidatedParseTree) -> Result<(), SchemaErrors> {
+        for node in &tree.0.nodes {
+            match node {
+                StructuralNode::Schema(schema) => {
+                    let name = unquote(&schema.identity.name);
+
+                    if !self.catalog.schemas.contains_key(&name) {
+                        self.error(
+                            SchemaErrorKind::UnknownSchema { schema: name },
+                            schema.position,
+                        );
+                    }
+
+                    for table in &schema.nodes {
+                        self.validate_table(Some(schema), table);
+                    }
+                }
+                StructuralNode::Table(table) => self.validate_table(None, table),
+                StructuralNode::Truncate(truncate) => {
+                    for name in &truncate.tables {
+                        self.validate_table_name(name);
+                    }
+                }
+                // Params are bound as variables by the analyzer
+                StructuralNode::Param(_) => unreachable!("params are bound before validation"),
+                StructuralNode::Variable(variable) => {
+                    let value = self.resolve(&variable.value);
+                    self.variables.insert(&variable.name, value);
+                }
+                // Neither sequences nor the results of SQL statements are part of the catalog
+                StructuralNode::ResetSequence(_) | StructuralNode::Sql(_) => {}
+            }
+        }
+
+        match self.errors.is_empty() {
+            true => Ok(()),
+            false => Err(SchemaErrors(mem::take(&mut self.errors))),
+        }
+    }
+
+    fn error(&mut self, kind: SchemaErrorKind, position: Position) {
+        self.errors.push(SchemaError { kind, position });
+    }
+
+    /// Returns the value a variable was declared with, or the value itself otherwise.
+    fn resolve(&self, value: &'a Value) -> &'a Value {
+        match value {
+            Value::Variable(name) => self.variables.get(name.as_str()).copied().unwrap_or(value),
+            _ => value,
+        }
+    }
+
+    fn validate_table(&mut self, schema: Option<&Schema>, table: &Table) {
+        let schema_name = schema.map(|s| unquote(&s.identity.name));
+        let table_name = unquote(&table.identity.name);
+
+        let resolved = match self.catalog.table(schema_name.as_deref(), &table_name) {
+            Some((schema, found)) => ResolvedTable {
+                qualified_name: format!("{}.{}", schema, table_name),
+                table: Some(found),
+            },
+            None => {
+                let qualified_name = match &schema_name {
+                    Some(schema) => format!("{}.{}", schema, table_name),
+                    None => table_name,
+                };
+
+                // An unknown schema has already been reported, and every table in it is unknown
+                if schema_name.is_none_or(|s| self.catalog.schemas.contains_key(&s)) {
+                    self.error(
+                        SchemaErrorKind::UnknownTable {
+                            table: qualified_name.clone(),
+                        },
+                        table.position,
+                    );
+                }
+
+                ResolvedTable {
+                    qualified_name,
+                    table: None,
+                }
+            }
+        };
+
+        let scope = table_scope(schema, table);
+        let qualified_name = resolved.qualified_name.clone();
+        let found = resolved.table;
+
+        // Records can reference earlier records in the same table
+        self.scopes.insert(scope.clone(), resolved);
+
+        for name in table.nodes.iter().filter_map(|record| record.name.as_ref()) {
+            self.records.insert(format!("{}.{}", scope, name));
+        }
+
+        if let Some(found) = found {
+            if let Some(on_conflict) = &table.on_conflict {
+                for column in &on_conflict.columns {
+                    let column = unquote(column);
+
+                    if found.column(&column).is_none() {
+                        self.error(
+                            SchemaErrorKind::UnknownColumn {
+                                table: qualified_name.clone(),
+                                column,
+                            },
+                            on_conflict.position,
+                        );
+                    }
+                }
+            }
+
+            for record in &table.nodes {
+                self.validate_record(&scope, &qualified_name, found, record);
+            }
+        }
+    }
+
+    fn validate_table_name(&mut self, name: &QualifiedName) {
+        let schema = name.schema.as_deref().map(unquote);
+        let table = unquote(&name.name);
+
+        if self.catalog.table(schema.as_deref(), &table).is_some() {
+            return;
+        }
+
+        let kind = match schema {
+            Some(schema) if !self.catalog.schemas.contains_key(&schema) => {
+                SchemaErrorKind::UnknownSchema { schema }
+            }
+            Some(schema) => SchemaErrorKind::UnknownTable {
+                table: format!("{}.{}", schema, table),
+            },
+            None => SchemaErrorKind::UnknownTable { table },
+        };
+        self.error(kind, name.position);
+    }
+
+    fn validate_record(
+        &mut self,
+        scope: &str,
+        qualified_name: &str,
+        table: &catalog::Table,
+        record: &Record,
+    ) {
+        // Generated columns can still be used to match rows
+        let inserting = record.kind == RecordKind::Insert;
+        let given =
+            self.validate_attributes(scope, qualified_name, table, &record.nodes, inserting);
+
+        if let RecordKind::Update { set, .. } = &record.kind {
+            self.validate_attributes(scope, qualified_name, table, set, true);
+        }
+
+        if !inserting {
+            return;
+        }
+
+        for column in &table.columns {
+            if column.is_required() && !given.contains(&column.name) {
+                self.error(
+                    SchemaErrorKind::MissingColumn {
+                        table: qualified_name.to_owned(),
+                        column: column.name.clone(),
+                    },
+                    record.position,
+                );
+            }
+        }
+    }
+
+    /// Validates the attributes of a single clause, returning the unquoted names of the
+    /// columns given.
+    fn validate_attributes(
+        &mut self,
+        scope: &str,
+        qualified_name: &str,
+        table: &catalog::Table,
+        attributes: &[Attribute],
+        writes: bool,
+    ) -> HashSet<String> {
+        let mut given = HashSet::new();
+
+        for attribute in attributes {
+            let name = unquote(&attribute.name);
+
+            match table.column(&name) {
+                Some(column) if column.generated && writes => self.error(
+                    SchemaErrorKind::GeneratedColumn {
+                        table: qualified_name.to_owned(),
+                        column: name.clone(),
+                    },
+                    attribute.position,
+                ),
+                Some(column) => {
+                    let value = self.resolve(&attribute.value);
+
+                    if let Err(reason) = types::check(value, &column.data_type, &self.catalog.enums)
+                    {
+                        self.error(
+                            SchemaErrorKind::InvalidValue {
+                                column: name.clone(),
+                                data_type: column.data_type.clone(),
+                                reason,
+                            },
+                            attribute.position,
+                        );
+                    }
+                }
+                None => self.error(
+                    SchemaErrorKind::UnknownColumn {
+                        table: qualified_name.to_owned(),
+                        column: name.clone(),
+                    },
+                    attribute.position,
+                ),
+            }
+
+            let value = attribute.value.uncast();
+
+            if let Value::Reference(reference) = value {
+                self.validate_reference(scope, attribute, reference);
+            }
+
+            if let Value::Interpolated(segments) | Value::ParameterizedSql(segments) = value {
+                for segment in segments {
+                    if let Segment::Reference(reference) = segment {
+                        self.validate_reference(scope, attribute, reference);
+                    }
+                }
+            }
+
+            given.insert(name);
+        }
+
+        given
+    }
+
+    /// Checks that the column referenced from another record will be returned
+    /// when that record is inserted.
+    fn validate_reference(&mut self, scope: &str, attribute: &Attribute, reference: &Reference) {
+        let (table_scope, record, column) = match reference {
+            Reference::SchemaLevel(s) => {
+                (format!("{}.{}", s.schema, s.table), &s.record, &s.column)
+            }
+            Reference::TableLevel(t) => (t.table.clone(), &t.record, &t.column),
+            // Rows returned by SQL statements can't be checked
+            Reference::RecordLevel(r)
+                if !self.records.contains(&format!("{}.{}", scope, r.record)) =>
+            {
+                return
+            }
+            Reference::RecordLevel(r) => (scope.to_owned(), &r.record, &r.column),
+            Reference::ColumnLevel(_) => return,
+        };
+        let column = match column {
+            ReferencedColumn::Explicit(column) => unquote(column),
+            ReferencedColumn::Implicit => unquote(&attribute.name),
+        };
+
+        let resolved = match self.scopes.get(&table_scope) {
+            Some(resolved) => resolved,
+            None => return,
+        };
+
+        // Tables that weren't found have already been reported
+        if let Some(table) = resolved.table {
+            if table.column(&column).is_none() {
+                let kind = SchemaErrorKind::ReferencedColumnNotFound {
+                    record: format!("{}.{}", table_scope, record),
+                    table: resolved.qualified_name.clone(),
+                    column,
+                };
+                self.error(kind, attribute.position);
+            }
+        }
+    }
+}
+
+/// Converts a possibly quoted identifier to the name stored in the catalog. Unquoted
+/// identifiers aren't lowercased, since the loader quotes them as they are written.
+fn unquote(ident: &str) -> String {
+    match ident.strip_prefix('"').and_then(|i| i.strip_suffix('"')) {
+        Some(inner) => inner.replace("\"\"", "\""),
+        None => ident.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{analyze, Analyzer};
+    use crate::catalog::Column;
+    use crate::{lexer, parser};
+
+    fn column(name: &str, nullable: bool, default: Option<&str>) -> Column {
+        Column {
+            name: name.to_owned(),
+            data_type: "text".to_owned(),
+            nullable,
+            default: default.map(String::from),
+            identity: false,
+            generated: false,
+        }
+    }
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog {
+            search_path: vec!["public".to_owned()],
+            ..Catalog::default()
+        };
+        let users = catalog::Table {
+            columns: vec![
+                Column {
+                    identity: true,
+                    ..column("id", false, None)
+                },
+                column("name", false, None),
+                column("email", true, None),
+                column("admin", false, Some("false")),
+                Column {
+                    generated: true,
+                    ..column("slug", true, None)
+                },
+                Column {
+                    data_type: "smallint".to_owned(),
+                    ..column("age", true, None)
+                },
+            ],
+            foreign_keys: Vec::new(),
+        };
+
+        catalog
+            .schemas
+            .entry("public".to_owned())
+            .or_default()
+            .tables
+            .insert("users".to_owned(), users);
+        catalog
+            .schemas
+            .insert("empty".to_owned(), Default::default());
+        catalog
+    }
+
+    fn validate_input(input: &str) -> Result<(), SchemaErrors> {
+        let tokens = lexer::tokenize(input.chars()).unwrap();
+        let tree = analyze(parser::parse(tokens.into_iter()).unwrap()).unwrap();
+
+        validate(&tree, &catalog())
+    }
+
+    fn kinds(input: &str) -> Vec<SchemaErrorKind> {
+        match validate_input(input) {
+            Ok(()) => Vec::new(),
+            Err(SchemaErrors(errors)) => errors.into_iter().map(|e| e.kind).collect(),
+        }
+    }
+
+    #[test]
+    fn test_valid() {
+        let input = "
+            schema public (
+                table users (
+                    kevin (name 'Kevin', email 'k@example.com')
+                )
+            )
+            table users as u (
+                (name @public.users.kevin.name, admin true)
+                alice (name 'Alice')
+            )
+            table users (
+                (name @u.alice.name)
+                bob (name 'Bob')
+                (name @bob.)
+            )
+        ";
+
+        assert_eq!(validate_input(input), Ok(()));
+    }
+
+    #[test]
+    fn test_unknown_names() {
+        assert_eq!(
+            kinds("schema nope ( table users () ) schema empty ( table users () ) table nope ()"),
+            vec![
+                SchemaErrorKind::UnknownSchema {
+                    schema: "nope".to_owned()
+                },
+                SchemaErrorKind::UnknownTable {
+                    table: "empty.users".to_owned()
+                },
+                SchemaErrorKind::UnknownTable {
+                    table: "nope".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_earlier_trees() {
+        let catalog = catalog();
+        let mut analyzer = Analyzer::default();
+        let mut validator = Validator::new(&catalog);
+        let trees = [
+            "let age = 40000 table users ( kevin (name 'Kevin') )",
+            "table users ( (name @users.kevin.nope, age $age) )",
+        ]
+        .map(|input| {
+            let tokens = lexer::tokenize(input.chars()).unwrap();
+            let parse_tree = parser::parse(tokens.into_iter()).unwrap();
+            analyzer.analyze(parse_tree, &HashMap::new()).unwrap()
+        });
+
+        assert_eq!(validator.validate(&trees[0]), Ok(()));
+        assert_eq!(
+            validator
+                .validate(&trees[1])
+                .map_err(|SchemaErrors(errors)| errors.into_iter().map(|e| e.kind).collect()),
+            Err(vec![
+                SchemaErrorKind::ReferencedColumnNotFound {
+                    record: "users.kevin".to_owned(),
+                    table: "public.users".to_owned(),
+                    column: "nope".to_owned(),
+                },
+                SchemaErrorKind::InvalidValue {
+                    column: "age".to_owned(),
+                    data_type: "smallint".to_owned(),
+                    reason: "out of range".to_owned(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_mixed_case_names() {
+        assert_eq!(
+            kinds("schema Public ( table users () ) table USERS () truncate Users"),
+            vec![
+                SchemaErrorKind::UnknownSchema {
+                    schema: "Public".to_owned()
+                },
+                SchemaErrorKind::UnknownTable {
+                    table: "USERS".to_owned()
+                },
+                SchemaErrorKind::UnknownTable {
+                    table: "Users".to_owned()
+                },
+            ]
+        );
+        assert_eq!(
+            kinds("table users ( (Name 'Kevin') )"),
+            vec![
+                SchemaErrorKind::UnknownColumn {
+                    table: "public.users".to_owned(),
+                    column: "Name".to_owned(),
+                },
+                SchemaErrorKind::MissingColumn {
+                    table: "public.users".to_owned(),
+                    column: "name".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_truncated_tables() {
+        assert_eq!(
+            kinds("truncate users, nope, empty.users, missing.users\nreset sequence nope_seq"),
+            vec![
+                SchemaErrorKind::UnknownTable {
+                    table: "nope".to_owned()
+                },
+                SchemaErrorKind::UnknownTable {
+                    table: "empty.users".to_owned()
+                },
+                SchemaErrorKind::UnknownSchema {
+                    schema: "missing".to_owned()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sql_references() {
+        let input = "
+            tenant = sql `SELECT 1 AS tenant_id`
+            table users (
+                (name 'Kevin', email @tenant.tenant_id)
+            )
+        ";
+
+        assert_eq!(kinds(input), vec![]);
+    }
+
+    #[test]
+    fn test_invalid_columns() {
+        let input = "
+            table users (
+                (name 'a', emial 'a@example.com', slug 'a')
+                (email 'b@example.com')
+            )
+        ";
+
+        assert_eq!(
+            validate_input(input),
+            Err(SchemaErrors(vec![
+                SchemaError {
+                    kind: SchemaErrorKind::UnknownColumn {
+                        table: "public.users".to_owned(),
+                        column: "emial".to_owned(),
+                    },
+                    position: Position {
+                        line: 3,
+                        column: 28
+                    },
+                },
+                SchemaError {
+                    kind: SchemaErrorKind::GeneratedColumn {
+                        table: "public.users".to_owned(),
+                        column: "slug".to_owned(),
+                    },
+                    position: Position {
+                        line: 3,
+                        column: 51
+                    },
+                },
+                SchemaError {
+                    kind: SchemaErrorKind::MissingColumn {
+                        table: "public.users".to_owned(),
+                        column: "name".to_owned(),
+                    },
+                    position: Position {
+                        line: 4,
+                        column: 17
+                    },
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_referenced_column_not_found() {
+        let input = "
+            table users (
+                kevin (name 'Kevin')
+                (name @kevin.nmae)
+            )
+        ";
+
+        assert_eq!(
+            kinds(input),
+            vec![SchemaErrorKind::ReferencedColumnNotFound {
+                record: "users.kevin".to_owned(),
+                table: "public.users".to_owned(),
+                column: "nmae".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_value() {
+        let input = "
+            table users (
+                (name 'a', admin 'maybe', age 40_000)
+            )
+        ";
+
+        assert_eq!(
+            validate_input(input),
+            Err(SchemaErrors(vec![SchemaError {
+                kind: SchemaErrorKind::InvalidValue {
+                    column: "age".to_owned(),
+                    data_type: "smallint".to_owned(),
+                    reason: "out of range".to_owned(),
+                },
+                position: Position {
+                    line: 3,
+                    column: 43
+                },
+            }]))
+        );
+    }
+
+    #[test]
+    fn test_cast_values() {
+        let input = "
+            table users (
+                kevin (name 'Kevin', age 40_000::int)
+                (name @kevin.nmae::text)
+            )
+        ";
+
+        assert_eq!(
+            kinds(input),
+            vec![SchemaErrorKind::ReferencedColumnNotFound {
+                record: "users.kevin".to_owned(),
+                table: "public.users".to_owned(),
+                column: "nmae".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_variable_value() {
+        let input = "
+            let age = 40_000
+            let same = $age
+            table users (
+                (name 'a', age $same)
+            )
+        ";
+
+        assert_eq!(
+            kinds(input),
+            vec![SchemaErrorKind::InvalidValue {
+                column: "age".to_owned(),
+                data_type: "smallint".to_owned(),
+                reason: "out of range".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unknown_conflict_column() {
+        let input = "
+            table users on conflict (email, \"Name\") do nothing (
+                (name 'a', email 'a@example.com', \"Name\" 'a')
+            )
+        ";
+
+        assert_eq!(
+            kinds(input),
+            vec![
+                SchemaErrorKind::UnknownColumn {
+                    table: "public.users".to_owned(),
+                    column: "Name".to_owned(),
+                },
+                SchemaErrorKind::UnknownColumn {
+                    table: "public.users".to_owned(),
+                    column: "Name".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lookup_records() {
+        let input = "
+            table users (
+                kevin = lookup (slug 'kevin')
+                (name @kevin.slug)
+                bob = lookup (nmae 'Bob')
+            )
+        ";
+
+        assert_eq!(
+            kinds(input),
+            vec![SchemaErrorKind::UnknownColumn {
+                table: "public.users".to_owned(),
+                column: "nmae".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_update_and_delete_records() {
+        let input = "
+            table users (
+                update (slug 'kevin') set (name 'Kevin', slug 'k')
+                kevin = update (id 1) set (age 'old') expect 1
+                delete (nmae 'Bob')
+            )
+        ";
+
+        assert_eq!(
+            kinds(input),
+            vec![
+                SchemaErrorKind::GeneratedColumn {
+                    table: "public.users".to_owned(),
+                    column: "slug".to_owned(),
+                },
+                SchemaErrorKind::InvalidValue {
+                    column: "age".to_owned(),
+                    data_type: "smallint".to_owned(),
+                    reason: "expected an integer".to_owned(),
+                },
+                SchemaErrorKind::UnknownColumn {
+                    table: "public.users".to_owned(),
+                    column: "nmae".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("users"), "users");
+        assert_eq!(unquote("Users"), "Users");
+        assert_eq!(unquote("\"Users\""), "Users");
+        assert_eq!(unquote("\"My Table\""), "My Table");
+        assert_eq!(unquote("\"say \"\"hi\"\"\""), "say \"hi\"");
+    }
+}
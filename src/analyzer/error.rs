@@ -1,13 +1,45 @@
 use std::error::Error;
 use std::fmt;
-// use crate::Position;
+
+use crate::Position;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum AnalyzeErrorKind {
-    ColumnNotFound { column: String },
-    DuplicateColumn { scope: String, column: String },
-    DuplicateRecord { scope: String, record: String },
-    RecordNotFound { record: String },
+    ColumnNotFound {
+        column: String,
+    },
+    DuplicateColumn {
+        scope: String,
+        column: String,
+    },
+    DuplicateRecord {
+        scope: String,
+        record: String,
+    },
+    DuplicateSqlName {
+        name: String,
+    },
+    DuplicateVariable {
+        name: String,
+    },
+    InvalidGenerator {
+        column: String,
+        reason: String,
+    },
+    MissingConflictColumn {
+        scope: String,
+        record: String,
+        column: String,
+    },
+    MissingParam {
+        name: String,
+    },
+    RecordNotFound {
+        record: String,
+    },
+    VariableNotFound {
+        name: String,
+    },
 }
 
 impl fmt::Display for AnalyzeErrorKind {
@@ -23,9 +55,39 @@ impl fmt::Display for AnalyzeErrorKind {
             AnalyzeErrorKind::DuplicateRecord { scope, record } => {
                 write!(f, "duplicate record `{}` in scope `{}`", record, scope)
             }
+            AnalyzeErrorKind::DuplicateSqlName { name } => {
+                write!(f, "duplicate name `{}` for SQL statement", name)
+            }
+            AnalyzeErrorKind::DuplicateVariable { name } => {
+                write!(f, "duplicate variable `${}`", name)
+            }
+            AnalyzeErrorKind::InvalidGenerator { column, reason } => {
+                write!(f, "invalid generator for column `{}`: {}", column, reason)
+            }
+            AnalyzeErrorKind::MissingConflictColumn {
+                scope,
+                record,
+                column,
+            } => {
+                write!(
+                    f,
+                    "record `{}` in scope `{}` must have a value for conflict column `{}`",
+                    record, scope, column
+                )
+            }
+            AnalyzeErrorKind::MissingParam { name } => {
+                write!(
+                    f,
+                    "missing value for param `{}`, which can be given with `--param {}=<value>`",
+                    name, name
+                )
+            }
             AnalyzeErrorKind::RecordNotFound { record } => {
                 write!(f, "record `{}` not found", record)
             }
+            AnalyzeErrorKind::VariableNotFound { name } => {
+                write!(f, "variable `${}` not found", name)
+            }
         }
     }
 }
@@ -43,3 +105,151 @@ impl fmt::Display for AnalyzeError {
 }
 
 impl Error for AnalyzeError {}
+
+/// Something in a data file that is valid but probably a mistake.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnalyzeWarningKind {
+    TruncatesDeclaredRecords { table: String },
+}
+
+impl fmt::Display for AnalyzeWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnalyzeWarningKind::TruncatesDeclaredRecords { table } => {
+                write!(
+                    f,
+                    "truncating `{}` deletes the records declared for it earlier in the file",
+                    table
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AnalyzeWarning {
+    pub kind: AnalyzeWarningKind,
+    pub position: Position,
+}
+
+impl fmt::Display for AnalyzeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.position)
+    }
+}
+
+/// Mismatch between a data file and the database catalog.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaErrorKind {
+    GeneratedColumn {
+        table: String,
+        column: String,
+    },
+    InvalidValue {
+        column: String,
+        data_type: String,
+        reason: String,
+    },
+    MissingColumn {
+        table: String,
+        column: String,
+    },
+    ReferencedColumnNotFound {
+        record: String,
+        table: String,
+        column: String,
+    },
+    UnknownColumn {
+        table: String,
+        column: String,
+    },
+    UnknownSchema {
+        schema: String,
+    },
+    UnknownTable {
+        table: String,
+    },
+}
+
+impl fmt::Display for SchemaErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SchemaErrorKind::*;
+
+        match self {
+            GeneratedColumn { table, column } => {
+                write!(
+                    f,
+                    "cannot insert into generated column `{}` of table `{}`",
+                    column, table
+                )
+            }
+            InvalidValue {
+                column,
+                data_type,
+                reason,
+            } => {
+                write!(
+                    f,
+                    "invalid value for column `{}` of type `{}`: {}",
+                    column, data_type, reason
+                )
+            }
+            MissingColumn { table, column } => {
+                write!(
+                    f,
+                    "missing value for required column `{}` of table `{}`",
+                    column, table
+                )
+            }
+            ReferencedColumnNotFound {
+                record,
+                table,
+                column,
+            } => {
+                write!(
+                    f,
+                    "referenced column `{}` of record `{}` not found in table `{}`",
+                    column, record, table
+                )
+            }
+            UnknownColumn { table, column } => {
+                write!(f, "unknown column `{}` in table `{}`", column, table)
+            }
+            UnknownSchema { schema } => write!(f, "unknown schema `{}`", schema),
+            UnknownTable { table } => write!(f, "unknown table `{}`", table),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SchemaError {
+    pub kind: SchemaErrorKind,
+    pub position: Position,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.kind, self.position)
+    }
+}
+
+impl Error for SchemaError {}
+
+/// Every mismatch found in a data file, so they can all be fixed at once.
+#[derive(Debug, PartialEq)]
+pub struct SchemaErrors(pub Vec<SchemaError>);
+
+impl fmt::Display for SchemaErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for SchemaErrors {}
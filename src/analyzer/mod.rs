@@ -21,11 +21,15 @@ TODO
         (col "t1".rec1."col")
     )
 */
+pub mod catalog;
 pub mod error;
+mod types;
 
+use crate::generator::Fake;
 use crate::parser::nodes::*;
 use error::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::mem;
 
 pub type AnalyzeResult = Result<ValidatedParseTree, AnalyzeError>;
 
@@ -37,25 +41,177 @@ impl ValidatedParseTree {
     }
 }
 
+/// Keys of everything values can refer to: records by their reference scope, rows
+/// captured by SQL statements by name, and variables by name prefixed with `$`
 type RefSet = HashSet<String>;
 
 pub fn analyze(parse_tree: ParseTree) -> AnalyzeResult {
-    let mut refset = RefSet::default();
+    analyze_with_params(parse_tree, &HashMap::new())
+}
+
+/// Same as `analyze`, but with values for the params declared in the tree, which are
+/// bound as variables before anything else is checked.
+pub fn analyze_with_params(
+    parse_tree: ParseTree,
+    params: &HashMap<String, String>,
+) -> AnalyzeResult {
+    Analyzer::default().analyze(parse_tree, params)
+}
+
+/// Analyzes data files in the order they are loaded, so that each one can refer to
+/// the records, SQL statements, and variables declared in the files before it.
+#[derive(Default)]
+pub struct Analyzer {
+    refset: RefSet,
+}
+
+impl Analyzer {
+    /// Same as `analyze_with_params`, but with everything declared in the trees
+    /// analyzed so far in scope.
+    pub fn analyze(
+        &mut self,
+        mut parse_tree: ParseTree,
+        params: &HashMap<String, String>,
+    ) -> AnalyzeResult {
+        bind_params(&mut parse_tree, params)?;
+        analyze_nodes(&parse_tree.nodes, &mut self.refset)?;
+
+        Ok(ValidatedParseTree(parse_tree))
+    }
+}
 
-    for node in &parse_tree.nodes {
+fn analyze_nodes(nodes: &[StructuralNode], refset: &mut RefSet) -> Result<(), AnalyzeError> {
+    for node in nodes {
         match node {
             StructuralNode::Schema(schema) => {
                 for table in &schema.nodes {
-                    analyze_table(Some(schema), table, &mut refset)?;
+                    analyze_table(Some(schema), table, refset)?;
                 }
             }
             StructuralNode::Table(table) => {
-                analyze_table(None, table, &mut refset)?;
+                analyze_table(None, table, refset)?;
+            }
+            StructuralNode::Sql(sql) => {
+                // Captured rows are keyed by name alone, which can't clash with records
+                // since their keys are always qualified by a table
+                if let Some(name) = &sql.name {
+                    if !refset.insert(name.clone()) {
+                        return Err(AnalyzeError {
+                            kind: AnalyzeErrorKind::DuplicateSqlName { name: name.clone() },
+                        });
+                    }
+                }
+            }
+            StructuralNode::Variable(variable) => {
+                // Variables can only refer to those declared before them
+                if let Value::Variable(name) = variable.value.uncast() {
+                    analyze_variable(name, refset)?;
+                }
+
+                if !refset.insert(format!("${}", variable.name)) {
+                    return Err(AnalyzeError {
+                        kind: AnalyzeErrorKind::DuplicateVariable {
+                            name: variable.name.clone(),
+                        },
+                    });
+                }
+            }
+            StructuralNode::Param(_) => unreachable!("params are bound before analysis"),
+            StructuralNode::Truncate(_) | StructuralNode::ResetSequence(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces every param with a variable of the value given for it, which is always
+/// a string literal, or its default otherwise.
+fn bind_params(
+    parse_tree: &mut ParseTree,
+    params: &HashMap<String, String>,
+) -> Result<(), AnalyzeError> {
+    for node in &mut parse_tree.nodes {
+        if let StructuralNode::Param(param) = node {
+            let value = match (params.get(&param.name), param.default.take()) {
+                (Some(value), _) => Value::Text(format!("'{}'", value.replace('\'', "''"))),
+                (None, Some(default)) => default,
+                (None, None) => {
+                    return Err(AnalyzeError {
+                        kind: AnalyzeErrorKind::MissingParam {
+                            name: param.name.clone(),
+                        },
+                    })
+                }
+            };
+            let variable = Variable {
+                name: mem::take(&mut param.name),
+                value,
+                position: param.position,
+            };
+
+            *node = StructuralNode::Variable(Box::new(variable));
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds things that are probably mistakes, such as truncating a table after
+/// declaring records for it.
+pub fn warnings(tree: &ValidatedParseTree) -> Vec<AnalyzeWarning> {
+    let mut declared = HashSet::new();
+    let mut warnings = Vec::new();
+
+    for node in &tree.0.nodes {
+        match node {
+            StructuralNode::Schema(schema) => {
+                for table in schema.nodes.iter().filter(|table| !table.nodes.is_empty()) {
+                    declared.insert((Some(&schema.identity.name), &table.identity.name));
+                }
+            }
+            StructuralNode::Table(table) if !table.nodes.is_empty() => {
+                declared.insert((None, &table.identity.name));
+            }
+            StructuralNode::Truncate(truncate) => {
+                for name in &truncate.tables {
+                    if declared.contains(&(name.schema.as_ref(), &name.name)) {
+                        let table = match &name.schema {
+                            Some(schema) => format!("{}.{}", schema, name.name),
+                            None => name.name.clone(),
+                        };
+                        warnings.push(AnalyzeWarning {
+                            kind: AnalyzeWarningKind::TruncatesDeclaredRecords { table },
+                            position: name.position,
+                        });
+                    }
+                }
             }
+            _ => {}
         }
     }
 
-    Ok(ValidatedParseTree(parse_tree))
+    warnings
+}
+
+pub(super) fn table_scope(schema: Option<&Schema>, table: &Table) -> String {
+    // TODO: This is mostly copy-pasta
+    let scope = table
+        .identity
+        .alias
+        .as_ref()
+        .unwrap_or(&table.identity.name);
+    match schema {
+        Some(schema) => format!(
+            "{}.{}",
+            schema
+                .identity
+                .alias
+                .as_ref()
+                .unwrap_or(&schema.identity.name),
+            scope,
+        ),
+        None => scope.to_owned(),
+    }
 }
 
 fn analyze_table(
@@ -63,30 +219,36 @@ fn analyze_table(
     table: &Table,
     refset: &mut RefSet,
 ) -> Result<(), AnalyzeError> {
-    // TODO: This is mostly copy-pasta
-    let table_scope = {
-        let scope = table
-            .identity
-            .alias
-            .as_ref()
-            .unwrap_or(&table.identity.name);
-        match schema {
-            Some(schema) => format!(
-                "{}.{}",
-                schema
-                    .identity
-                    .alias
-                    .as_ref()
-                    .unwrap_or(&schema.identity.name),
-                scope,
-            ),
-            None => scope.to_owned(),
-        }
-    };
+    let table_scope = table_scope(schema, table);
     for record in &table.nodes {
         analyze_record(record, refset, &table_scope)?;
 
         if let Some(name) = &record.name {
+            // Rows left as is on conflict are found by the conflict columns instead
+            if let (
+                RecordKind::Insert,
+                Some(OnConflict {
+                    columns,
+                    action: ConflictAction::DoNothing,
+                    ..
+                }),
+            ) = (&record.kind, &table.on_conflict)
+            {
+                let missing = columns
+                    .iter()
+                    .find(|column| !record.nodes.iter().any(|attr| &attr.name == *column));
+
+                if let Some(column) = missing {
+                    return Err(AnalyzeError {
+                        kind: AnalyzeErrorKind::MissingConflictColumn {
+                            scope: table_scope,
+                            record: name.clone(),
+                            column: column.clone(),
+                        },
+                    });
+                }
+            }
+
             let key = format!("{}.{}", table_scope, name);
 
             if !refset.insert(key) {
@@ -103,14 +265,56 @@ fn analyze_table(
     Ok(())
 }
 
+/// Resolves a reference to a record in the same table, falling back to a row
+/// captured by a SQL statement if the table has no such record.
+pub(crate) fn record_level_key(
+    parent_scope: &str,
+    record: &str,
+    exists: impl Fn(&str) -> bool,
+) -> String {
+    let key = format!("{}.{}", parent_scope, record);
+
+    match !exists(&key) && exists(record) {
+        true => record.to_owned(),
+        false => key,
+    }
+}
+
+fn analyze_variable(name: &str, refset: &RefSet) -> Result<(), AnalyzeError> {
+    match refset.contains(&format!("${}", name)) {
+        true => Ok(()),
+        false => Err(AnalyzeError {
+            kind: AnalyzeErrorKind::VariableNotFound {
+                name: name.to_owned(),
+            },
+        }),
+    }
+}
+
 fn analyze_record(
     record: &Record,
     refset: &RefSet,
     parent_scope: &str,
+) -> Result<(), AnalyzeError> {
+    analyze_attributes(&record.nodes, refset, parent_scope)?;
+
+    // The `set` attributes of an update are a clause of their own, so they may repeat
+    // columns from the match clause and only reference columns within the clause
+    if let RecordKind::Update { set, .. } = &record.kind {
+        analyze_attributes(set, refset, parent_scope)?;
+    }
+
+    Ok(())
+}
+
+fn analyze_attributes(
+    attributes: &[Attribute],
+    refset: &RefSet,
+    parent_scope: &str,
 ) -> Result<(), AnalyzeError> {
     let mut attrnames = HashSet::new();
 
-    for attr in &record.nodes {
+    for attr in attributes {
         if !attrnames.insert(&attr.name) {
             return Err(AnalyzeError {
                 kind: AnalyzeErrorKind::DuplicateColumn {
@@ -120,37 +324,272 @@ fn analyze_record(
             });
         }
 
-        if let Value::Reference(refval) = &attr.value {
-            // Column-level references only need validation that the column being referenced
-            // is explicitly declared in the record already, since they cannot come from the
-            // database.
-            if let Reference::ColumnLevel(c) = refval {
-                if !attrnames.contains(&c.column) {
-                    return Err(AnalyzeError {
-                        kind: AnalyzeErrorKind::ColumnNotFound {
-                            column: c.column.clone(),
-                        },
-                    });
+        let value = attr.value.uncast();
+
+        if let Value::Variable(name) = value {
+            analyze_variable(name, refset)?;
+        }
+
+        if let Value::Interpolated(segments) | Value::ParameterizedSql(segments) = value {
+            for segment in segments {
+                match segment {
+                    Segment::Reference(refval) => {
+                        analyze_reference(refval, &attrnames, refset, parent_scope)?
+                    }
+                    Segment::Variable(name) => analyze_variable(name, refset)?,
+                    Segment::Text(_) => {}
                 }
-                continue;
             }
+        }
 
-            let expected_key = match refval {
-                Reference::SchemaLevel(s) => format!("{}.{}.{}", s.schema, s.table, s.record),
-                Reference::TableLevel(t) => format!("{}.{}", t.table, t.record),
-                Reference::RecordLevel(r) => format!("{}.{}", parent_scope, r.record),
-                Reference::ColumnLevel(_) => unreachable!(),
-            };
-
-            if !refset.contains(&expected_key) {
+        if let Value::Generator(generator) = value {
+            if let Err(reason) = Fake::resolve(generator) {
                 return Err(AnalyzeError {
-                    kind: AnalyzeErrorKind::RecordNotFound {
-                        record: expected_key,
+                    kind: AnalyzeErrorKind::InvalidGenerator {
+                        column: attr.name.clone(),
+                        reason,
                     },
                 });
             }
         }
+
+        if let Value::Reference(refval) = value {
+            analyze_reference(refval, &attrnames, refset, parent_scope)?;
+        }
     }
 
     Ok(())
 }
+
+fn analyze_reference(
+    refval: &Reference,
+    attrnames: &HashSet<&String>,
+    refset: &RefSet,
+    parent_scope: &str,
+) -> Result<(), AnalyzeError> {
+    // Column-level references only need validation that the column being referenced
+    // is explicitly declared in the record already, since they cannot come from the
+    // database.
+    if let Reference::ColumnLevel(c) = refval {
+        if !attrnames.contains(&c.column) {
+            return Err(AnalyzeError {
+                kind: AnalyzeErrorKind::ColumnNotFound {
+                    column: c.column.clone(),
+                },
+            });
+        }
+        return Ok(());
+    }
+
+    let expected_key = match refval {
+        Reference::SchemaLevel(s) => format!("{}.{}.{}", s.schema, s.table, s.record),
+        Reference::TableLevel(t) => format!("{}.{}", t.table, t.record),
+        Reference::RecordLevel(r) => {
+            record_level_key(parent_scope, &r.record, |key| refset.contains(key))
+        }
+        Reference::ColumnLevel(_) => unreachable!(),
+    };
+
+    if !refset.contains(&expected_key) {
+        return Err(AnalyzeError {
+            kind: AnalyzeErrorKind::RecordNotFound {
+                record: expected_key,
+            },
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer, parser};
+
+    fn analyze_str(input: &str) -> AnalyzeResult {
+        let tokens = lexer::tokenize(input.chars()).unwrap();
+
+        analyze(parser::parse(tokens.into_iter()).unwrap())
+    }
+
+    fn warnings_for(input: &str) -> Vec<AnalyzeWarning> {
+        warnings(&analyze_str(input).unwrap())
+    }
+
+    #[test]
+    fn test_truncate_warnings() {
+        let input = "
+            truncate users, orders
+            table users ( (name 'Kevin') )
+            table orders ()
+            schema app ( table users ( (name 'Bob') ) )
+            truncate orders, app.users, users restart identity
+        ";
+
+        assert_eq!(
+            warnings_for(input),
+            vec![
+                AnalyzeWarning {
+                    kind: AnalyzeWarningKind::TruncatesDeclaredRecords {
+                        table: "app.users".to_owned(),
+                    },
+                    position: crate::Position {
+                        line: 6,
+                        column: 30
+                    },
+                },
+                AnalyzeWarning {
+                    kind: AnalyzeWarningKind::TruncatesDeclaredRecords {
+                        table: "users".to_owned(),
+                    },
+                    position: crate::Position {
+                        line: 6,
+                        column: 41
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_generators() {
+        analyze_str("table users ( (email fake.email(), born fake.date(1950, 2005)) )").unwrap();
+        assert_eq!(
+            analyze_str("table users ( update (id 1) set (born fake.date(2005)) )").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::InvalidGenerator {
+                    column: "born".to_owned(),
+                    reason: "expected `fake.date(from_year, to_year)` with years from 1 to 9999 in order"
+                        .to_owned(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        analyze_str("let a = 1 let b = $a table users ( (age $b) )").unwrap();
+        assert_eq!(
+            analyze_str("table users ( (age $a) ) let a = 1").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::VariableNotFound {
+                    name: "a".to_owned(),
+                },
+            })
+        );
+        assert_eq!(
+            analyze_str("let b = $a").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::VariableNotFound {
+                    name: "a".to_owned(),
+                },
+            })
+        );
+        assert_eq!(
+            analyze_str("let a = 1 let a = 2").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::DuplicateVariable {
+                    name: "a".to_owned(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_interpolated_references() {
+        analyze_str(
+            "let n = 1 table users ( alice (name 'a') (name 'b', email '{@name}{$n}@{@alice.}') )",
+        )
+        .unwrap();
+        assert_eq!(
+            analyze_str("table users ( (name 'b', email '{@bob.name}') )").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::RecordNotFound {
+                    record: "users.bob".to_owned(),
+                },
+            })
+        );
+        assert_eq!(
+            analyze_str("table users ( (email '{@name}', name 'b') )").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::ColumnNotFound {
+                    column: "name".to_owned(),
+                },
+            })
+        );
+        assert_eq!(
+            analyze_str("table users ( (email 'user-{$n}') )").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::VariableNotFound {
+                    name: "n".to_owned(),
+                },
+            })
+        );
+        analyze_str("table users ( alice (age 30) (age `@alice.age + 1`, score `@age * 2`) )")
+            .unwrap();
+        assert_eq!(
+            analyze_str("table users ( (age `@bob.age + 1`) )").err(),
+            Some(AnalyzeError {
+                kind: AnalyzeErrorKind::RecordNotFound {
+                    record: "users.bob".to_owned(),
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_params() {
+        let analyze_str = |input: &str, params: &[(&str, &str)]| {
+            let tokens = lexer::tokenize(input.chars()).unwrap();
+            let params = params
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            analyze_with_params(parser::parse(tokens.into_iter()).unwrap(), &params)
+                .map(|tree| tree.into_inner().nodes)
+        };
+
+        assert_eq!(
+            analyze_str("param name param plan = 1", &[("name", "O'Brien")]),
+            Ok(vec![
+                StructuralNode::Variable(Box::new(Variable {
+                    name: "name".to_owned(),
+                    value: Value::Text("'O''Brien'".to_owned()),
+                    position: crate::Position { line: 1, column: 1 },
+                })),
+                StructuralNode::Variable(Box::new(Variable {
+                    name: "plan".to_owned(),
+                    value: Value::Number("1".to_owned()),
+                    position: crate::Position {
+                        line: 1,
+                        column: 12
+                    },
+                })),
+            ])
+        );
+        assert_eq!(
+            analyze_str(
+                "param plan = 'free' table users ( (plan $plan) )",
+                &[("plan", "pro")]
+            )
+            .map(|nodes| nodes.len()),
+            Ok(2)
+        );
+        assert_eq!(
+            analyze_str("param name", &[("plan", "pro")]),
+            Err(AnalyzeError {
+                kind: AnalyzeErrorKind::MissingParam {
+                    name: "name".to_owned(),
+                },
+            })
+        );
+        assert_eq!(
+            analyze_str("let name = 'a' param name = 'b'", &[]),
+            Err(AnalyzeError {
+                kind: AnalyzeErrorKind::DuplicateVariable {
+                    name: "name".to_owned(),
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,508 @@
+//! Static checks that literal values can be stored in the columns they are given for,
+//! for the types where that can be known without asking the database.
+//!
+//! Checks err on the side of letting values through, since Postgres accepts many
+//! more input formats (eg. for dates) than are worth replicating here.
+
+use crate::parser::nodes::Value;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+type CheckResult = Result<(), String>;
+
+/// Checks the value against the column type as formatted by Postgres, returning
+/// the reason it is invalid if so.
+pub(super) fn check(
+    value: &Value,
+    data_type: &str,
+    enums: &BTreeMap<String, Vec<String>>,
+) -> CheckResult {
+    let (base, modifiers) = split_type(data_type);
+
+    if base.ends_with("[]") {
+        return Ok(());
+    }
+
+    if let Some(labels) = enums.get(data_type) {
+        return match value {
+            Value::Text(text) => match unquote(text) {
+                Some(label) if !labels.contains(&label) => Err(format!(
+                    "expected one of {}",
+                    labels
+                        .iter()
+                        .map(|l| format!("'{}'", l))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+                _ => Ok(()),
+            },
+            Value::Bool(_) | Value::Number(_) => Err("expected a string".to_owned()),
+            _ => Ok(()),
+        };
+    }
+
+    match value {
+        Value::Bool(_) => match base.as_str() {
+            "boolean" => Ok(()),
+            base if is_known(base) && !is_string(base) => Err("unexpected boolean".to_owned()),
+            _ => Ok(()),
+        },
+        Value::Number(number) => {
+            let number = number.replace('_', "");
+
+            match base.as_str() {
+                // Postgres rounds numbers with decimals when storing them as integers
+                "smallint" => check_integer_range(&number, i16::MIN.into(), i16::MAX.into()),
+                "integer" => check_integer_range(&number, i32::MIN.into(), i32::MAX.into()),
+                "bigint" => check_integer_range(&number, i64::MIN as f64, i64::MAX as f64),
+                "numeric" => check_numeric_precision(&number, &modifiers),
+                base if is_string(base) => check_length(&number, &modifiers),
+                base if is_known(base) && !is_numeric(base) => Err("unexpected number".to_owned()),
+                _ => Ok(()),
+            }
+        }
+        Value::Text(text) => match unquote(text) {
+            Some(text) => check_text(&text, &base, &modifiers),
+            None => Ok(()),
+        },
+        // Postgres checks the value against the type it is cast to
+        Value::Cast(_)
+        | Value::Generator(_)
+        | Value::Interpolated(_)
+        | Value::ParameterizedSql(_)
+        | Value::Reference(_)
+        | Value::SqlFragment(_)
+        | Value::Variable(_) => Ok(()),
+    }
+}
+
+fn check_text(text: &str, base: &str, modifiers: &[u32]) -> CheckResult {
+    let trimmed = text.trim();
+
+    match base {
+        "boolean" => check_boolean(trimmed),
+        "smallint" | "integer" | "bigint" => match trimmed.replace('_', "").parse::<i64>() {
+            Ok(_) => check(&Value::Number(trimmed.to_owned()), base, &BTreeMap::new()),
+            Err(_) => Err("expected an integer".to_owned()),
+        },
+        "numeric" | "real" | "double precision" => match trimmed.replace('_', "").parse::<f64>() {
+            Ok(_) if base == "numeric" => check_numeric_precision(trimmed, modifiers),
+            Ok(_) => Ok(()),
+            Err(_) => Err("expected a number".to_owned()),
+        },
+        "uuid" => check_uuid(trimmed),
+        "date" => check_date(trimmed).map(|_| ()),
+        "timestamp without time zone" | "timestamp with time zone" => check_timestamp(trimmed),
+        "time without time zone" | "time with time zone" => check_time(trimmed).map(|_| ()),
+        "inet" => check_inet(trimmed),
+        "json" | "jsonb" => serde_json::from_str::<serde::de::IgnoredAny>(text)
+            .map(|_| ())
+            .map_err(|e| format!("invalid JSON: {}", e)),
+        base if is_string(base) => check_length(text, modifiers),
+        _ => Ok(()),
+    }
+}
+
+/// Splits a type like `timestamp(3) with time zone` into `timestamp with time zone`
+/// and its modifiers, ie. `[3]`.
+fn split_type(data_type: &str) -> (String, Vec<u32>) {
+    match (data_type.find('('), data_type.find(')')) {
+        (Some(start), Some(end)) if start < end => {
+            let base = format!("{}{}", &data_type[..start], &data_type[end + 1..]);
+            let modifiers = data_type[start + 1..end]
+                .split(',')
+                .filter_map(|m| m.trim().parse().ok())
+                .collect();
+
+            (base, modifiers)
+        }
+        _ => (data_type.to_owned(), Vec::new()),
+    }
+}
+
+/// Converts a string literal to its value, if it is a standard single-quoted string.
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('\'')?.strip_suffix('\'')?;
+
+    Some(inner.replace("''", "'"))
+}
+
+fn is_numeric(base: &str) -> bool {
+    matches!(
+        base,
+        "smallint" | "integer" | "bigint" | "numeric" | "real" | "double precision"
+    )
+}
+
+fn is_string(base: &str) -> bool {
+    matches!(
+        base,
+        "text" | "character varying" | "character" | "name" | "citext"
+    )
+}
+
+/// Whether the type is one that values are checked against, so that values of the
+/// wrong kind can be rejected.
+fn is_known(base: &str) -> bool {
+    is_numeric(base)
+        || is_string(base)
+        || matches!(
+            base,
+            "boolean"
+                | "uuid"
+                | "date"
+                | "timestamp without time zone"
+                | "timestamp with time zone"
+                | "time without time zone"
+                | "time with time zone"
+                | "inet"
+                | "json"
+                | "jsonb"
+        )
+}
+
+fn check_integer_range(number: &str, min: f64, max: f64) -> CheckResult {
+    match number.parse::<f64>() {
+        Ok(n) if (min..=max).contains(&n.round()) => Ok(()),
+        Ok(_) => Err("out of range".to_owned()),
+        Err(_) => Err("expected an integer".to_owned()),
+    }
+}
+
+/// Checks that the number fits in `numeric(precision, scale)` after rounding to the scale.
+fn check_numeric_precision(number: &str, modifiers: &[u32]) -> CheckResult {
+    let (precision, scale) = match modifiers {
+        [precision] => (*precision, 0),
+        [precision, scale] => (*precision, *scale),
+        _ => return Ok(()),
+    };
+    let number: f64 = match number.replace('_', "").parse() {
+        Ok(number) => number,
+        Err(_) => return Ok(()),
+    };
+    let factor = 10f64.powi(scale as i32);
+    let rounded = (number.abs() * factor).round() / factor;
+
+    match rounded < 10f64.powi(precision as i32 - scale as i32) {
+        true => Ok(()),
+        false => Err(format!(
+            "must round to an absolute value less than 10^{}",
+            precision as i32 - scale as i32
+        )),
+    }
+}
+
+/// Checks the length of strings for types like `character varying(n)`, which allow
+/// longer strings as long as the excess characters are spaces.
+fn check_length(text: &str, modifiers: &[u32]) -> CheckResult {
+    match modifiers {
+        [max] if text.trim_end_matches(' ').chars().count() > *max as usize => {
+            Err(format!("longer than {} characters", max))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_boolean(text: &str) -> CheckResult {
+    let text = text.to_lowercase();
+    let matches_prefix = |word: &str, min: usize| text.len() >= min && word.starts_with(&text);
+
+    // Same as Postgres, any unique prefix of these words is accepted
+    let valid = matches_prefix("true", 1)
+        || matches_prefix("false", 1)
+        || matches_prefix("yes", 1)
+        || matches_prefix("no", 1)
+        || matches_prefix("on", 2)
+        || matches_prefix("off", 2)
+        || text == "1"
+        || text == "0";
+
+    match valid {
+        true => Ok(()),
+        false => Err("expected a boolean".to_owned()),
+    }
+}
+
+fn check_uuid(text: &str) -> CheckResult {
+    let error = || Err("invalid UUID".to_owned());
+    let text = match text.strip_prefix('{') {
+        Some(inner) => match inner.strip_suffix('}') {
+            Some(inner) => inner,
+            None => return error(),
+        },
+        None => text,
+    };
+
+    let mut digits = 0;
+    let mut after_hyphen = false;
+
+    for c in text.chars() {
+        match c {
+            c if c.is_ascii_hexdigit() => {
+                digits += 1;
+                after_hyphen = false;
+            }
+            // Hyphens are allowed after any group of four digits
+            '-' if digits > 0 && digits % 4 == 0 && !after_hyphen => after_hyphen = true,
+            _ => return error(),
+        }
+    }
+
+    match digits == 32 && !after_hyphen {
+        true => Ok(()),
+        false => error(),
+    }
+}
+
+/// Splits off the leading run of ASCII digits.
+fn split_digits(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Validates dates in `YYYY-MM-DD` format, returning the rest of the string,
+/// and rejects anything else that clearly isn't a date.
+fn check_date(text: &str) -> Result<&str, String> {
+    let error = || Err("invalid date".to_owned());
+    let special = [
+        "today",
+        "tomorrow",
+        "yesterday",
+        "now",
+        "epoch",
+        "infinity",
+        "-infinity",
+    ];
+
+    if special.contains(&text.to_lowercase().as_str()) {
+        return Ok("");
+    }
+
+    let (year, rest) = split_digits(text);
+    let iso = year.len() >= 4 && rest.starts_with('-');
+
+    if !iso {
+        // Other formats (eg. `January 8, 1999`) are left to Postgres
+        return match text.contains(|c: char| c.is_ascii_digit()) {
+            true => Ok(""),
+            false => error(),
+        };
+    }
+
+    let (month, rest) = split_digits(&rest[1..]);
+    let (day, rest) = match rest.strip_prefix('-') {
+        Some(rest) => split_digits(rest),
+        None => return error(),
+    };
+    let (year, month, day): (u32, u32, u32) = match (year.parse(), month.parse(), day.parse()) {
+        (Ok(year), Ok(month), Ok(day)) => (year, month, day),
+        _ => return error(),
+    };
+
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err("month out of range".to_owned()),
+    };
+
+    match (1..=days_in_month).contains(&day) {
+        true => Ok(rest),
+        false => Err("day out of range".to_owned()),
+    }
+}
+
+/// Validates times in `HH:MM[:SS[.fff]]` format, returning the rest of the string.
+fn check_time(text: &str) -> Result<&str, String> {
+    let special = ["now", "allballs"];
+
+    if special.contains(&text.to_lowercase().as_str()) {
+        return Ok("");
+    }
+
+    let (hour, rest) = split_digits(text);
+    let (minute, rest) = match rest.strip_prefix(':') {
+        Some(rest) if !hour.is_empty() => split_digits(rest),
+        _ => {
+            return match text.contains(|c: char| c.is_ascii_digit()) {
+                true => Ok(""),
+                false => Err("invalid time".to_owned()),
+            }
+        }
+    };
+    let (second, rest) = match rest.strip_prefix(':') {
+        Some(rest) => split_digits(rest),
+        None => ("0", rest),
+    };
+    let (hour, minute, second): (u32, u32, u32) =
+        match (hour.parse(), minute.parse(), second.parse()) {
+            (Ok(hour), Ok(minute), Ok(second)) => (hour, minute, second),
+            _ => return Err("invalid time".to_owned()),
+        };
+
+    // Postgres allows `24:00:00` and leap seconds
+    match hour < 24 && minute < 60 && second <= 60 || (hour, minute, second) == (24, 0, 0) {
+        true => Ok(rest),
+        false => Err("time out of range".to_owned()),
+    }
+}
+
+fn check_timestamp(text: &str) -> CheckResult {
+    let rest = check_date(text)?;
+
+    match rest.strip_prefix(['T', ' ']) {
+        Some(time) if time.starts_with(|c: char| c.is_ascii_digit()) => {
+            check_time(time).map(|_| ())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_inet(text: &str) -> CheckResult {
+    let error = || Err("invalid IP address".to_owned());
+    let (address, prefix) = match text.split_once('/') {
+        Some((address, prefix)) => match prefix.parse::<u8>() {
+            Ok(prefix) => (address, Some(prefix)),
+            Err(_) => return error(),
+        },
+        None => (text, None),
+    };
+
+    let max_prefix = match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => 32,
+        Ok(IpAddr::V6(_)) => 128,
+        // IPv4 addresses can be abbreviated when followed by a prefix, eg. `10.1/16`
+        Err(_) if prefix.is_some() && is_abbreviated_ipv4(address) => 32,
+        Err(_) => return error(),
+    };
+
+    match prefix {
+        Some(prefix) if prefix > max_prefix => Err("network prefix out of range".to_owned()),
+        _ => Ok(()),
+    }
+}
+
+fn is_abbreviated_ipv4(address: &str) -> bool {
+    let octets: Vec<_> = address.split('.').collect();
+
+    octets.len() < 4 && octets.iter().all(|o| o.parse::<u8>().is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_value(value: Value, data_type: &str) -> CheckResult {
+        let enums = BTreeMap::from([(
+            "mood".to_owned(),
+            vec!["happy".to_owned(), "sad".to_owned()],
+        )]);
+
+        check(&value, data_type, &enums)
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Text(format!("'{}'", s))
+    }
+
+    fn number(s: &str) -> Value {
+        Value::Number(s.to_owned())
+    }
+
+    #[test]
+    fn test_valid_values() {
+        for (value, data_type) in [
+            (Value::Bool(true), "boolean"),
+            (Value::Bool(false), "text"),
+            (Value::Bool(true), "some_domain"),
+            (number("32767"), "smallint"),
+            (number("-32768"), "smallint"),
+            (number("1.4"), "integer"),
+            (number("9_223_372_036_854_775_807"), "bigint"),
+            (number("999.994"), "numeric(5,2)"),
+            (number("1e300"), "double precision"),
+            (number("12"), "character varying(2)"),
+            (text("of"), "boolean"),
+            (text(" 12 "), "integer"),
+            (text("NaN"), "numeric"),
+            (text("ab   "), "character varying(2)"),
+            (text("it''s"), "character(4)"),
+            (text("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11"), "uuid"),
+            (text("{a0eebc99-9c0b4ef8-bb6d6bb9-bd380a11}"), "uuid"),
+            (text("2024-02-29"), "date"),
+            (text("January 8, 1999"), "date"),
+            (text("today"), "date"),
+            (
+                text("2024-01-01T23:59:60.5Z"),
+                "timestamp(3) with time zone",
+            ),
+            (text("2024-01-01 24:00"), "timestamp without time zone"),
+            (text("infinity"), "timestamp with time zone"),
+            (text("04:05 PM"), "time without time zone"),
+            (text("192.168.0.1"), "inet"),
+            (text("10.1/16"), "inet"),
+            (text("::1/128"), "inet"),
+            (text("{\"a\": [1, 2]}"), "jsonb"),
+            (text("happy"), "mood"),
+            (text("anything"), "integer[]"),
+            (Value::SqlFragment("now()".to_owned()), "date"),
+        ] {
+            assert_eq!(check_value(value, data_type), Ok(()), "{}", data_type);
+        }
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        for (value, data_type) in [
+            (Value::Bool(true), "integer"),
+            (Value::Bool(true), "mood"),
+            (number("32768"), "smallint"),
+            (number("-2147483649"), "integer"),
+            (number("999.995"), "numeric(5,2)"),
+            (number("1000"), "numeric(3)"),
+            (number("123"), "character varying(2)"),
+            (number("1"), "boolean"),
+            (number("20200101"), "date"),
+            (number("1"), "jsonb"),
+            (text("maybe"), "boolean"),
+            (text("o"), "boolean"),
+            (text("1.5"), "integer"),
+            (text("one"), "numeric"),
+            (text("abc"), "character varying(2)"),
+            (text("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a1"), "uuid"),
+            (text("a0eebc99--9c0b-4ef8-bb6d-6bb9bd380a11"), "uuid"),
+            (text("2023-02-29"), "date"),
+            (text("2024-13-01"), "date"),
+            (text("tbd"), "date"),
+            (text("2024-01-01T25:00"), "timestamp with time zone"),
+            (text("12:60"), "time without time zone"),
+            (text("10"), "inet"),
+            (text("10.0.0.1/33"), "inet"),
+            (text("{\"a\": }"), "json"),
+            (text("angry"), "mood"),
+        ] {
+            assert!(check_value(value, data_type).is_err(), "{}", data_type);
+        }
+    }
+
+    #[test]
+    fn test_split_type() {
+        assert_eq!(split_type("integer"), ("integer".to_owned(), vec![]));
+        assert_eq!(
+            split_type("numeric(5,2)"),
+            ("numeric".to_owned(), vec![5, 2])
+        );
+        assert_eq!(
+            split_type("timestamp(3) with time zone"),
+            ("timestamp with time zone".to_owned(), vec![3])
+        );
+        assert_eq!(
+            split_type("character varying(20)[]"),
+            ("character varying[]".to_owned(), vec![20])
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum CatalogErrorKind {
+    Io(io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion { version: u64 },
+}
+
+impl fmt::Display for CatalogErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CatalogErrorKind::*;
+
+        match self {
+            Io(e) => write!(f, "{}", e),
+            Json(e) => write!(f, "invalid catalog snapshot: {}", e),
+            UnsupportedVersion { version } => write!(
+                f,
+                "unsupported catalog snapshot version {} (expected {}), take a new snapshot",
+                version,
+                super::VERSION,
+            ),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CatalogError {
+    pub kind: CatalogErrorKind,
+    pub path: PathBuf,
+}
+
+impl Error for CatalogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            CatalogErrorKind::Io(e) => Some(e),
+            CatalogErrorKind::Json(e) => Some(e),
+            CatalogErrorKind::UnsupportedVersion { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.kind)
+    }
+}
@@ -0,0 +1,388 @@
+//! Metadata about the schemas, tables, and columns in a database, used to
+//! validate data files before anything is inserted.
+//!
+//! The catalog can be saved as a JSON snapshot to validate against without a
+//! database connection. Snapshots are sorted by name (and columns by position)
+//! so that changes to them diff cleanly.
+
+pub mod error;
+
+use error::{CatalogError, CatalogErrorKind};
+use postgres::GenericClient;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::{fs, io::Write, path::Path};
+
+/// Version of the snapshot format, to be incremented whenever it changes
+/// in a way that older versions of hldr could not read.
+pub const VERSION: u64 = 1;
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Catalog {
+    /// Schemas searched for unqualified table names, in order
+    pub search_path: Vec<String>,
+    pub schemas: BTreeMap<String, Schema>,
+    /// Labels of enum types in order, keyed by the type name as used by columns
+    #[serde(default)]
+    pub enums: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Schema {
+    pub tables: BTreeMap<String, Table>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Table {
+    /// Columns in the order they are defined
+    pub columns: Vec<Column>,
+    #[serde(default)]
+    pub foreign_keys: Vec<ForeignKey>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Column {
+    pub name: String,
+    /// Type as formatted by Postgres, eg. `character varying(20)`
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    #[serde(default)]
+    pub identity: bool,
+    #[serde(default)]
+    pub generated: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct ForeignKey {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub references: ForeignKeyTarget,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct ForeignKeyTarget {
+    pub schema: String,
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+/// The catalog as saved to a file, along with the format version.
+#[derive(Deserialize, Serialize)]
+struct Snapshot<C> {
+    version: u64,
+    #[serde(flatten)]
+    catalog: C,
+}
+
+impl Column {
+    /// Whether the column must be given a value when inserting a row.
+    pub fn is_required(&self) -> bool {
+        !self.nullable && self.default.is_none() && !self.identity && !self.generated
+    }
+}
+
+impl Catalog {
+    /// Reads the catalog for every non-system schema in the database.
+    pub fn load(client: &mut impl GenericClient) -> Result<Self, postgres::Error> {
+        let mut catalog = Catalog {
+            search_path: client
+                .query_one("SELECT current_schemas(false)::text[]", &[])?
+                .get(0),
+            ..Catalog::default()
+        };
+
+        let schemas = client.query(
+            "SELECT nspname::text
+               FROM pg_namespace
+              WHERE nspname NOT LIKE 'pg\\_%'
+                AND nspname <> 'information_schema'",
+            &[],
+        )?;
+
+        for row in schemas {
+            catalog.schemas.insert(row.get(0), Schema::default());
+        }
+
+        let columns = client.query(
+            "SELECT n.nspname::text,
+                    c.relname::text,
+                    a.attname::text,
+                    format_type(a.atttypid, a.atttypmod),
+                    NOT a.attnotnull,
+                    pg_get_expr(d.adbin, d.adrelid),
+                    a.attidentity <> '',
+                    a.attgenerated <> ''
+               FROM pg_attribute a
+               JOIN pg_class c ON c.oid = a.attrelid
+               JOIN pg_namespace n ON n.oid = c.relnamespace
+               LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum
+              WHERE c.relkind IN ('r', 'p', 'v', 'f')
+                AND a.attnum > 0
+                AND NOT a.attisdropped
+                AND n.nspname NOT LIKE 'pg\\_%'
+                AND n.nspname <> 'information_schema'
+              ORDER BY n.nspname, c.relname, a.attnum",
+            &[],
+        )?;
+
+        for row in columns {
+            let generated: bool = row.get(7);
+
+            let column = Column {
+                name: row.get(2),
+                data_type: row.get(3),
+                nullable: row.get(4),
+                // Generation expressions are stored as defaults but aren't really
+                default: if generated { None } else { row.get(5) },
+                identity: row.get(6),
+                generated,
+            };
+
+            catalog
+                .schemas
+                .entry(row.get(0))
+                .or_default()
+                .tables
+                .entry(row.get(1))
+                .or_default()
+                .columns
+                .push(column);
+        }
+
+        let foreign_keys = client.query(
+            "SELECT n.nspname::text,
+                    c.relname::text,
+                    con.conname::text,
+                    ARRAY(SELECT a.attname::text
+                            FROM unnest(con.conkey) WITH ORDINALITY k(attnum, i)
+                            JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum
+                           ORDER BY k.i),
+                    fn.nspname::text,
+                    fc.relname::text,
+                    ARRAY(SELECT a.attname::text
+                            FROM unnest(con.confkey) WITH ORDINALITY k(attnum, i)
+                            JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum
+                           ORDER BY k.i)
+               FROM pg_constraint con
+               JOIN pg_class c ON c.oid = con.conrelid
+               JOIN pg_namespace n ON n.oid = c.relnamespace
+               JOIN pg_class fc ON fc.oid = con.confrelid
+               JOIN pg_namespace fn ON fn.oid = fc.relnamespace
+              WHERE con.contype = 'f'
+                AND n.nspname NOT LIKE 'pg\\_%'
+                AND n.nspname <> 'information_schema'
+              ORDER BY n.nspname, c.relname, con.conname",
+            &[],
+        )?;
+
+        for row in foreign_keys {
+            let foreign_key = ForeignKey {
+                name: row.get(2),
+                columns: row.get(3),
+                references: ForeignKeyTarget {
+                    schema: row.get(4),
+                    table: row.get(5),
+                    columns: row.get(6),
+                },
+            };
+
+            catalog
+                .schemas
+                .entry(row.get(0))
+                .or_default()
+                .tables
+                .entry(row.get(1))
+                .or_default()
+                .foreign_keys
+                .push(foreign_key);
+        }
+
+        let enums = client.query(
+            "SELECT format_type(t.oid, NULL),
+                    array_agg(e.enumlabel::text ORDER BY e.enumsortorder)
+               FROM pg_type t
+               JOIN pg_enum e ON e.enumtypid = t.oid
+              GROUP BY t.oid",
+            &[],
+        )?;
+
+        for row in enums {
+            catalog.enums.insert(row.get(0), row.get(1));
+        }
+
+        Ok(catalog)
+    }
+
+    /// Reads a snapshot previously written by `write_snapshot`.
+    pub fn read_snapshot(path: &Path) -> Result<Self, CatalogError> {
+        let error = |kind| CatalogError {
+            kind,
+            path: path.to_owned(),
+        };
+
+        let contents = fs::read_to_string(path).map_err(|e| error(CatalogErrorKind::Io(e)))?;
+
+        Self::from_snapshot(&contents).map_err(error)
+    }
+
+    fn from_snapshot(contents: &str) -> Result<Self, CatalogErrorKind> {
+        let value: serde_json::Value =
+            serde_json::from_str(contents).map_err(CatalogErrorKind::Json)?;
+
+        // Check the version before anything else, since the rest of the format may differ
+        match value.get("version").and_then(serde_json::Value::as_u64) {
+            Some(VERSION) => {}
+            Some(version) => return Err(CatalogErrorKind::UnsupportedVersion { version }),
+            None => {}
+        }
+
+        let snapshot: Snapshot<Catalog> =
+            serde_json::from_value(value).map_err(CatalogErrorKind::Json)?;
+
+        Ok(snapshot.catalog)
+    }
+
+    /// Writes the catalog as pretty-printed JSON, along with the format version.
+    pub fn write_snapshot(&self, mut writer: impl Write) -> serde_json::Result<()> {
+        let snapshot = Snapshot {
+            version: VERSION,
+            catalog: self,
+        };
+
+        serde_json::to_writer_pretty(&mut writer, &snapshot)?;
+        writeln!(writer).map_err(serde_json::Error::io)
+    }
+
+    /// Overrides the search path saved in a snapshot with one in the same format
+    /// as the `search_path` setting, eg. `schema1, "Schema 2"`.
+    pub fn set_search_path(&mut self, search_path: &str) {
+        self.search_path = search_path
+            .split(',')
+            .map(|schema| {
+                let schema = schema.trim();
+
+                match schema.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+                    Some(quoted) => quoted.replace("\"\"", "\""),
+                    None => schema.to_lowercase(),
+                }
+            })
+            .collect();
+    }
+
+    /// Finds a table by name, searching the search path if no schema is given,
+    /// and returns the schema the table is in along with it.
+    pub fn table(&self, schema: Option<&str>, name: &str) -> Option<(&str, &Table)> {
+        match schema {
+            Some(schema) => self
+                .schemas
+                .get_key_value(schema)
+                .and_then(|(schema, s)| Some((schema.as_str(), s.tables.get(name)?))),
+            None => self.search_path.iter().find_map(|schema| {
+                let table = self.schemas.get(schema)?.tables.get(name)?;
+                Some((schema.as_str(), table))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_lookup() {
+        let mut catalog = Catalog {
+            search_path: vec!["first".to_owned(), "second".to_owned()],
+            ..Catalog::default()
+        };
+
+        for (schema, table) in [
+            ("first", "a"),
+            ("second", "a"),
+            ("second", "b"),
+            ("third", "c"),
+        ] {
+            catalog
+                .schemas
+                .entry(schema.to_owned())
+                .or_default()
+                .tables
+                .insert(table.to_owned(), Table::default());
+        }
+
+        let schema = |s, t| catalog.table(s, t).map(|(schema, _)| schema);
+
+        assert_eq!(schema(None, "a"), Some("first"));
+        assert_eq!(schema(None, "b"), Some("second"));
+        assert_eq!(schema(None, "c"), None);
+        assert_eq!(schema(Some("second"), "a"), Some("second"));
+        assert_eq!(schema(Some("third"), "c"), Some("third"));
+        assert_eq!(schema(Some("third"), "a"), None);
+
+        catalog.set_search_path(r#"Second, "first""#);
+        assert_eq!(catalog.table(None, "a").unwrap().0, "second");
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut catalog = Catalog {
+            search_path: vec!["public".to_owned()],
+            ..Catalog::default()
+        };
+        let table = Table {
+            columns: vec![Column {
+                name: "id".to_owned(),
+                data_type: "integer".to_owned(),
+                nullable: false,
+                default: Some("nextval('users_id_seq'::regclass)".to_owned()),
+                identity: false,
+                generated: false,
+            }],
+            foreign_keys: vec![ForeignKey {
+                name: "users_org_id_fkey".to_owned(),
+                columns: vec!["org_id".to_owned()],
+                references: ForeignKeyTarget {
+                    schema: "public".to_owned(),
+                    table: "orgs".to_owned(),
+                    columns: vec!["id".to_owned()],
+                },
+            }],
+        };
+        catalog
+            .schemas
+            .entry("public".to_owned())
+            .or_default()
+            .tables
+            .insert("users".to_owned(), table);
+
+        let mut out = Vec::new();
+        catalog.write_snapshot(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("{\n  \"version\": 1,\n"));
+        assert_eq!(Catalog::from_snapshot(&out).unwrap(), catalog);
+    }
+
+    #[test]
+    fn test_snapshot_errors() {
+        assert!(matches!(
+            Catalog::from_snapshot(r#"{"version": 999, "something": "else"}"#),
+            Err(CatalogErrorKind::UnsupportedVersion { version: 999 })
+        ));
+        assert!(matches!(
+            Catalog::from_snapshot(r#"{"search_path": []}"#),
+            Err(CatalogErrorKind::Json(_))
+        ));
+        assert!(matches!(
+            Catalog::from_snapshot("nope"),
+            Err(CatalogErrorKind::Json(_))
+        ));
+    }
+}
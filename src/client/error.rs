@@ -0,0 +1,55 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug)]
+pub enum ClientErrorKind {
+    Config,
+    Connection,
+    Tls,
+}
+
+#[derive(Debug)]
+pub struct ClientError {
+    error: Box<dyn Error + Send + Sync>,
+    kind: ClientErrorKind,
+}
+
+impl ClientError {
+    pub fn config_error(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self {
+            kind: ClientErrorKind::Config,
+            error: error.into(),
+        }
+    }
+
+    pub fn connection_error(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self {
+            kind: ClientErrorKind::Connection,
+            error: error.into(),
+        }
+    }
+
+    pub fn tls_error(error: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self {
+            kind: ClientErrorKind::Tls,
+            error: error.into(),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ClientErrorKind::*;
+
+        match self.kind {
+            Config => write!(f, "Config error: {}", self.error),
+            Connection => write!(f, "Connection error: {}", self.error),
+            Tls => write!(f, "TLS error: {}", self.error),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Fallbacks for connection parameters that are missing from the connection string,
+//! following libpq's environment variables, connection service file, and password file.
+//!
+//! See https://www.postgresql.org/docs/current/libpq-envars.html
+
+use super::error::ClientError;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Lookup for environment variables, which can be swapped out in tests.
+pub(super) type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// Environment variables and the connection parameters they provide defaults for.
+const ENV_VARS: &[(&str, &str)] = &[
+    ("PGHOST", "host"),
+    ("PGPORT", "port"),
+    ("PGDATABASE", "dbname"),
+    ("PGUSER", "user"),
+    ("PGPASSWORD", "password"),
+    ("PGOPTIONS", "options"),
+    ("PGCONNECT_TIMEOUT", "connect_timeout"),
+    ("PGSSLMODE", "sslmode"),
+    ("PGSSLROOTCERT", "sslrootcert"),
+    ("PGSSLCERT", "sslcert"),
+    ("PGSSLKEY", "sslkey"),
+];
+
+pub(super) fn env_params(env: Env) -> Vec<(String, String)> {
+    ENV_VARS
+        .iter()
+        .filter_map(|(var, key)| env(var).map(|value| (key.to_string(), value)))
+        .collect()
+}
+
+/// Directories libpq is commonly built to read the system-wide service file from,
+/// since its default is compiled in, eg. `/etc/postgresql-common` on Debian.
+const SYSCONFDIRS: &[&str] = &[
+    "/etc/postgresql-common",
+    "/etc/sysconfig/pgsql",
+    "/usr/local/pgsql/etc",
+    "/etc",
+];
+
+fn home_file(env: Env, name: &str) -> Option<PathBuf> {
+    env("HOME").map(|home| PathBuf::from(home).join(name))
+}
+
+/// Returns the system-wide service file in `PGSYSCONFDIR`, or otherwise the candidates
+/// in the default directories.
+fn system_service_files(env: Env) -> Vec<PathBuf> {
+    match env("PGSYSCONFDIR") {
+        Some(dir) => vec![PathBuf::from(dir).join("pg_service.conf")],
+        None => SYSCONFDIRS
+            .iter()
+            .map(|dir| Path::new(dir).join("pg_service.conf"))
+            .collect(),
+    }
+}
+
+/// Looks up the parameters for the named service, first in the user's service file
+/// and then in the system-wide file, erroring if neither defines the service.
+pub(super) fn service_params(
+    service: &str,
+    env: Env,
+) -> Result<Vec<(String, String)>, ClientError> {
+    let user_file = env("PGSERVICEFILE")
+        .map(PathBuf::from)
+        .or_else(|| home_file(env, ".pg_service.conf"));
+
+    for path in user_file.into_iter().chain(system_service_files(env)) {
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => continue,
+        };
+
+        if let Some(params) = parse_service_file(&contents, service) {
+            log::debug!("Using service `{}` from {}", service, path.display());
+            return Ok(params);
+        }
+    }
+
+    Err(ClientError::config_error(format!(
+        "definition of service `{}` not found",
+        service
+    )))
+}
+
+/// Returns the parameters of the given service section in an INI-style service file.
+fn parse_service_file(contents: &str, service: &str) -> Option<Vec<(String, String)>> {
+    let mut params = None;
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            // Services cannot be split across sections, so there is no need to
+            // keep going after the matching section ends
+            if params.is_some() {
+                break;
+            }
+            if section == service {
+                params = Some(Vec::new());
+            }
+            continue;
+        }
+
+        if let (Some(params), Some((key, value))) = (&mut params, line.split_once('=')) {
+            params.push((key.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+
+    params
+}
+
+/// Looks up the password for the given connection in the password file.
+pub(super) fn pgpass_password(
+    host: &str,
+    port: u16,
+    dbname: &str,
+    user: &str,
+    env: Env,
+) -> Option<String> {
+    let path = env("PGPASSFILE")
+        .map(PathBuf::from)
+        .or_else(|| home_file(env, ".pgpass"))?;
+    let contents = fs::read_to_string(&path).ok()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Same as libpq, refuse to use a password file that others can read
+        let mode = fs::metadata(&path).ok()?.permissions().mode();
+
+        if mode & 0o077 != 0 {
+            log::warn!(
+                "password file {} has group or world access; permissions should be u=rw (0600) or less",
+                path.display()
+            );
+            return None;
+        }
+    }
+
+    parse_pgpass(&contents, host, port, dbname, user)
+}
+
+/// Finds the password of the first line matching the connection, where lines are in
+/// the format `hostname:port:database:username:password` and the first four fields
+/// can be `*` to match anything.
+fn parse_pgpass(contents: &str, host: &str, port: u16, dbname: &str, user: &str) -> Option<String> {
+    let port = port.to_string();
+    let expected = [host, port.as_str(), dbname, user];
+
+    for line in contents.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let fields = split_pgpass_line(line);
+
+        if fields.len() != 5 {
+            continue;
+        }
+
+        let matches = fields[..4]
+            .iter()
+            .zip(expected)
+            .all(|(field, expected)| field == "*" || field == expected);
+
+        if matches {
+            return Some(fields[4].clone());
+        }
+    }
+
+    None
+}
+
+/// Splits a password file line on colons, where colons and backslashes
+/// can be escaped with a backslash.
+fn split_pgpass_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    fields.last_mut().unwrap().push(c);
+                }
+            }
+            ':' if fields.len() < 5 => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_params() {
+        let env = |var: &str| match var {
+            "PGHOST" => Some("db.example.com".to_owned()),
+            "PGUSER" => Some("me".to_owned()),
+            "PGSSLMODE" => Some("require".to_owned()),
+            _ => None,
+        };
+
+        assert_eq!(
+            env_params(&env),
+            vec![
+                ("host".to_owned(), "db.example.com".to_owned()),
+                ("user".to_owned(), "me".to_owned()),
+                ("sslmode".to_owned(), "require".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_system_service_files() {
+        let env = |var: &str| match var {
+            "PGSYSCONFDIR" => Some("/opt/pg/etc".to_owned()),
+            _ => None,
+        };
+
+        assert_eq!(
+            system_service_files(&env),
+            vec![PathBuf::from("/opt/pg/etc/pg_service.conf")]
+        );
+
+        let files = system_service_files(&|_| None);
+
+        assert_eq!(files.len(), SYSCONFDIRS.len());
+        assert_eq!(
+            files[0],
+            PathBuf::from("/etc/postgresql-common/pg_service.conf")
+        );
+    }
+
+    #[test]
+    fn test_parse_service_file() {
+        let contents = "
+            # A comment
+            [first]
+            host=first.example.com
+
+            [second]
+            host = second.example.com
+            dbname=second_db
+            [third]
+            host=third.example.com
+        ";
+
+        assert_eq!(
+            parse_service_file(contents, "second"),
+            Some(vec![
+                ("host".to_owned(), "second.example.com".to_owned()),
+                ("dbname".to_owned(), "second_db".to_owned()),
+            ])
+        );
+        assert_eq!(parse_service_file(contents, "fourth"), None);
+    }
+
+    #[test]
+    fn test_parse_pgpass() {
+        let contents = "
+# A comment
+db.example.com:5432:app:me:first
+*:5433:*:me:second
+localhost:*:*:*:with\\:colon\\\\
+"
+        .trim_start();
+
+        for (host, port, dbname, user, expected) in [
+            ("db.example.com", 5432, "app", "me", Some("first")),
+            ("db.example.com", 5432, "other", "me", None),
+            ("elsewhere", 5433, "other", "me", Some("second")),
+            ("elsewhere", 5433, "other", "you", None),
+            ("localhost", 5432, "any", "anyone", Some("with:colon\\")),
+        ] {
+            assert_eq!(
+                parse_pgpass(contents, host, port, dbname, user).as_deref(),
+                expected,
+                "{} {} {} {}",
+                host,
+                port,
+                dbname,
+                user,
+            );
+        }
+    }
+}
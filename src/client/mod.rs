@@ -0,0 +1,670 @@
+pub mod error;
+mod libpq;
+#[cfg(feature = "tls")]
+mod pem;
+
+use error::ClientError;
+use libpq::Env;
+use postgres::{
+    config::{Config, Host},
+    Client, NoTls,
+};
+use serde::Deserialize;
+use std::{env, fmt, path::PathBuf, str::FromStr, time::Duration};
+
+/// TLS settings, named after and following the semantics of their `libpq` counterparts.
+///
+/// These can be supplied either in the options file or the connection string, although
+/// the postgres driver itself does not understand them and so they are removed from the
+/// connection string before it is parsed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct TlsOptions {
+    pub sslmode: Option<SslMode>,
+    pub sslrootcert: Option<PathBuf>,
+    pub sslcert: Option<PathBuf>,
+    pub sslkey: Option<PathBuf>,
+}
+
+impl TlsOptions {
+    /// Fills in any settings not present with those from `other`.
+    fn or(self, other: &TlsOptions) -> Self {
+        Self {
+            sslmode: self.sslmode.or(other.sslmode),
+            sslrootcert: self.sslrootcert.or_else(|| other.sslrootcert.clone()),
+            sslcert: self.sslcert.or_else(|| other.sslcert.clone()),
+            sslkey: self.sslkey.or_else(|| other.sslkey.clone()),
+        }
+    }
+
+    /// Sets the value for the given connection parameter, returning `false` if the
+    /// parameter is not TLS-related.
+    fn set(&mut self, key: &str, value: String) -> Result<bool, ClientError> {
+        match key {
+            "sslmode" => self.sslmode = Some(value.parse().map_err(ClientError::config_error)?),
+            "sslrootcert" => self.sslrootcert = Some(value.into()),
+            "sslcert" => self.sslcert = Some(value.into()),
+            "sslkey" => self.sslkey = Some(value.into()),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    /// Only try a non-TLS connection
+    Disable,
+    /// Try a TLS connection first without verifying the server certificate,
+    /// falling back to a non-TLS connection if the server does not support it
+    Prefer,
+    /// Only try a TLS connection, verifying the server certificate against
+    /// `sslrootcert` only if it is present
+    Require,
+    /// Only try a TLS connection, verifying the server certificate
+    VerifyCa,
+    /// Only try a TLS connection, verifying both the server certificate and that
+    /// the server host name matches the one in the certificate
+    VerifyFull,
+}
+
+impl SslMode {
+    fn requires_tls(&self) -> bool {
+        !matches!(self, SslMode::Disable | SslMode::Prefer)
+    }
+}
+
+impl FromStr for SslMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => Err(format!(
+                "invalid sslmode `{}`, expected one of disable, prefer, require, verify-ca, verify-full",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SslMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SslMode::Disable => write!(f, "disable"),
+            SslMode::Prefer => write!(f, "prefer"),
+            SslMode::Require => write!(f, "require"),
+            SslMode::VerifyCa => write!(f, "verify-ca"),
+            SslMode::VerifyFull => write!(f, "verify-full"),
+        }
+    }
+}
+
+/// Parameters that libpq understands but the postgres driver does not.
+#[derive(Debug, Default, PartialEq)]
+struct LibpqParams {
+    service: Option<String>,
+    tls: TlsOptions,
+}
+
+impl LibpqParams {
+    fn set(&mut self, key: &str, value: String) -> Result<bool, ClientError> {
+        match key {
+            "service" => {
+                self.service = Some(value);
+                Ok(true)
+            }
+            _ => self.tls.set(key, value),
+        }
+    }
+}
+
+pub fn new_client(connstr: &str, tls: &TlsOptions) -> Result<Client, ClientError> {
+    let (mut config, tls) = resolve(connstr, tls, &|var| env::var(var).ok())?;
+    let sslmode = tls.sslmode.unwrap_or(SslMode::Prefer);
+
+    config.application_name("hldr");
+
+    if config.get_connect_timeout().is_none() {
+        config.connect_timeout(Duration::new(30, 0));
+    }
+
+    config.ssl_mode(match sslmode {
+        SslMode::Disable => postgres::config::SslMode::Disable,
+        SslMode::Prefer => postgres::config::SslMode::Prefer,
+        _ => postgres::config::SslMode::Require,
+    });
+
+    if sslmode == SslMode::Disable {
+        return config.connect(NoTls).map_err(ClientError::connection_error);
+    }
+
+    connect_tls(&config, &tls, sslmode)
+}
+
+/// Resolves the connection configuration in the same order of precedence as libpq,
+/// with the connection string being the first of:
+///
+/// 1. The given connection string, ie. from the command line or options file
+/// 2. The `DATABASE_URL` environment variable
+///
+/// Any parameters missing from the connection string are then taken from, in order:
+///
+/// 1. The options file, for TLS settings
+/// 2. The connection service file, if a service is given by `service` or `PGSERVICE`
+/// 3. The `PGHOST`, `PGUSER`, etc. environment variables
+/// 4. The password file, for the password, and the current OS user, for the user
+fn resolve(connstr: &str, tls: &TlsOptions, env: Env) -> Result<(Config, TlsOptions), ClientError> {
+    let connstr = match connstr.is_empty() {
+        true => env("DATABASE_URL").unwrap_or_default(),
+        false => connstr.to_owned(),
+    };
+    let (connstr, params) = split_libpq_params(&connstr)?;
+
+    let mut config = Config::from_str(&connstr).map_err(ClientError::config_error)?;
+    let mut tls = params.tls.or(tls);
+    let mut defaults = Vec::new();
+
+    if let Some(service) = params.service.or_else(|| env("PGSERVICE")) {
+        defaults.extend(libpq::service_params(&service, env)?);
+    }
+
+    defaults.extend(libpq::env_params(env));
+
+    for (key, value) in defaults {
+        set_default(&mut config, &mut tls, &key, &value)?;
+    }
+
+    if config.get_user().is_none() {
+        if let Some(user) = env("USER").or_else(|| env("USERNAME")) {
+            config.user(&user);
+        }
+    }
+
+    if let (None, Some(user)) = (config.get_password(), config.get_user()) {
+        // libpq uses `localhost` in the password file for Unix socket connections
+        let host = match config.get_hosts().first() {
+            Some(Host::Tcp(host)) => host.as_str(),
+            _ => "localhost",
+        };
+        let port = config.get_ports().first().copied().unwrap_or(5432);
+        let dbname = config.get_dbname().unwrap_or(user);
+
+        if let Some(password) = libpq::pgpass_password(host, port, dbname, user, env) {
+            config.password(password);
+        }
+    }
+
+    Ok((config, tls))
+}
+
+/// Sets the connection parameter only if it has not already been set.
+fn set_default(
+    config: &mut Config,
+    tls: &mut TlsOptions,
+    key: &str,
+    value: &str,
+) -> Result<(), ClientError> {
+    let invalid =
+        |key: &str| ClientError::config_error(format!("invalid value for `{}`: {}", key, value));
+
+    match key {
+        "host" if config.get_hosts().is_empty() => {
+            for host in value.split(',') {
+                config.host(host);
+            }
+        }
+        "port" if config.get_ports().is_empty() => {
+            for port in value.split(',') {
+                config.port(port.parse().map_err(|_| invalid(key))?);
+            }
+        }
+        "dbname" if config.get_dbname().is_none() => {
+            config.dbname(value);
+        }
+        "user" if config.get_user().is_none() => {
+            config.user(value);
+        }
+        "password" if config.get_password().is_none() => {
+            config.password(value);
+        }
+        "options" if config.get_options().is_none() => {
+            config.options(value);
+        }
+        "connect_timeout" if config.get_connect_timeout().is_none() => {
+            let seconds: u64 = value.parse().map_err(|_| invalid(key))?;
+            config.connect_timeout(Duration::from_secs(seconds));
+        }
+        "sslmode" | "sslrootcert" | "sslcert" | "sslkey" => {
+            let mut defaults = TlsOptions::default();
+            defaults.set(key, value.to_owned())?;
+            *tls = std::mem::take(tls).or(&defaults);
+        }
+        "host" | "port" | "dbname" | "user" | "password" | "options" | "connect_timeout" => {}
+        _ => log::warn!("Ignoring unsupported connection parameter `{}`", key),
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "tls")]
+fn connect_tls(config: &Config, tls: &TlsOptions, sslmode: SslMode) -> Result<Client, ClientError> {
+    use native_tls::{Certificate, TlsConnector};
+    use postgres_native_tls::MakeTlsConnector;
+
+    let mut builder = TlsConnector::builder();
+
+    if let Some(path) = &tls.sslrootcert {
+        let cert = Certificate::from_pem(&read_tls_file(path)?).map_err(ClientError::tls_error)?;
+        builder.add_root_certificate(cert);
+    }
+
+    match (&tls.sslcert, &tls.sslkey) {
+        (Some(cert), Some(key)) => {
+            builder.identity(identity(cert, key)?);
+        }
+        (None, None) => {}
+        _ => {
+            return Err(ClientError::tls_error(
+                "sslcert and sslkey must be supplied together",
+            ));
+        }
+    }
+
+    // As with libpq, `require` only verifies the server certificate when given a root
+    // certificate to verify against, and only `verify-full` checks the host name
+    let verify_ca = match sslmode {
+        SslMode::Require => tls.sslrootcert.is_some(),
+        _ => sslmode.requires_tls(),
+    };
+    let verify_host = sslmode == SslMode::VerifyFull;
+
+    builder.danger_accept_invalid_certs(!verify_ca);
+    builder.danger_accept_invalid_hostnames(!verify_host);
+
+    let connector = builder.build().map_err(ClientError::tls_error)?;
+
+    config
+        .connect(MakeTlsConnector::new(connector))
+        .map_err(ClientError::connection_error)
+}
+
+/// Loads a PEM-encoded client certificate and private key, where the key can be in
+/// PKCS#8, PKCS#1 (RSA only) or SEC1 (EC only) format.
+#[cfg(feature = "tls")]
+fn identity(
+    cert: &std::path::Path,
+    key: &std::path::Path,
+) -> Result<native_tls::Identity, ClientError> {
+    let pkcs8 = pem::to_pkcs8(&read_tls_file(key)?)
+        .map_err(|e| ClientError::tls_error(format!("{}: {}", key.display(), e)))?;
+
+    native_tls::Identity::from_pkcs8(&read_tls_file(cert)?, &pkcs8).map_err(ClientError::tls_error)
+}
+
+#[cfg(feature = "tls")]
+fn read_tls_file(path: &std::path::Path) -> Result<Vec<u8>, ClientError> {
+    std::fs::read(path).map_err(|e| ClientError::tls_error(format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(not(feature = "tls"))]
+fn connect_tls(
+    config: &Config,
+    _tls: &TlsOptions,
+    sslmode: SslMode,
+) -> Result<Client, ClientError> {
+    if sslmode.requires_tls() {
+        return Err(ClientError::tls_error(format!(
+            "sslmode `{}` requires hldr to be built with the `tls` feature",
+            sslmode
+        )));
+    }
+
+    // Without a TLS connector, `prefer` simply connects without TLS
+    config.connect(NoTls).map_err(ClientError::connection_error)
+}
+
+/// Removes parameters that the postgres driver does not understand from either a
+/// key/value or URI style connection string, returning the remaining string and
+/// the parameters.
+///
+/// Malformed connection strings are returned as-is so that the driver can
+/// report the appropriate error.
+fn split_libpq_params(connstr: &str) -> Result<(String, LibpqParams), ClientError> {
+    let mut params = LibpqParams::default();
+
+    if connstr.starts_with("postgres://") || connstr.starts_with("postgresql://") {
+        let (base, query) = match connstr.split_once('?') {
+            Some(parts) => parts,
+            None => return Ok((connstr.to_owned(), params)),
+        };
+        let mut kept = Vec::new();
+
+        for param in query.split('&') {
+            let (key, value) = param.split_once('=').unwrap_or((param, ""));
+            let value = match percent_decode(value) {
+                Some(value) => value,
+                None => return Ok((connstr.to_owned(), LibpqParams::default())),
+            };
+
+            if !params.set(key, value)? {
+                kept.push(param);
+            }
+        }
+
+        let connstr = match kept.is_empty() {
+            true => base.to_owned(),
+            false => format!("{}?{}", base, kept.join("&")),
+        };
+
+        return Ok((connstr, params));
+    }
+
+    let mut kept = Vec::new();
+
+    for pair in KeyValuePairs::new(connstr) {
+        let (key, value, raw) = match pair {
+            Some(pair) => pair,
+            None => return Ok((connstr.to_owned(), LibpqParams::default())),
+        };
+
+        if !params.set(key, value)? {
+            kept.push(raw);
+        }
+    }
+
+    Ok((kept.join(" "), params))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+
+    while let Some(b) = iter.next() {
+        match b {
+            b'%' => {
+                let hex = [iter.next()?, iter.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            }
+            b => bytes.push(b),
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Iterator over `key = value` pairs in a key/value style connection string, yielding
+/// the key, the unescaped value, and the raw text of the pair, or `None` if the string
+/// is malformed.
+struct KeyValuePairs<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> KeyValuePairs<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.advance();
+        }
+    }
+
+    fn pair(&mut self, start: usize) -> Option<(&'a str, String, &'a str)> {
+        while matches!(self.peek(), Some(c) if c != '=' && !c.is_whitespace()) {
+            self.advance();
+        }
+
+        let key = &self.input[start..self.position];
+
+        self.skip_whitespace();
+
+        if key.is_empty() || self.advance()? != '=' {
+            return None;
+        }
+
+        self.skip_whitespace();
+
+        let mut value = String::new();
+        let quoted = self.peek() == Some('\'');
+
+        if quoted {
+            self.advance();
+        }
+
+        loop {
+            match self.peek() {
+                Some('\'') if quoted => {
+                    self.advance();
+                    break;
+                }
+                Some(c) if c.is_whitespace() && !quoted => break,
+                Some('\\') => {
+                    self.advance();
+                    value.push(self.advance()?);
+                }
+                Some(c) => {
+                    self.advance();
+                    value.push(c);
+                }
+                None if quoted => return None,
+                None => break,
+            }
+        }
+
+        Some((key, value, &self.input[start..self.position]))
+    }
+}
+
+impl<'a> Iterator for KeyValuePairs<'a> {
+    type Item = Option<(&'a str, String, &'a str)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some(_) => Some(self.pair(self.position)),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(connstr: &str) -> (String, TlsOptions) {
+        let (connstr, params) = split_libpq_params(connstr).unwrap();
+        (connstr, params.tls)
+    }
+
+    #[test]
+    fn test_split_key_value_params() {
+        let (connstr, tls) = split(concat!(
+            "host=localhost sslmode = verify-full  sslrootcert='/certs/root ca.crt' ",
+            "options='-c search_path=a,b' sslcert=/certs/client\\ cert.crt sslkey=/certs/client.key",
+        ));
+
+        assert_eq!(connstr, "host=localhost options='-c search_path=a,b'");
+        assert_eq!(
+            tls,
+            TlsOptions {
+                sslmode: Some(SslMode::VerifyFull),
+                sslrootcert: Some(PathBuf::from("/certs/root ca.crt")),
+                sslcert: Some(PathBuf::from("/certs/client cert.crt")),
+                sslkey: Some(PathBuf::from("/certs/client.key")),
+            }
+        );
+    }
+
+    #[test]
+    fn test_split_uri_params() {
+        let (connstr, tls) =
+            split("postgresql://me@localhost/db?sslmode=require&connect_timeout=5&sslrootcert=%2Froot.crt");
+
+        assert_eq!(connstr, "postgresql://me@localhost/db?connect_timeout=5");
+        assert_eq!(
+            tls,
+            TlsOptions {
+                sslmode: Some(SslMode::Require),
+                sslrootcert: Some(PathBuf::from("/root.crt")),
+                ..TlsOptions::default()
+            }
+        );
+
+        let (connstr, _) = split("postgres://localhost/db?sslmode=disable");
+        assert_eq!(connstr, "postgres://localhost/db");
+    }
+
+    #[test]
+    fn test_split_without_params() {
+        for connstr in [
+            "",
+            "host=localhost user=me",
+            "postgres://localhost",
+            "user='unclosed",
+        ] {
+            assert_eq!(split(connstr), (connstr.to_owned(), TlsOptions::default()));
+        }
+    }
+
+    #[test]
+    fn test_invalid_sslmode() {
+        assert!(split_libpq_params("sslmode=allow").is_err());
+        assert!(split_libpq_params("postgres://localhost?sslmode=nope").is_err());
+    }
+
+    #[test]
+    fn test_connection_string_takes_precedence() {
+        let file = TlsOptions {
+            sslmode: Some(SslMode::Require),
+            sslrootcert: Some(PathBuf::from("file.crt")),
+            ..TlsOptions::default()
+        };
+        let (_, connstr) = split("sslmode=verify-ca");
+
+        assert_eq!(
+            connstr.or(&file),
+            TlsOptions {
+                sslmode: Some(SslMode::VerifyCa),
+                sslrootcert: Some(PathBuf::from("file.crt")),
+                ..TlsOptions::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_split_service() {
+        let (connstr, params) = split_libpq_params("service=app user=me").unwrap();
+
+        assert_eq!(connstr, "user=me");
+        assert_eq!(params.service.as_deref(), Some("app"));
+    }
+
+    fn resolve_with(
+        connstr: &str,
+        vars: &[(&str, &str)],
+    ) -> Result<(Config, TlsOptions), ClientError> {
+        let env = |var: &str| {
+            vars.iter()
+                .find(|(name, _)| *name == var)
+                .map(|(_, value)| value.to_string())
+        };
+
+        resolve(connstr, &TlsOptions::default(), &env)
+    }
+
+    #[test]
+    fn test_resolve_database_url() {
+        let vars = [("DATABASE_URL", "postgres://me@db.example.com/app")];
+
+        let (config, _) = resolve_with("", &vars).unwrap();
+        assert_eq!(config.get_dbname(), Some("app"));
+
+        let (config, _) = resolve_with("dbname=other", &vars).unwrap();
+        assert_eq!(config.get_dbname(), Some("other"));
+    }
+
+    #[test]
+    fn test_resolve_env_vars() {
+        let vars = [
+            ("PGHOST", "one.example.com,two.example.com"),
+            ("PGPORT", "5433"),
+            ("PGUSER", "env_user"),
+            ("PGDATABASE", "env_db"),
+            ("PGSSLMODE", "require"),
+            ("USER", "os_user"),
+        ];
+
+        let (config, tls) = resolve_with("user=me", &vars).unwrap();
+
+        assert_eq!(
+            config.get_hosts(),
+            &[
+                Host::Tcp("one.example.com".to_owned()),
+                Host::Tcp("two.example.com".to_owned())
+            ]
+        );
+        assert_eq!(config.get_ports(), &[5433]);
+        assert_eq!(config.get_user(), Some("me"));
+        assert_eq!(config.get_dbname(), Some("env_db"));
+        assert_eq!(tls.sslmode, Some(SslMode::Require));
+
+        let (config, _) = resolve_with("", &vars[..1]).unwrap();
+        assert_eq!(config.get_user(), None);
+
+        let (config, _) = resolve_with("", &vars[5..]).unwrap();
+        assert_eq!(config.get_user(), Some("os_user"));
+    }
+
+    #[test]
+    fn test_resolve_invalid_env_var() {
+        assert!(resolve_with("", &[("PGPORT", "nope")]).is_err());
+    }
+
+    #[test]
+    fn test_resolve_missing_service() {
+        assert!(resolve_with("service=nope", &[]).is_err());
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn test_identity() {
+        let testdata = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/client/testdata");
+
+        for (cert, key) in [
+            ("rsa.crt", "rsa.key"),
+            ("rsa.crt", "rsa-pkcs8.key"),
+            ("ec.crt", "ec.key"),
+            ("ec.crt", "ec-pkcs8.key"),
+        ] {
+            let result = identity(&testdata.join(cert), &testdata.join(key));
+            assert!(result.is_ok(), "{}: {:?}", key, result.err());
+        }
+
+        let error = |key: &str| match identity(&testdata.join("ec.crt"), &testdata.join(key)) {
+            Ok(_) => panic!("{}: expected error", key),
+            Err(error) => error.to_string(),
+        };
+
+        assert!(error("ec.crt").ends_with("ec.crt: no private key found"));
+        assert!(error("missing.key").contains("missing.key"));
+    }
+}
@@ -1,16 +1,21 @@
 use postgres;
+use serde::Serialize;
 use std::error::Error;
 use std::fmt;
 use std::io;
+use std::path::{Path, PathBuf};
 
-use crate::{analyzer, lexer, loader, parser};
+use crate::{analyzer, catalog, client, lexer, loader, manifest, parser, Position};
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum HldrErrorKind {
     IoError,
     LexError,
     ParseError,
     ValidateError,
+    SchemaError,
+    CatalogError,
+    ManifestError,
     ClientError,
     LoadError,
     GeneralDatabaseError,
@@ -20,6 +25,16 @@ pub enum HldrErrorKind {
 pub struct HldrError {
     pub kind: HldrErrorKind,
     pub error: Box<dyn Error>,
+    pub position: Option<Position>,
+    /// The data file being processed when the error occurred, if any
+    pub file: Option<PathBuf>,
+}
+
+impl HldrError {
+    pub(crate) fn in_file(mut self, file: &Path) -> Self {
+        self.file = Some(file.to_owned());
+        self
+    }
 }
 
 impl From<io::Error> for HldrError {
@@ -27,6 +42,8 @@ impl From<io::Error> for HldrError {
         HldrError {
             kind: HldrErrorKind::IoError,
             error: Box::new(error),
+            position: None,
+            file: None,
         }
     }
 }
@@ -36,24 +53,34 @@ impl From<postgres::error::Error> for HldrError {
         HldrError {
             kind: HldrErrorKind::GeneralDatabaseError,
             error: Box::new(error),
+            position: None,
+            file: None,
         }
     }
 }
 
 impl From<lexer::error::LexError> for HldrError {
     fn from(error: lexer::error::LexError) -> Self {
+        let position = Some(error.position);
+
         HldrError {
             kind: HldrErrorKind::LexError,
             error: Box::new(error),
+            position,
+            file: None,
         }
     }
 }
 
 impl From<parser::error::ParseError> for HldrError {
     fn from(error: parser::error::ParseError) -> Self {
+        let position = error.position();
+
         HldrError {
             kind: HldrErrorKind::ParseError,
             error: Box::new(error),
+            position,
+            file: None,
         }
     }
 }
@@ -63,24 +90,67 @@ impl From<analyzer::error::AnalyzeError> for HldrError {
         HldrError {
             kind: HldrErrorKind::ValidateError,
             error: Box::new(error),
+            position: None,
+            file: None,
+        }
+    }
+}
+
+impl From<analyzer::error::SchemaErrors> for HldrError {
+    fn from(error: analyzer::error::SchemaErrors) -> Self {
+        let position = error.0.first().map(|e| e.position);
+
+        HldrError {
+            kind: HldrErrorKind::SchemaError,
+            error: Box::new(error),
+            position,
+            file: None,
         }
     }
 }
 
-impl From<loader::error::ClientError> for HldrError {
-    fn from(error: loader::error::ClientError) -> Self {
+impl From<catalog::error::CatalogError> for HldrError {
+    fn from(error: catalog::error::CatalogError) -> Self {
+        HldrError {
+            kind: HldrErrorKind::CatalogError,
+            error: Box::new(error),
+            position: None,
+            file: None,
+        }
+    }
+}
+
+impl From<manifest::error::ManifestError> for HldrError {
+    fn from(error: manifest::error::ManifestError) -> Self {
+        HldrError {
+            kind: HldrErrorKind::ManifestError,
+            error: Box::new(error),
+            position: None,
+            file: None,
+        }
+    }
+}
+
+impl From<client::error::ClientError> for HldrError {
+    fn from(error: client::error::ClientError) -> Self {
         HldrError {
             kind: HldrErrorKind::ClientError,
             error: Box::new(error),
+            position: None,
+            file: None,
         }
     }
 }
 
 impl From<loader::error::LoadError> for HldrError {
     fn from(error: loader::error::LoadError) -> Self {
+        let position = Some(error.position);
+
         HldrError {
             kind: HldrErrorKind::LoadError,
             error: Box::new(error),
+            position,
+            file: None,
         }
     }
 }
@@ -93,6 +163,9 @@ impl Error for HldrError {
 
 impl fmt::Display for HldrError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.error.fmt(f)
+        match &self.file {
+            Some(file) => write!(f, "{}: {}", file.display(), self.error),
+            None => self.error.fmt(f),
+        }
     }
 }
@@ -0,0 +1,428 @@
+//! Fake values for realistic demo data, eg. `fake.email()`, generated locally from a
+//! seeded random number generator so that a run can be reproduced with its seed.
+
+mod words;
+
+use crate::parser::nodes::{Generator, Value};
+use crate::rng::Rng;
+use words::*;
+
+/// What a generator call generates, with its arguments checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fake {
+    Address,
+    City,
+    Company,
+    Country,
+    Date { from: i64, to: i64 },
+    Email,
+    FirstName,
+    Int { min: i64, max: i64 },
+    LastName,
+    Name,
+    Paragraph,
+    Phone,
+    Postcode,
+    Sentence,
+    StreetAddress,
+    Timestamp { from: i64, to: i64 },
+    Username,
+    Uuid,
+    Word,
+    Words(i64),
+}
+
+/// Every generator's signature, with any constraints on its arguments.
+const SIGNATURES: &[(&str, &str, &str)] = &[
+    ("address", "address()", ""),
+    ("city", "city()", ""),
+    ("company", "company()", ""),
+    ("country", "country()", ""),
+    (
+        "date",
+        "date(from_year, to_year)",
+        " with years from 1 to 9999 in order",
+    ),
+    ("email", "email()", ""),
+    ("first_name", "first_name()", ""),
+    ("int", "int(min, max)", " with min no greater than max"),
+    ("last_name", "last_name()", ""),
+    ("name", "name()", ""),
+    ("paragraph", "paragraph()", ""),
+    ("phone", "phone()", ""),
+    ("postcode", "postcode()", ""),
+    ("sentence", "sentence()", ""),
+    ("street_address", "street_address()", ""),
+    (
+        "timestamp",
+        "timestamp(from_year, to_year)",
+        " with years from 1 to 9999 in order",
+    ),
+    ("username", "username()", ""),
+    ("uuid", "uuid()", ""),
+    ("word", "word()", ""),
+    ("words", "words(count)", " with a count from 1 to 1000"),
+];
+
+impl Fake {
+    /// Resolves the generator call, returning why its name or arguments are invalid if so.
+    pub fn resolve(generator: &Generator) -> Result<Self, String> {
+        let name = generator.name.as_str();
+        let (signature, constraints) = match SIGNATURES.iter().find(|(n, ..)| *n == name) {
+            Some((_, signature, constraints)) => (signature, constraints),
+            None => return Err(format!("unknown generator `fake.{}`", name)),
+        };
+        let invalid = || Err(format!("expected `fake.{}`{}", signature, constraints));
+
+        let arguments: Option<Vec<i64>> = generator
+            .arguments
+            .iter()
+            .map(|argument| match argument {
+                Value::Number(n) => n.replace('_', "").parse().ok(),
+                _ => None,
+            })
+            .collect();
+        let arguments = match arguments {
+            Some(arguments) => arguments,
+            None => return invalid(),
+        };
+        let year = |year: i64| (1..=9999).contains(&year);
+
+        let fake = match (name, &arguments[..]) {
+            ("address", []) => Fake::Address,
+            ("city", []) => Fake::City,
+            ("company", []) => Fake::Company,
+            ("country", []) => Fake::Country,
+            ("date", &[from, to]) if year(from) && year(to) && from <= to => {
+                Fake::Date { from, to }
+            }
+            ("email", []) => Fake::Email,
+            ("first_name", []) => Fake::FirstName,
+            ("int", &[min, max]) if min <= max => Fake::Int { min, max },
+            ("last_name", []) => Fake::LastName,
+            ("name", []) => Fake::Name,
+            ("paragraph", []) => Fake::Paragraph,
+            ("phone", []) => Fake::Phone,
+            ("postcode", []) => Fake::Postcode,
+            ("sentence", []) => Fake::Sentence,
+            ("street_address", []) => Fake::StreetAddress,
+            ("timestamp", &[from, to]) if year(from) && year(to) && from <= to => {
+                Fake::Timestamp { from, to }
+            }
+            ("username", []) => Fake::Username,
+            ("uuid", []) => Fake::Uuid,
+            ("word", []) => Fake::Word,
+            ("words", &[count]) if (1..=1000).contains(&count) => Fake::Words(count),
+            _ => return invalid(),
+        };
+
+        Ok(fake)
+    }
+}
+
+impl Fake {
+    /// Generates the value as a SQL literal.
+    pub fn generate(self, rng: &mut Rng) -> String {
+        match self {
+            Fake::Int { min, max } => rng.range(min, max).to_string(),
+            fake => quote(&fake.text(rng)),
+        }
+    }
+
+    fn text(self, rng: &mut Rng) -> String {
+        match self {
+            Fake::Address => {
+                let street = Fake::StreetAddress.text(rng);
+                let city = rng.choose(CITIES);
+                format!("{}, {} {}", street, city, Fake::Postcode.text(rng))
+            }
+            Fake::City => rng.choose(CITIES).to_owned(),
+            Fake::Company => {
+                let name = rng.choose(LAST_NAMES);
+                format!("{} {}", name, rng.choose(COMPANY_SUFFIXES))
+            }
+            Fake::Country => rng.choose(COUNTRIES).to_owned(),
+            Fake::Date { from, to } => {
+                let (year, month, day) = random_day(rng, from, to);
+                format!("{:04}-{:02}-{:02}", year, month, day)
+            }
+            Fake::Email => {
+                let first = handle(rng.choose(FIRST_NAMES));
+                let last = handle(rng.choose(LAST_NAMES));
+                let number = rng.range(1, 999);
+                format!("{}.{}{}@{}", first, last, number, rng.choose(DOMAINS))
+            }
+            Fake::FirstName => rng.choose(FIRST_NAMES).to_owned(),
+            Fake::Int { min, max } => rng.range(min, max).to_string(),
+            Fake::LastName => rng.choose(LAST_NAMES).to_owned(),
+            Fake::Name => {
+                let first = rng.choose(FIRST_NAMES);
+                format!("{} {}", first, rng.choose(LAST_NAMES))
+            }
+            Fake::Paragraph => {
+                let count = rng.range(3, 6);
+                let sentences: Vec<_> = (0..count).map(|_| Fake::Sentence.text(rng)).collect();
+                sentences.join(" ")
+            }
+            Fake::Phone => {
+                let exchange = rng.range(100, 999);
+                format!("555-{:03}-{:04}", exchange, rng.range(0, 9999))
+            }
+            Fake::Postcode => format!("{:05}", rng.range(10000, 99999)),
+            Fake::Sentence => {
+                let count = rng.range(6, 12);
+                let mut sentence = Fake::Words(count).text(rng);
+                sentence[..1].make_ascii_uppercase();
+                sentence.push('.');
+                sentence
+            }
+            Fake::StreetAddress => {
+                let number = rng.range(1, 9999);
+                let street = rng.choose(STREET_NAMES);
+                format!("{} {} {}", number, street, rng.choose(STREET_SUFFIXES))
+            }
+            Fake::Timestamp { from, to } => {
+                let (year, month, day) = random_day(rng, from, to);
+                let seconds = rng.range(0, 86_399);
+                format!(
+                    "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                    year,
+                    month,
+                    day,
+                    seconds / 3600,
+                    seconds / 60 % 60,
+                    seconds % 60
+                )
+            }
+            Fake::Username => {
+                let first = handle(rng.choose(FIRST_NAMES));
+                let last = handle(rng.choose(LAST_NAMES));
+                format!("{}_{}{}", first, last, rng.range(1, 99))
+            }
+            Fake::Uuid => {
+                let mut bytes = [0; 16];
+                bytes[..8].copy_from_slice(&rng.next_u64().to_be_bytes());
+                bytes[8..].copy_from_slice(&rng.next_u64().to_be_bytes());
+
+                // Version 4 (random) in the RFC 4122 variant
+                bytes[6] = (bytes[6] & 0x0f) | 0x40;
+                bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+                let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                )
+            }
+            Fake::Word => rng.choose(LOREM).to_owned(),
+            Fake::Words(count) => {
+                let words: Vec<_> = (0..count).map(|_| rng.choose(LOREM)).collect();
+                words.join(" ")
+            }
+        }
+    }
+}
+
+/// Returns a day from the start of the first year to the end of the last.
+fn random_day(rng: &mut Rng, from_year: i64, to_year: i64) -> (i64, i64, i64) {
+    let first = days_from_civil(from_year, 1, 1);
+    let last = days_from_civil(to_year, 12, 31);
+
+    civil_from_days(rng.range(first, last))
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// Lowercases the name for use in emails and usernames, eg. `O'Brien` to `obrien`.
+fn handle(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Days since 1970-01-01 of the date in the proleptic Gregorian calendar, using
+/// the algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`, returning the year, month, and day.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(name: &str, arguments: &[&str]) -> Generator {
+        Generator {
+            name: name.to_owned(),
+            arguments: arguments
+                .iter()
+                .map(|argument| match argument.starts_with('\'') {
+                    true => Value::Text(argument.to_string()),
+                    false => Value::Number(argument.to_string()),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Fake::resolve(&generator("email", &[])), Ok(Fake::Email));
+        assert_eq!(
+            Fake::resolve(&generator("date", &["1950", "2_005"])),
+            Ok(Fake::Date {
+                from: 1950,
+                to: 2005
+            })
+        );
+        assert_eq!(
+            Fake::resolve(&generator("int", &["-5", "-5"])),
+            Ok(Fake::Int { min: -5, max: -5 })
+        );
+
+        assert_eq!(
+            Fake::resolve(&generator("emial", &[])),
+            Err("unknown generator `fake.emial`".to_owned())
+        );
+        assert_eq!(
+            Fake::resolve(&generator("email", &["1"])),
+            Err("expected `fake.email()`".to_owned())
+        );
+
+        for (name, arguments) in [
+            ("date", &["1950"][..]),
+            ("date", &["2005", "1950"]),
+            ("date", &["0", "1950"]),
+            ("date", &["'1950'", "'2005'"]),
+            ("int", &["1.5", "2"]),
+            ("words", &["0"]),
+            ("words", &[]),
+        ] {
+            let result = Fake::resolve(&generator(name, arguments));
+            assert!(result.is_err(), "{} {:?}", name, arguments);
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_values() {
+        let fakes = [
+            Fake::Name,
+            Fake::Email,
+            Fake::Address,
+            Fake::Paragraph,
+            Fake::Uuid,
+            Fake::Timestamp {
+                from: 1950,
+                to: 2005,
+            },
+        ];
+        let values = |seed| {
+            let mut rng = Rng::new(seed);
+            fakes.map(|fake| fake.generate(&mut rng))
+        };
+
+        assert_eq!(values(42), values(42));
+        assert_ne!(values(42), values(43));
+    }
+
+    #[test]
+    fn test_stable_values() {
+        // Changing the values generated for a seed would break snapshots of seeded runs
+        let mut rng = Rng::new(42);
+        let values = [
+            Fake::Name,
+            Fake::Email,
+            Fake::Address,
+            Fake::Date {
+                from: 1950,
+                to: 2005,
+            },
+            Fake::Uuid,
+            Fake::Sentence,
+        ]
+        .map(|fake| fake.generate(&mut rng));
+
+        assert_eq!(
+            values,
+            [
+                "'Elena Alvarez'",
+                "'carlos.fischer377@example.com'",
+                "'9083 Lake Boulevard, Brookside 54207'",
+                "'1974-02-16'",
+                "'836ded89-7f3e-46e6-851f-977347ed6db7'",
+                "'Velit voluptate fugiat aute aliqua reprehenderit.'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_values() {
+        let mut rng = Rng::new(7);
+
+        for _ in 0..1000 {
+            let date = Fake::Date {
+                from: 1999,
+                to: 2000,
+            }
+            .generate(&mut rng);
+            assert!(
+                date.starts_with("'1999-") || date.starts_with("'2000-"),
+                "{}",
+                date
+            );
+
+            let int: i64 = Fake::Int { min: -2, max: 2 }
+                .generate(&mut rng)
+                .parse()
+                .unwrap();
+            assert!((-2..=2).contains(&int));
+
+            let uuid = Fake::Uuid.generate(&mut rng);
+            assert_eq!(uuid.len(), 38);
+            assert_eq!(&uuid[15..16], "4");
+
+            // Quotes in names are escaped
+            let name = Fake::LastName.generate(&mut rng);
+            assert_eq!(name.matches('\'').count() % 2, 0, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+
+        for days in [-719_162, -1, 0, 59, 11_016, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
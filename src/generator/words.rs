@@ -0,0 +1,157 @@
+//! Word lists the generators pick from, kept in the source so that the same seed
+//! always produces the same values regardless of any dependency's data.
+
+pub(super) const FIRST_NAMES: &[&str] = &[
+    "Ada", "Alan", "Alice", "Amara", "Andre", "Ava", "Benjamin", "Bianca", "Carlos", "Chen",
+    "Chloe", "Daniel", "Diego", "Elena", "Emma", "Farah", "Felix", "Grace", "Hannah", "Hiro",
+    "Isabel", "Ivan", "James", "Jasmine", "Kai", "Kevin", "Laila", "Leo", "Lucia", "Malik",
+    "Maria", "Mateo", "Maya", "Nadia", "Noah", "Olivia", "Omar", "Priya", "Rafael", "Rosa", "Sam",
+    "Sofia", "Tariq", "Tessa", "Uma", "Victor", "Wei", "Yara", "Yusuf", "Zoe",
+];
+
+pub(super) const LAST_NAMES: &[&str] = &[
+    "Abbott", "Alvarez", "Bauer", "Brown", "Chen", "Clarke", "Costa", "Dubois", "Evans", "Fischer",
+    "Garcia", "Gupta", "Hansen", "Hughes", "Ito", "Jensen", "Johnson", "Kim", "Kowalski", "Lopez",
+    "Martin", "Meyer", "Moreau", "Nakamura", "Nguyen", "Novak", "O'Brien", "Okafor", "Patel",
+    "Petrov", "Quinn", "Rossi", "Santos", "Schmidt", "Silva", "Singh", "Smith", "Tanaka", "Taylor",
+    "Walker", "Wang", "Weber", "Williams", "Young", "Zhang",
+];
+
+pub(super) const STREET_NAMES: &[&str] = &[
+    "Ash", "Birch", "Cedar", "Cherry", "Elm", "Hickory", "Highland", "Hill", "Lake", "Maple",
+    "Meadow", "Mill", "Oak", "Park", "Pine", "River", "Spring", "Sunset", "Valley", "Willow",
+];
+
+pub(super) const STREET_SUFFIXES: &[&str] = &[
+    "Avenue",
+    "Boulevard",
+    "Court",
+    "Drive",
+    "Lane",
+    "Place",
+    "Road",
+    "Street",
+    "Way",
+];
+
+pub(super) const CITIES: &[&str] = &[
+    "Ashford",
+    "Bayview",
+    "Brookside",
+    "Cedar Falls",
+    "Clearwater",
+    "Fairview",
+    "Glenwood",
+    "Greenville",
+    "Harbor City",
+    "Lakewood",
+    "Millbrook",
+    "Northfield",
+    "Oakridge",
+    "Riverside",
+    "Springfield",
+    "Stonebridge",
+    "Westbrook",
+    "Willow Creek",
+];
+
+pub(super) const COUNTRIES: &[&str] = &[
+    "Argentina",
+    "Australia",
+    "Brazil",
+    "Canada",
+    "Chile",
+    "Denmark",
+    "Egypt",
+    "France",
+    "Germany",
+    "India",
+    "Ireland",
+    "Italy",
+    "Japan",
+    "Kenya",
+    "Mexico",
+    "Netherlands",
+    "New Zealand",
+    "Nigeria",
+    "Norway",
+    "Poland",
+    "Portugal",
+    "South Korea",
+    "Spain",
+    "Sweden",
+    "United Kingdom",
+    "United States",
+    "Vietnam",
+];
+
+pub(super) const COMPANY_SUFFIXES: &[&str] = &[
+    "& Co", "Group", "Holdings", "Inc", "Labs", "LLC", "Partners", "Systems",
+];
+
+pub(super) const DOMAINS: &[&str] = &["example.com", "example.net", "example.org"];
+
+pub(super) const LOREM: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "ea",
+    "commodo",
+    "consequat",
+    "duis",
+    "aute",
+    "irure",
+    "in",
+    "reprehenderit",
+    "voluptate",
+    "velit",
+    "esse",
+    "cillum",
+    "fugiat",
+    "nulla",
+    "pariatur",
+    "excepteur",
+    "sint",
+    "occaecat",
+    "cupidatat",
+    "non",
+    "proident",
+    "sunt",
+    "culpa",
+    "qui",
+    "officia",
+    "deserunt",
+    "mollit",
+    "anim",
+    "id",
+    "est",
+    "laborum",
+];
@@ -4,7 +4,10 @@ use std::fmt;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum LexErrorKind {
+    InvalidEscape(String),
     InvalidNumericLiteral(String),
+    UnclosedComment,
+    UnclosedInterpolation,
     UnclosedQuotedIdentifier,
     UnclosedString,
     UnexpectedEOF,
@@ -16,9 +19,18 @@ impl fmt::Display for LexErrorKind {
         use LexErrorKind::*;
 
         match self {
+            InvalidEscape(s) => {
+                write!(f, "invalid escape sequence `{}`", s)
+            }
             InvalidNumericLiteral(n) => {
                 write!(f, "invalid numeric literal `{}`", n)
             }
+            UnclosedComment => {
+                write!(f, "unclosed block comment starting")
+            }
+            UnclosedInterpolation => {
+                write!(f, "unclosed interpolation in string starting")
+            }
             UnclosedQuotedIdentifier => {
                 write!(f, "unclosed quoted identifier starting")
             }
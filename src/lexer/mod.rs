@@ -3,13 +3,23 @@ mod prelude;
 mod states;
 pub mod tokens;
 
+use crate::Position;
 use error::LexError;
 use prelude::{Context, State};
 use states::Start;
 use tokens::Token;
 
 pub fn tokenize(input: impl Iterator<Item = char>) -> Result<Vec<Token>, LexError> {
+    tokenize_at(input, Position::default())
+}
+
+/// Tokenizes input that starts at the given position, eg. an interpolation in a string.
+fn tokenize_at(
+    input: impl Iterator<Item = char>,
+    position: Position,
+) -> Result<Vec<Token>, LexError> {
     let mut ctx = Context::default();
+    ctx.current_position = position;
     let mut state: Box<dyn State> = Box::new(Start);
 
     for c in input {
@@ -123,6 +133,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_doc_comments() {
+        let input = "---  Seeded admins\n---\n---- not docs\n-----";
+        assert_eq!(
+            tokens(input),
+            vec![
+                Token {
+                    kind: TokenKind::DocComment("Seeded admins".to_string()),
+                    position: Position { line: 1, column: 1 }
+                },
+                Token {
+                    kind: TokenKind::LineSep,
+                    position: Position { line: 1, column: 19 }
+                },
+                Token {
+                    kind: TokenKind::DocComment("".to_string()),
+                    position: Position { line: 2, column: 1 }
+                },
+                Token {
+                    kind: TokenKind::LineSep,
+                    position: Position { line: 2, column: 4 }
+                },
+                Token {
+                    kind: TokenKind::LineSep,
+                    position: Position { line: 3, column: 14 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_comments() {
+        let kinds = |input: &str| -> Vec<_> {
+            tokens(input).into_iter().map(|t| t.kind).collect()
+        };
+
+        assert_eq!(
+            kinds("a /* one /* two */ still -- one\n*/ b /*/ */ c /**/"),
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Identifier("b".to_string()),
+                TokenKind::Identifier("c".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize("a\n  /* /* */ *".chars()),
+            Err(LexError {
+                kind: LexErrorKind::UnclosedComment,
+                position: Position { line: 2, column: 3 },
+            })
+        );
+        assert_eq!(tokenize("a / b".chars()), Err(bad_char('/', Position { line: 1, column: 3 })));
+    }
+
     #[test]
     fn test_keywords() {
         let input = "as schema table";
@@ -344,6 +408,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lookup_record() {
+        let input = "role=lookup";
+        assert_eq!(
+            tokens(input),
+            vec![
+                Token {
+                    kind: TokenKind::Identifier("role".to_string()),
+                    position: Position { line: 1, column: 1 },
+                },
+                Token {
+                    kind: TokenKind::Symbol(Symbol::Equals),
+                    position: Position { line: 1, column: 5 },
+                },
+                Token {
+                    kind: TokenKind::Identifier("lookup".to_string()),
+                    position: Position { line: 1, column: 6 },
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_other_symbols_followed_by_identifiers() {
         let input = r#" .one ."two" @three @"four" "#;
@@ -397,4 +483,26 @@ mod tests {
             ]
         );
     }
+    #[test]
+    fn test_casts() {
+        let input = "1::bigint '{}'::int[]";
+        let kinds: Vec<_> = tokens(input).into_iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Number("1".to_string()),
+                TokenKind::Symbol(Symbol::DoubleColon),
+                TokenKind::Identifier("bigint".to_string()),
+                TokenKind::Text("'{}'".to_string()),
+                TokenKind::Symbol(Symbol::DoubleColon),
+                TokenKind::Identifier("int".to_string()),
+                TokenKind::Symbol(Symbol::BracketLeft),
+                TokenKind::Symbol(Symbol::BracketRight),
+            ]
+        );
+        assert_eq!(
+            tokenize("'a':text".chars()),
+            Err(bad_char('t', Position { line: 1, column: 5 }))
+        );
+    }
 }
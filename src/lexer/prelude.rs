@@ -40,7 +40,7 @@ pub(super) struct Stack {
 impl Stack {
     pub fn new(start_position: Position, c: Option<char>) -> Self {
         Self {
-            content: c.map(|c| String::from(c)).unwrap_or_default(),
+            content: c.map(String::from).unwrap_or_default(),
             start_position,
         }
     }
@@ -49,12 +49,21 @@ impl Stack {
         self.content
     }
 
+    /// Takes the content so far, leaving the stack empty but at the same position.
+    pub fn take(&mut self) -> String {
+        std::mem::take(&mut self.content)
+    }
+
     pub fn push(&mut self, c: char) {
         self.content.push(c);
     }
 
+    pub fn as_str(&self) -> &str {
+        &self.content
+    }
+
     pub fn top(&self) -> Option<char> {
-        self.content.chars().rev().next()
+        self.content.chars().next_back()
     }
 }
 
@@ -1,7 +1,98 @@
+use crate::lexer::error::{LexError, LexErrorKind};
 use crate::lexer::tokens::{Token, TokenKind};
 use crate::lexer::prelude::*;
+use crate::Position;
 use super::start::Start;
 
+/// State after receiving double-dashes, which start a doc comment if followed by
+/// exactly one more dash.
+#[derive(Debug)]
+pub(super) struct AfterDoubleDash(pub Position);
+
+impl State for AfterDoubleDash {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        match c {
+            Some('-') => to(AfterTripleDash(self.0)),
+            _ => defer_to(InComment, ctx, c),
+        }
+    }
+}
+
+/// State after receiving three dashes, which start a doc comment unless followed by
+/// more dashes, so that lines of dashes are still regular comments.
+#[derive(Debug)]
+struct AfterTripleDash(Position);
+
+impl State for AfterTripleDash {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        match c {
+            Some('-') => to(InComment),
+            _ => defer_to(InDocComment(Stack::new(self.0, None)), ctx, c),
+        }
+    }
+}
+
+/// State after receiving `---`, collecting the text of the doc comment.
+#[derive(Debug)]
+struct InDocComment(Stack);
+
+impl State for InDocComment {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        let mut stack = self.0;
+
+        match c {
+            Some(c) if !is_newline(c) => {
+                stack.push(c);
+                to(InDocComment(stack))
+            }
+            _ => {
+                let position = stack.start_position;
+                let kind = TokenKind::DocComment(stack.consume().trim().to_owned());
+                ctx.add_token(Token { kind, position });
+                defer_to(InComment, ctx, c)
+            }
+        }
+    }
+}
+
+/// State inside a block comment, which can be nested like in SQL, eg.
+/// `/* outer /* inner */ still outer */`.
+#[derive(Debug)]
+pub(super) struct InBlockComment {
+    pub start: Position,
+    pub depth: usize,
+    /// The previous character, if it could start `/*` or `*/`
+    pub previous: Option<char>,
+}
+
+impl State for InBlockComment {
+    fn receive(self: Box<Self>, _ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        let InBlockComment { start, mut depth, previous } = *self;
+
+        // Unlike `--` comments, block comments never end a line, even if they span lines
+        match (previous, c) {
+            (Some('*'), Some('/')) if depth == 1 => return to(Start),
+            (Some('*'), Some('/')) => depth -= 1,
+            (Some('/'), Some('*')) => depth += 1,
+            (_, Some(_)) => {}
+            (_, None) => {
+                return Err(LexError {
+                    kind: LexErrorKind::UnclosedComment,
+                    position: start,
+                });
+            }
+        }
+
+        // The characters of `/*` or `*/` can't be part of another, eg. `/*/`
+        let previous = match (previous, c) {
+            (Some('*'), Some('/')) | (Some('/'), Some('*')) => None,
+            (_, c) => c.filter(|c| matches!(c, '*' | '/')),
+        };
+
+        to(InBlockComment { start, depth, previous })
+    }
+}
+
 /// State after receiving double-dashes.
 #[derive(Debug, PartialEq)]
 pub struct InComment;
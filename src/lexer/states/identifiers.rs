@@ -2,6 +2,7 @@ use crate::lexer::error::{LexError, LexErrorKind};
 use crate::lexer::tokens::{Keyword, Symbol, Token, TokenKind};
 use crate::lexer::prelude::*;
 use super::start::Start;
+use super::text::{InDollarQuotedText, InEscapeText};
 
 /// State after receiving a valid identifier character.
 #[derive(Debug)]
@@ -16,6 +17,10 @@ impl State for InIdentifier {
                 stack.push(c);
                 to(InIdentifier(stack))
             }
+            // Like in SQL, a quote directly after `E` starts an escape string
+            Some('\'') if matches!(stack.as_str(), "E" | "e") => {
+                to(InEscapeText(Stack::new(stack.start_position, Some('\''))))
+            }
             _ => {
                 let position = stack.start_position;
                 let kind = identifier_to_token_kind(stack.consume());
@@ -26,6 +31,45 @@ impl State for InIdentifier {
     }
 }
 
+/// State after receiving a dollar sign, expecting the name of a variable or the
+/// tag of a dollar-quoted string, eg. `$body$`.
+#[derive(Debug)]
+pub(super) struct InVariable(pub Stack);
+
+impl State for InVariable {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        use LexErrorKind::{UnexpectedCharacter, UnexpectedEOF};
+
+        let mut stack = self.0;
+
+        match c {
+            Some(c) if is_identifier_char(c) => {
+                stack.push(c);
+                to(InVariable(stack))
+            }
+            Some('$') => {
+                let delimiter = format!("${}$", stack.as_str());
+                let text = Stack::new(stack.start_position, None);
+                to(InDollarQuotedText(text, delimiter))
+            }
+            Some(c) if stack.top().is_none() => Err(LexError {
+                kind: UnexpectedCharacter(c),
+                position: ctx.current_position,
+            }),
+            None if stack.top().is_none() => Err(LexError {
+                kind: UnexpectedEOF,
+                position: ctx.current_position,
+            }),
+            _ => {
+                let position = stack.start_position;
+                let kind = TokenKind::Variable(stack.consume());
+                ctx.add_token(Token { kind, position });
+                defer_to(Start, ctx, c)
+            }
+        }
+    }
+}
+
 /// State after receiving a valid identifier character.
 #[derive(Debug)]
 pub(super) struct InQuotedIdentifier(pub Stack);
@@ -113,7 +157,7 @@ mod identifiers_tests {
         #[test]
         fn test_receive_whitespace() {
             let mut ctx = Context::default();
-            let mut stack = Stack::new(Position { line: 2, column: 3}, 'x');
+            let mut stack = Stack::new(Position { line: 2, column: 3}, Some('x'));
             stack.push('y');
             stack.push('z');
 
@@ -140,7 +184,7 @@ mod identifiers_tests {
                 let mut ctx = Context::default();
                 ctx.current_position = Position { line: 1, column: 3};
 
-                let mut stack = Stack::new(Position { line: 1, column: 1}, 'a');
+                let mut stack = Stack::new(Position { line: 1, column: 1}, Some('a'));
                 stack.push('b');
                 stack.push('c');
 
@@ -163,7 +207,7 @@ mod identifiers_tests {
         #[test]
         fn test_receive_none() {
             let mut ctx = Context::default();
-            let mut stack = Stack::new(Position { line: 2, column: 3}, 'x');
+            let mut stack = Stack::new(Position { line: 2, column: 3}, Some('x'));
             stack.push('y');
             stack.push('z');
 
@@ -179,6 +223,45 @@ mod identifiers_tests {
         }
     }
 
+    mod in_variable_tests {
+        use pretty_assertions::assert_eq;
+        use super::*;
+
+        #[test]
+        fn test_receive_terminating() {
+            for c in [Some(' '), Some(','), Some(')'), Some('\n'), None] {
+                let mut ctx = Context::default();
+                let mut stack = Stack::new(Position { line: 1, column: 4 }, None);
+                stack.push('x');
+                stack.push('y');
+
+                let state = Box::new(InVariable(stack)).receive(&mut ctx, c).unwrap();
+
+                assert!((*state).type_id() == TypeId::of::<Start>());
+                assert_eq!(ctx.into_tokens()[0], Token {
+                    kind: TokenKind::Variable("xy".to_owned()),
+                    position: Position { line: 1, column: 4 },
+                });
+            }
+        }
+
+        #[test]
+        fn test_receive_without_name() {
+            for (c, kind) in [
+                (Some(' '), LexErrorKind::UnexpectedCharacter(' ')),
+                (Some('-'), LexErrorKind::UnexpectedCharacter('-')),
+                (None, LexErrorKind::UnexpectedEOF),
+            ] {
+                let mut ctx = Context::new(Position { line: 1, column: 5 }, None);
+                let stack = Stack::new(Position { line: 1, column: 4 }, None);
+
+                let err = Box::new(InVariable(stack)).receive(&mut ctx, c).err().unwrap();
+
+                assert_eq!(err, LexError { kind, position: Position { line: 1, column: 5 } });
+            }
+        }
+    }
+
     mod in_quoted_identifier_tests {
         use pretty_assertions::assert_eq;
         use super::*;
@@ -206,7 +289,7 @@ mod identifiers_tests {
         #[test]
         fn test_receive_whitespace() {
             let mut ctx = Context::default();
-            let mut stack = Stack::new(Position { line: 2, column: 3}, 'x');
+            let mut stack = Stack::new(Position { line: 2, column: 3}, Some('x'));
             stack.push('y');
             stack.push('z');
 
@@ -220,7 +303,7 @@ mod identifiers_tests {
         fn test_receive_terminators() {
             for c in ['\r', '\n', ',', '(', ')'] {
                 let mut ctx = Context::default();
-                let mut stack = Stack::new(Position { line: 1, column: 1}, 'a');
+                let mut stack = Stack::new(Position { line: 1, column: 1}, Some('a'));
                 stack.push('b');
                 stack.push('c');
 
@@ -268,7 +351,7 @@ mod identifiers_tests {
         #[test]
         fn test_receive_whitespace() {
             let mut ctx = Context::default();
-            let mut stack = Stack::new(Position { line: 2, column: 3}, 'x');
+            let mut stack = Stack::new(Position { line: 2, column: 3}, Some('x'));
             stack.push('y');
             stack.push('z');
 
@@ -296,7 +379,7 @@ mod identifiers_tests {
                 let mut ctx = Context::default();
                 ctx.current_position = Position { line: 1, column: 3};
 
-                let mut stack = Stack::new(Position { line: 1, column: 1}, 'a');
+                let mut stack = Stack::new(Position { line: 1, column: 1}, Some('a'));
                 stack.push('b');
                 stack.push('c');
 
@@ -354,6 +437,20 @@ mod identifiers_tests {
             );
         }
 
+        #[test]
+        fn test_contextual_keywords() {
+            for ident in [
+                "on", "conflict", "do", "nothing", "lookup", "update", "set", "delete", "expect",
+                "truncate", "restart", "identity", "cascade", "reset", "sequence", "sql",
+                "let", "param",
+            ] {
+                assert_eq!(
+                    identifier_to_token_kind(ident.to_owned()),
+                    TokenKind::Identifier(ident.to_owned()),
+                );
+            }
+        }
+
         #[test]
         fn test_bool_true() {
             for ident in ["t", "true"] {
@@ -100,7 +100,7 @@ impl State for InInteger {
 
 fn can_terminate(c: Option<char>) -> bool {
     c.is_none()
-        || matches!(c, Some(')'))
+        || matches!(c, Some(')' | ',' | ':'))
         || matches!(c, Some(c) if is_whitespace(c) || is_newline(c))
 }
 
@@ -116,7 +116,7 @@ mod numbers_tests {
         #[test]
         fn test_digit_after_digit() {
             let mut ctx = Context::default();
-            let stack = Stack::new(Position::default(), '6');
+            let stack = Stack::new(Position::default(), Some('6'));
 
             let state = Box::new(InInteger(stack)).receive(&mut ctx, Some('7')).unwrap();
 
@@ -127,7 +127,7 @@ mod numbers_tests {
         #[test]
         fn test_underscore_after_digit() {
             let mut ctx = Context::default();
-            let stack = Stack::new(Position::default(), '9');
+            let stack = Stack::new(Position::default(), Some('9'));
 
             let state = Box::new(InInteger(stack)).receive(&mut ctx, Some('_')).unwrap();
 
@@ -138,7 +138,7 @@ mod numbers_tests {
         #[test]
         fn test_period_after_digit() {
             let mut ctx = Context::default();
-            let stack = Stack::new(Position::default(), '9');
+            let stack = Stack::new(Position::default(), Some('9'));
 
             let state = Box::new(InInteger(stack)).receive(&mut ctx, Some('.')).unwrap();
 
@@ -146,10 +146,33 @@ mod numbers_tests {
             assert_eq!(Context::default(), ctx);
         }
 
+        #[test]
+        fn test_comma_after_digit() {
+            let mut ctx = Context::default();
+            ctx.current_position = Position { line: 1, column: 3 };
+
+            let mut stack = Stack::new(Position { line: 1, column: 1 }, Some('4'));
+            stack.push('2');
+
+            let state = Box::new(InInteger(stack)).receive(&mut ctx, Some(',')).unwrap();
+
+            assert!((*state).type_id() == TypeId::of::<Start>());
+            assert_eq!(ctx.into_tokens(), vec![
+                Token {
+                    kind: TokenKind::Number("42".to_owned()),
+                    position: Position { line: 1, column: 1 },
+                },
+                Token {
+                    kind: TokenKind::Symbol(crate::lexer::tokens::Symbol::Comma),
+                    position: Position { line: 1, column: 3 },
+                },
+            ]);
+        }
+
         #[test]
         fn test_underscore_after_underscore() {
             let mut ctx = Context::new(Position { line: 9, column: 10 }, None);
-            let stack = Stack::new(Position::default(), '_');
+            let stack = Stack::new(Position::default(), Some('_'));
 
             let err = Box::new(InInteger(stack)).receive(&mut ctx, Some('_')).err().unwrap();
 
@@ -166,7 +189,7 @@ mod numbers_tests {
         #[test]
         fn test_period_after_underscore() {
             let mut ctx = Context::new(Position { line: 9, column: 10 }, None);
-            let stack = Stack::new(Position::default(), '_');
+            let stack = Stack::new(Position::default(), Some('_'));
 
             let err = Box::new(InInteger(stack)).receive(&mut ctx, Some('.')).err().unwrap();
 
@@ -1,25 +1,40 @@
 use crate::lexer::error::{LexError, LexErrorKind};
-use crate::lexer::tokens::{Token, TokenKind};
+use crate::lexer::tokens::{TextPart, Token, TokenKind};
 use crate::lexer::prelude::*;
+use crate::Position;
 use super::start::Start;
 
-/// State after receiving a backtick.
+/// State after receiving a backtick, along with the parts of the fragment before any
+/// references so far and the quote of the string or quoted identifier it is in, if any.
 #[derive(Debug)]
-pub(super) struct InSqlSelect(pub Stack);
+pub(super) struct InSqlSelect(pub Stack, pub Vec<TextPart>, pub Option<char>);
 
 impl State for InSqlSelect {
     fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
         use LexErrorKind::UnclosedString;
 
-        let mut stack = self.0;
+        let InSqlSelect(mut stack, parts, quote) = *self;
 
         match c {
             Some('`') => {
-                to(AfterSqlSelect(stack))
+                to(AfterSqlSelect(stack, parts, quote))
+            }
+            // Neither strings nor quoted identifiers can contain references
+            Some(c @ ('\'' | '"')) => {
+                let quote = match quote {
+                    Some(q) if q == c => None,
+                    None => Some(c),
+                    _ => quote,
+                };
+                stack.push(c);
+                to(InSqlSelect(stack, parts, quote))
+            }
+            Some('@') if quote.is_none() && can_precede_reference(stack.top()) => {
+                to(AfterSqlAtSign(stack, parts, ctx.current_position))
             }
             Some(c) => {
                 stack.push(c);
-                to(InSqlSelect(stack))
+                to(InSqlSelect(stack, parts, quote))
             }
             None => Err(LexError {
                 kind: UnclosedString,
@@ -33,11 +48,11 @@ impl State for InSqlSelect {
 /// character received is another backtick, which indicates the previous
 /// backtick was being escaped and is part of the SQL select statement.
 #[derive(Debug)]
-pub(super) struct AfterSqlSelect(pub Stack);
+pub(super) struct AfterSqlSelect(pub Stack, pub Vec<TextPart>, pub Option<char>);
 
 impl State for AfterSqlSelect {
     fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
-        let mut stack = self.0;
+        let AfterSqlSelect(mut stack, mut parts, quote) = *self;
 
         match c {
             Some('`') => {
@@ -46,14 +61,146 @@ impl State for AfterSqlSelect {
                 // since text strings and quoted identifiers have to remain properly escaped
                 // when passing to the database
                 stack.push('`');
-                to(InSqlSelect(stack))
+                to(InSqlSelect(stack, parts, quote))
             }
             _ => {
                 let position = stack.start_position;
-                let kind = TokenKind::SqlFragment(stack.consume());
+                let kind = match parts.is_empty() {
+                    true => TokenKind::SqlFragment(stack.consume()),
+                    false => {
+                        let sql = stack.consume();
+                        if !sql.is_empty() {
+                            parts.push(TextPart::Text(sql));
+                        }
+                        TokenKind::InterpolatedSql(parts)
+                    }
+                };
                 ctx.add_token(Token { kind, position });
                 defer_to(Start, ctx, c)
             }
         }
     }
 }
+
+/// State after receiving an at sign in a SQL fragment, which starts a reference if
+/// directly followed by a name and is otherwise part of an operator, eg. `@>`.
+#[derive(Debug)]
+struct AfterSqlAtSign(Stack, Vec<TextPart>, Position);
+
+impl State for AfterSqlAtSign {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        let AfterSqlAtSign(mut stack, mut parts, position) = *self;
+
+        match c {
+            Some(c) if c == '"' || is_identifier_char(c) => {
+                let sql = stack.take();
+                if !sql.is_empty() {
+                    parts.push(TextPart::Text(sql));
+                }
+
+                let mut reference = Stack::new(position, Some('@'));
+                reference.push(c);
+                to(InSqlReference(stack, parts, reference, c == '"'))
+            }
+            _ => {
+                stack.push('@');
+                defer_to(InSqlSelect(stack, parts, None), ctx, c)
+            }
+        }
+    }
+}
+
+/// State inside a reference in a SQL fragment, which ends at the first character that
+/// can't be part of one outside of a quoted identifier.
+#[derive(Debug)]
+struct InSqlReference(Stack, Vec<TextPart>, Stack, bool);
+
+impl State for InSqlReference {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        let InSqlReference(stack, mut parts, mut reference, quoted) = *self;
+
+        match c {
+            Some('"') => {
+                reference.push('"');
+                to(InSqlReference(stack, parts, reference, !quoted))
+            }
+            Some(c) if c != '`' && (quoted || c == '.' || is_identifier_char(c)) => {
+                reference.push(c);
+                to(InSqlReference(stack, parts, reference, quoted))
+            }
+            _ => {
+                let position = reference.start_position;
+                let tokens = crate::lexer::tokenize_at(reference.consume().chars(), position)?;
+                parts.push(TextPart::Interpolation(tokens));
+                defer_to(InSqlSelect(stack, parts, None), ctx, c)
+            }
+        }
+    }
+}
+
+/// Whether a reference can start after the character, which rules out operators
+/// containing `@` (eg. `<@`) and email addresses in comments.
+fn can_precede_reference(c: Option<char>) -> bool {
+    match c {
+        Some(c) => is_whitespace(c) || is_newline(c) || matches!(c, '(' | ',' | '['),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::tokenize;
+    use crate::lexer::tokens::{Symbol, TextPart, Token, TokenKind};
+    use crate::Position;
+
+    fn token(kind: TokenKind, column: usize) -> Token {
+        Token { kind, position: Position { line: 1, column } }
+    }
+
+    fn kind(input: &str) -> TokenKind {
+        tokenize(input.chars()).unwrap().remove(0).kind
+    }
+
+    #[test]
+    fn test_references() {
+        assert_eq!(
+            kind("`@order.total * 2`"),
+            TokenKind::InterpolatedSql(vec![
+                TextPart::Interpolation(vec![
+                    token(TokenKind::Symbol(Symbol::AtSign), 2),
+                    token(TokenKind::Identifier("order".to_owned()), 3),
+                    token(TokenKind::Symbol(Symbol::Period), 8),
+                    token(TokenKind::Identifier("total".to_owned()), 9),
+                ]),
+                TextPart::Text(" * 2".to_owned()),
+            ])
+        );
+        assert_eq!(
+            kind("`coalesce(@\"Name\", 'x')`"),
+            TokenKind::InterpolatedSql(vec![
+                TextPart::Text("coalesce(".to_owned()),
+                TextPart::Interpolation(vec![
+                    token(TokenKind::Symbol(Symbol::AtSign), 11),
+                    token(TokenKind::QuotedIdentifier("\"Name\"".to_owned()), 12),
+                ]),
+                TextPart::Text(", 'x')".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_at_signs_without_references() {
+        for input in [
+            "`'{1}' <@ tags`",
+            "`tags @> '{1}'`",
+            "`'a@example.com'`",
+            "`\"a @b\"`",
+            "`to_tsvector(body) @@ 'cat'`",
+            "`@ -5`",
+            "`'it''s @x'`",
+        ] {
+            let fragment = input[1..input.len() - 1].to_owned();
+            assert_eq!(kind(input), TokenKind::SqlFragment(fragment), "{}", input);
+        }
+    }
+}
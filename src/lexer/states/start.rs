@@ -1,10 +1,10 @@
 use crate::lexer::error::{LexError, LexErrorKind};
 use crate::lexer::tokens::{Symbol, Token, TokenKind};
 use crate::lexer::prelude::*;
-use super::identifiers::{InIdentifier, InQuotedIdentifier};
+use super::identifiers::{InIdentifier, InQuotedIdentifier, InVariable};
 use super::numbers::InInteger;
 use super::sql::InSqlSelect;
-use super::symbols::{AfterPeriod, AfterSingleDash};
+use super::symbols::{AfterColon, AfterPeriod, AfterSingleDash, AfterSlash};
 use super::text::InText;
 
 
@@ -43,11 +43,32 @@ impl State for Start {
                 ctx.add_token(Token { kind, position: ctx.current_position });
                 to(Start)
             }
+            '[' => {
+                let kind = TokenKind::Symbol(Symbol::BracketLeft);
+                ctx.add_token(Token { kind, position: ctx.current_position });
+                to(Start)
+            }
+            ']' => {
+                let kind = TokenKind::Symbol(Symbol::BracketRight);
+                ctx.add_token(Token { kind, position: ctx.current_position });
+                to(Start)
+            }
             ',' => {
                 let kind = TokenKind::Symbol(Symbol::Comma);
                 ctx.add_token(Token { kind, position: ctx.current_position });
                 to(Start)
             }
+            '=' => {
+                let kind = TokenKind::Symbol(Symbol::Equals);
+                ctx.add_token(Token { kind, position: ctx.current_position });
+                to(Start)
+            }
+            ':' => {
+                to(AfterColon(ctx.current_position))
+            }
+            '/' => {
+                to(AfterSlash(ctx.current_position))
+            }
             '.' => {
                 let stack = Stack::new(ctx.current_position, Some(c));
                 to(AfterPeriod(stack))
@@ -58,15 +79,19 @@ impl State for Start {
             }
             '\'' => {
                 let stack = Stack::new(ctx.current_position, Some(c));
-                to(InText(stack))
+                to(InText(stack, Vec::new()))
             }
             '"' => {
                 let stack = Stack::new(ctx.current_position, Some(c));
                 to(InQuotedIdentifier(stack))
             }
+            '$' => {
+                let stack = Stack::new(ctx.current_position, None);
+                to(InVariable(stack))
+            }
             '`' => {
                 let stack = Stack::new(ctx.current_position, None);
-                to(InSqlSelect(stack))
+                to(InSqlSelect(stack, Vec::new(), None))
             }
             '0'..='9' => {
                 let stack = Stack::new(ctx.current_position, Some(c));
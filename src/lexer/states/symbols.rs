@@ -1,7 +1,8 @@
 use crate::lexer::error::{LexError, LexErrorKind};
 use crate::lexer::tokens::{Symbol, Token, TokenKind};
 use crate::lexer::prelude::*;
-use super::comments::InComment;
+use crate::Position;
+use super::comments::{AfterDoubleDash, InBlockComment};
 use super::numbers::{InFloat, InInteger};
 use super::start::Start;
 
@@ -27,6 +28,52 @@ impl State for AfterPeriod {
     }
 }
 
+/// State after receiving a colon, which is only valid as the first half of `::`.
+#[derive(Debug)]
+pub(super) struct AfterColon(pub Position);
+
+impl State for AfterColon {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        use LexErrorKind::{UnexpectedCharacter, UnexpectedEOF};
+
+        match c {
+            Some(':') => {
+                let kind = TokenKind::Symbol(Symbol::DoubleColon);
+                ctx.add_token(Token { kind, position: self.0 });
+                to(Start)
+            }
+            Some(c) => Err(LexError {
+                kind: UnexpectedCharacter(c),
+                position: ctx.current_position,
+            }),
+            None => Err(LexError {
+                kind: UnexpectedEOF,
+                position: ctx.current_position,
+            }),
+        }
+    }
+}
+
+/// State after receiving a slash, which is only valid as the start of `/*`.
+#[derive(Debug)]
+pub(super) struct AfterSlash(pub Position);
+
+impl State for AfterSlash {
+    fn receive(self: Box<Self>, _ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        use LexErrorKind::UnexpectedCharacter;
+
+        match c {
+            Some('*') => {
+                to(InBlockComment { start: self.0, depth: 1, previous: None })
+            }
+            _ => Err(LexError {
+                kind: UnexpectedCharacter('/'),
+                position: self.0,
+            }),
+        }
+    }
+}
+
 /// State after receiving a single dash
 #[derive(Debug)]
 pub(super) struct AfterSingleDash(pub Stack);
@@ -39,7 +86,7 @@ impl State for AfterSingleDash {
 
         match c {
             Some('-') => {
-                to(InComment)
+                to(AfterDoubleDash(stack.start_position))
             }
             Some(c @ '0'..='9') => {
                 stack.push(c);
@@ -1,25 +1,29 @@
 use crate::lexer::error::{LexError, LexErrorKind};
-use crate::lexer::tokens::{Token, TokenKind};
+use crate::lexer::tokens::{TextPart, Token, TokenKind};
 use crate::lexer::prelude::*;
 use super::start::Start;
 
-/// State after receiving a single quote and inside a string literal.
+/// State after receiving a single quote and inside a string literal, along with
+/// the parts of the string before any interpolations so far.
 #[derive(Debug)]
-pub(super) struct InText(pub Stack);
+pub(super) struct InText(pub Stack, pub Vec<TextPart>);
 
 impl State for InText {
     fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
         use LexErrorKind::UnclosedString;
 
-        let mut stack = self.0;
+        let InText(mut stack, parts) = *self;
 
         match c {
             Some('\'') => {
-                to(AfterText(stack))
+                to(AfterText(stack, parts))
+            }
+            Some('{') => {
+                to(AfterTextBrace(stack, parts))
             }
             Some(c) => {
                 stack.push(c);
-                to(InText(stack))
+                to(InText(stack, parts))
             }
             None => Err(LexError {
                 kind: UnclosedString,
@@ -33,19 +37,174 @@ impl State for InText {
 /// character received is another single quote, which indicates the previous
 /// quote was being escaped and is part of the text string.
 #[derive(Debug)]
-pub(super) struct AfterText(pub Stack);
+pub(super) struct AfterText(pub Stack, pub Vec<TextPart>);
 
 impl State for AfterText {
     fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
-        let mut stack = self.0;
+        let AfterText(mut stack, mut parts) = *self;
         stack.push('\'');
 
         match c {
             Some('\'') => {
                 stack.push('\'');
-                to(InText(stack))
+                to(InText(stack, parts))
             }
             _ => {
+                let position = stack.start_position;
+                let kind = match parts.is_empty() {
+                    true => TokenKind::Text(stack.consume()),
+                    false => {
+                        // The closing quote isn't part of the text, which is empty when the
+                        // string ends with an interpolation
+                        let text = stack.consume();
+                        if text.len() > 1 {
+                            parts.push(text_part(&text[..text.len() - 1]));
+                        }
+                        TokenKind::InterpolatedText(parts)
+                    }
+                };
+                ctx.add_token(Token { kind, position });
+                defer_to(Start, ctx, c)
+            }
+        }
+    }
+}
+
+/// State after receiving an opening brace in a string literal, which starts an
+/// interpolation if followed by a reference or variable.
+#[derive(Debug)]
+struct AfterTextBrace(Stack, Vec<TextPart>);
+
+impl State for AfterTextBrace {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        let AfterTextBrace(mut stack, mut parts) = *self;
+
+        match c {
+            Some(c @ ('@' | '$')) => {
+                // The opening quote is only part of the text before the first interpolation
+                let text = stack.take();
+                let text = match parts.is_empty() {
+                    true => &text[1..],
+                    false => &text,
+                };
+                if !text.is_empty() {
+                    parts.push(text_part(text));
+                }
+
+                let expression = Stack::new(ctx.current_position, Some(c));
+                to(InInterpolation(stack, parts, expression))
+            }
+            Some('{') => {
+                to(AfterTextDoubleBrace(stack, parts))
+            }
+            _ => {
+                stack.push('{');
+                defer_to(InText(stack, parts), ctx, c)
+            }
+        }
+    }
+}
+
+/// State after receiving two opening braces in a string literal, which escape
+/// a brace that would otherwise start an interpolation, eg. `'{{@literally}'`.
+#[derive(Debug)]
+struct AfterTextDoubleBrace(Stack, Vec<TextPart>);
+
+impl State for AfterTextDoubleBrace {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        let AfterTextDoubleBrace(mut stack, parts) = *self;
+
+        match c {
+            Some(c @ ('@' | '$')) => {
+                stack.push('{');
+                stack.push(c);
+                to(InText(stack, parts))
+            }
+            _ => {
+                stack.push('{');
+                stack.push('{');
+                defer_to(InText(stack, parts), ctx, c)
+            }
+        }
+    }
+}
+
+/// State inside an interpolation in a string literal, until the closing brace.
+#[derive(Debug)]
+struct InInterpolation(Stack, Vec<TextPart>, Stack);
+
+impl State for InInterpolation {
+    fn receive(self: Box<Self>, _ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        use LexErrorKind::UnclosedInterpolation;
+
+        let InInterpolation(stack, mut parts, mut expression) = *self;
+
+        match c {
+            Some('}') => {
+                let position = expression.start_position;
+                let tokens = crate::lexer::tokenize_at(expression.consume().chars(), position)?;
+                parts.push(TextPart::Interpolation(tokens));
+                to(InText(stack, parts))
+            }
+            Some(c) if c != '\'' && !is_newline(c) => {
+                expression.push(c);
+                to(InInterpolation(stack, parts, expression))
+            }
+            _ => Err(LexError {
+                kind: UnclosedInterpolation,
+                position: expression.start_position,
+            }),
+        }
+    }
+}
+
+/// State inside an escape string, eg. `E'line\n'`, along with the text so far as a
+/// standard string literal, so that backslashes are no longer special once lexed.
+#[derive(Debug)]
+pub(super) struct InEscapeText(pub Stack);
+
+impl State for InEscapeText {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        use LexErrorKind::UnclosedString;
+
+        let mut stack = self.0;
+
+        match c {
+            Some('\'') => {
+                to(AfterEscapeText(stack))
+            }
+            Some('\\') => {
+                let sequence = Stack::new(ctx.current_position, Some('\\'));
+                to(InEscapeSequence(stack, sequence))
+            }
+            Some(c) => {
+                push_text_char(&mut stack, c);
+                to(InEscapeText(stack))
+            }
+            None => Err(LexError {
+                kind: UnclosedString,
+                position: stack.start_position,
+            }),
+        }
+    }
+}
+
+/// State after receiving what might be the closing quote of an escape string unless
+/// followed by another quote, same as in standard strings.
+#[derive(Debug)]
+struct AfterEscapeText(Stack);
+
+impl State for AfterEscapeText {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        let mut stack = self.0;
+
+        match c {
+            Some('\'') => {
+                push_text_char(&mut stack, '\'');
+                to(InEscapeText(stack))
+            }
+            _ => {
+                stack.push('\'');
                 let position = stack.start_position;
                 let kind = TokenKind::Text(stack.consume());
                 ctx.add_token(Token { kind, position });
@@ -54,3 +213,302 @@ impl State for AfterText {
         }
     }
 }
+
+/// State after receiving a backslash in an escape string, along with the escape
+/// sequence so far, eg. `\u00e9`.
+#[derive(Debug)]
+struct InEscapeSequence(Stack, Stack);
+
+impl State for InEscapeSequence {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        use LexErrorKind::UnclosedString;
+
+        let InEscapeSequence(mut stack, mut sequence) = *self;
+
+        let c = match c {
+            Some(c) => c,
+            None => return Err(LexError {
+                kind: UnclosedString,
+                position: stack.start_position,
+            }),
+        };
+
+        match c {
+            'x' | 'u' | 'U' | '0'..='7' if sequence.as_str() == "\\" => {
+                sequence.push(c);
+            }
+            _ if sequence.as_str() == "\\" => {
+                let c = match c {
+                    'b' => '\u{8}',
+                    'f' => '\u{c}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    // Any other character, such as a quote or backslash, is itself
+                    c => c,
+                };
+                push_text_char(&mut stack, c);
+                return to(InEscapeText(stack));
+            }
+            _ if is_escape_digit(sequence.as_str(), c) => {
+                sequence.push(c);
+            }
+            // The sequence ends before its maximum number of digits
+            _ => {
+                push_text_char(&mut stack, escaped_char(sequence)?);
+                return defer_to(InEscapeText(stack), ctx, Some(c));
+            }
+        }
+
+        let (digits, max_digits) = escape_digits(sequence.as_str());
+        match digits.len() == max_digits {
+            true => {
+                push_text_char(&mut stack, escaped_char(sequence)?);
+                to(InEscapeText(stack))
+            }
+            false => to(InEscapeSequence(stack, sequence)),
+        }
+    }
+}
+
+/// State inside a dollar-quoted string, eg. `$$it's$$`, along with the delimiter that
+/// closes it, which is the same as the one that opened it.
+#[derive(Debug)]
+pub(super) struct InDollarQuotedText(pub Stack, pub String);
+
+impl State for InDollarQuotedText {
+    fn receive(self: Box<Self>, ctx: &mut Context, c: Option<char>) -> ReceiveResult {
+        use LexErrorKind::UnclosedString;
+
+        let InDollarQuotedText(mut stack, delimiter) = *self;
+
+        match c {
+            Some(c) => {
+                stack.push(c);
+
+                match stack.as_str().strip_suffix(&delimiter) {
+                    Some(text) => {
+                        let kind = TokenKind::Text(format!("'{}'", text.replace('\'', "''")));
+                        ctx.add_token(Token { kind, position: stack.start_position });
+                        to(Start)
+                    }
+                    None => to(InDollarQuotedText(stack, delimiter)),
+                }
+            }
+            None => Err(LexError {
+                kind: UnclosedString,
+                position: stack.start_position,
+            }),
+        }
+    }
+}
+
+/// Pushes a character of an escape string to the standard string literal it is lexed as.
+fn push_text_char(stack: &mut Stack, c: char) {
+    if c == '\'' {
+        stack.push('\'');
+    }
+    stack.push(c);
+}
+
+/// Returns the digits of an escape sequence so far and how many it can have at most,
+/// eg. `\x4` has one of up to two hexadecimal digits.
+fn escape_digits(sequence: &str) -> (&str, usize) {
+    match sequence.chars().nth(1) {
+        Some('x') => (&sequence[2..], 2),
+        Some('u') => (&sequence[2..], 4),
+        Some('U') => (&sequence[2..], 8),
+        _ => (&sequence[1..], 3),
+    }
+}
+
+fn is_escape_digit(sequence: &str, c: char) -> bool {
+    match sequence.chars().nth(1) {
+        Some('x' | 'u' | 'U') => c.is_ascii_hexdigit(),
+        _ => matches!(c, '0'..='7'),
+    }
+}
+
+/// Returns the character of a complete escape sequence, or an error if it has too
+/// few digits or isn't a valid character.
+fn escaped_char(sequence: Stack) -> Result<char, LexError> {
+    // Like any other unknown escape, `\x` without digits is the character itself
+    if sequence.as_str() == "\\x" {
+        return Ok('x');
+    }
+
+    let (digits, max_digits) = escape_digits(sequence.as_str());
+
+    // Octal and hexadecimal escapes are bytes, so only ASCII characters are supported
+    let (radix, ascii_only) = match sequence.as_str().chars().nth(1) {
+        Some('u' | 'U') => (16, false),
+        Some('x') => (16, true),
+        _ => (8, true),
+    };
+    let escaped = match digits.len() < max_digits && !ascii_only {
+        true => None,
+        false => u32::from_str_radix(digits, radix).ok().and_then(char::from_u32),
+    };
+    let escaped = escaped.filter(|c| *c != '\0' && (c.is_ascii() || !ascii_only));
+
+    escaped.ok_or_else(|| LexError {
+        position: sequence.start_position,
+        kind: LexErrorKind::InvalidEscape(sequence.consume()),
+    })
+}
+
+/// Unescapes quotes in text between interpolations, which is quoted again as a whole
+/// once the interpolations are resolved.
+fn text_part(text: &str) -> TextPart {
+    TextPart::Text(text.replace("''", "'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::tokenize;
+    use crate::lexer::error::{LexError, LexErrorKind};
+    use crate::lexer::tokens::{Symbol, TextPart, Token, TokenKind};
+    use crate::Position;
+
+    fn token(kind: TokenKind, column: usize) -> Token {
+        Token { kind, position: Position { line: 1, column } }
+    }
+
+    fn interpolated(input: &str) -> Vec<TextPart> {
+        match tokenize(input.chars()).unwrap().remove(0).kind {
+            TokenKind::InterpolatedText(parts) => parts,
+            kind => panic!("expected interpolated text, found {}", kind),
+        }
+    }
+
+    #[test]
+    fn test_interpolations() {
+        assert_eq!(
+            interpolated("'{@alice.username}@example.com'"),
+            vec![
+                TextPart::Interpolation(vec![
+                    token(TokenKind::Symbol(Symbol::AtSign), 3),
+                    token(TokenKind::Identifier("alice".to_owned()), 4),
+                    token(TokenKind::Symbol(Symbol::Period), 9),
+                    token(TokenKind::Identifier("username".to_owned()), 10),
+                ]),
+                TextPart::Text("@example.com".to_owned()),
+            ]
+        );
+        assert_eq!(
+            interpolated("'isn''t {$a}{$b}'"),
+            vec![
+                TextPart::Text("isn't ".to_owned()),
+                TextPart::Interpolation(vec![token(TokenKind::Variable("a".to_owned()), 10)]),
+                TextPart::Interpolation(vec![token(TokenKind::Variable("b".to_owned()), 14)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_braces_without_interpolation() {
+        for input in ["'{\"a\": 1}'", "'{{@alice}'", "'{}'", "'{'"] {
+            let kind = tokenize(input.chars()).unwrap().remove(0).kind;
+            let expected = match input {
+                "'{{@alice}'" => "'{@alice}'",
+                _ => input,
+            };
+            assert_eq!(kind, TokenKind::Text(expected.to_owned()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_escape_strings() {
+        for (input, expected) in [
+            (r"E'line\nbreak'", "'line\nbreak'"),
+            (r"e'tab\there'", "'tab\there'"),
+            (r"E'it\'s ''quoted'' \\ \q'", r"'it''s ''quoted'' \ q'"),
+            (r"E'é\U0001F49D\x41\101\7'", "'é💝AA\u{7}'"),
+            (r"E'\x4g'", "'\u{4}g'"),
+            (r"E'\x\xg'", "'xxg'"),
+            (r"E'{@alice}'", "'{@alice}'"),
+        ] {
+            assert_eq!(
+                tokenize(input.chars()),
+                Ok(vec![token(TokenKind::Text(expected.to_owned()), 1)]),
+                "{}",
+                input,
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        for (input, sequence) in [
+            (r"E'\u00e'", r"\u00e"),
+            (r"E'\U0001F49'", r"\U0001F49"),
+            (r"E'\ud800'", r"\ud800"),
+            (r"E'\xff'", r"\xff"),
+            (r"E'\0'", r"\0"),
+            (r"E'\u0000'", r"\u0000"),
+        ] {
+            assert_eq!(
+                tokenize(input.chars()),
+                Err(LexError {
+                    kind: LexErrorKind::InvalidEscape(sequence.to_owned()),
+                    position: Position { line: 1, column: 3 },
+                }),
+                "{}",
+                input,
+            );
+        }
+    }
+
+    #[test]
+    fn test_dollar_quoted_strings() {
+        for (input, expected) in [
+            ("$$it's$$", "'it''s'"),
+            ("$body$a $$ or $bod$\n{@alice}$body$", "'a $$ or $bod$\n{@alice}'"),
+            ("$$$$", "''"),
+        ] {
+            assert_eq!(
+                tokenize(input.chars()),
+                Ok(vec![token(TokenKind::Text(expected.to_owned()), 1)]),
+                "{}",
+                input,
+            );
+        }
+        assert_eq!(
+            tokenize("$a $$b$$".chars()),
+            Ok(vec![
+                token(TokenKind::Variable("a".to_owned()), 1),
+                token(TokenKind::Text("'b'".to_owned()), 4),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_unclosed_strings() {
+        for input in ["E'abc", r"E'\'", r"E'\u00", "$$abc$", "$a$abc$b$"] {
+            assert_eq!(
+                tokenize(format!("  {}", input).chars()),
+                Err(LexError {
+                    kind: LexErrorKind::UnclosedString,
+                    position: Position { line: 1, column: 3 },
+                }),
+                "{}",
+                input,
+            );
+        }
+    }
+
+    #[test]
+    fn test_unclosed_interpolation() {
+        for input in ["'{@alice'", "'{$a\n}'", "'{$a"] {
+            assert_eq!(
+                tokenize(input.chars()),
+                Err(LexError {
+                    kind: LexErrorKind::UnclosedInterpolation,
+                    position: Position { line: 1, column: 3 },
+                }),
+                "{}",
+                input,
+            );
+        }
+    }
+}
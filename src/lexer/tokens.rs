@@ -4,8 +4,26 @@ use std::fmt;
 #[derive(Clone, Debug, PartialEq)]
 pub enum Keyword {
     As,
+    Cascade,
+    Conflict,
+    Delete,
+    Do,
+    Expect,
+    Identity,
+    Let,
+    Lookup,
+    Nothing,
+    On,
+    Param,
+    Reset,
+    Restart,
     Schema,
+    Sequence,
+    Set,
+    Sql,
     Table,
+    Truncate,
+    Update,
 }
 
 impl fmt::Display for Keyword {
@@ -14,8 +32,26 @@ impl fmt::Display for Keyword {
 
         match self {
             As => write!(f, "as"),
+            Cascade => write!(f, "cascade"),
+            Conflict => write!(f, "conflict"),
+            Delete => write!(f, "delete"),
+            Do => write!(f, "do"),
+            Expect => write!(f, "expect"),
+            Identity => write!(f, "identity"),
+            Let => write!(f, "let"),
+            Lookup => write!(f, "lookup"),
+            Nothing => write!(f, "nothing"),
+            On => write!(f, "on"),
+            Param => write!(f, "param"),
+            Reset => write!(f, "reset"),
+            Restart => write!(f, "restart"),
             Schema => write!(f, "schema"),
+            Sequence => write!(f, "sequence"),
+            Set => write!(f, "set"),
+            Sql => write!(f, "sql"),
             Table => write!(f, "table"),
+            Truncate => write!(f, "truncate"),
+            Update => write!(f, "update"),
         }
     }
 }
@@ -23,7 +59,11 @@ impl fmt::Display for Keyword {
 #[derive(Clone, Debug, PartialEq)]
 pub enum Symbol {
     AtSign,
+    BracketLeft,
+    BracketRight,
     Comma,
+    DoubleColon,
+    Equals,
     ParenLeft,
     ParenRight,
     Period,
@@ -36,7 +76,11 @@ impl fmt::Display for Symbol {
 
         match self {
             AtSign => write!(f, "@"),
+            BracketLeft => write!(f, "["),
+            BracketRight => write!(f, "]"),
             Comma => write!(f, ","),
+            DoubleColon => write!(f, "::"),
+            Equals => write!(f, "="),
             ParenLeft => write!(f, "("),
             ParenRight => write!(f, ")"),
             Period => write!(f, "."),
@@ -48,7 +92,11 @@ impl fmt::Display for Symbol {
 #[derive(Clone, Debug, PartialEq)]
 pub enum TokenKind {
     Bool(bool),
+    /// Text of a `---` comment, which documents the table or record that follows it
+    DocComment(String),
     Identifier(String),
+    InterpolatedSql(Vec<TextPart>),
+    InterpolatedText(Vec<TextPart>),
     Keyword(Keyword),
     LineSep,
     Number(String),
@@ -56,6 +104,7 @@ pub enum TokenKind {
     SqlFragment(String),
     Symbol(Symbol),
     Text(String),
+    Variable(String),
 }
 
 impl fmt::Display for TokenKind {
@@ -64,7 +113,10 @@ impl fmt::Display for TokenKind {
 
         match self {
             Bool(b) => write!(f, "boolean `{}`", b),
+            DocComment(_) => write!(f, "doc comment"),
             Identifier(i) => write!(f, "identifier `{}`", i),
+            InterpolatedSql(_) => write!(f, "SQL fragment with references"),
+            InterpolatedText(_) => write!(f, "string with interpolations"),
             Keyword(k) => write!(f, "keyword `{}`", k),
             LineSep => write!(f, "newline"),
             Number(n) => write!(f, "number `{}`", n),
@@ -72,6 +124,20 @@ impl fmt::Display for TokenKind {
             SqlFragment(s) => write!(f, "SQL fragment `{}`", s),
             Symbol(s) => write!(f, "symbol `{}`", s),
             Text(s) => write!(f, "string '{}'", s),
+            Variable(v) => write!(f, "variable `${}`", v),
+        }
+    }
+}
+
+impl TokenKind {
+    /// Checks whether the token is the given keyword. Apart from `as`, `schema`, and
+    /// `table`, keywords are contextual: they are lexed as identifiers so they can still
+    /// name columns and records, and only the parser treats them as keywords.
+    pub fn is_keyword(&self, keyword: &Keyword) -> bool {
+        match self {
+            TokenKind::Keyword(k) => k == keyword,
+            TokenKind::Identifier(i) => *i == keyword.to_string(),
+            _ => false,
         }
     }
 }
@@ -82,6 +148,16 @@ pub struct Token {
     pub position: Position,
 }
 
+/// Part of a string literal with interpolations, eg. `'user-{$i}'`, or of a SQL
+/// fragment with references, eg. `` `@order.total * 2` ``.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TextPart {
+    /// Text between interpolations, with its escaped quotes unescaped, or SQL as is
+    Text(String),
+    /// Tokens of the reference or variable
+    Interpolation(Vec<Token>),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,8 +167,26 @@ mod tests {
         use Keyword::*;
 
         assert_eq!(format!("{}", As), "as");
+        assert_eq!(format!("{}", Cascade), "cascade");
+        assert_eq!(format!("{}", Conflict), "conflict");
+        assert_eq!(format!("{}", Delete), "delete");
+        assert_eq!(format!("{}", Do), "do");
+        assert_eq!(format!("{}", Expect), "expect");
+        assert_eq!(format!("{}", Identity), "identity");
+        assert_eq!(format!("{}", Let), "let");
+        assert_eq!(format!("{}", Lookup), "lookup");
+        assert_eq!(format!("{}", Nothing), "nothing");
+        assert_eq!(format!("{}", On), "on");
+        assert_eq!(format!("{}", Param), "param");
+        assert_eq!(format!("{}", Reset), "reset");
+        assert_eq!(format!("{}", Restart), "restart");
         assert_eq!(format!("{}", Schema), "schema");
+        assert_eq!(format!("{}", Sequence), "sequence");
+        assert_eq!(format!("{}", Set), "set");
+        assert_eq!(format!("{}", Sql), "sql");
         assert_eq!(format!("{}", Table), "table");
+        assert_eq!(format!("{}", Truncate), "truncate");
+        assert_eq!(format!("{}", Update), "update");
     }
 
     #[test]
@@ -100,7 +194,11 @@ mod tests {
         use Symbol::*;
 
         assert_eq!(format!("{}", AtSign), "@");
+        assert_eq!(format!("{}", BracketLeft), "[");
+        assert_eq!(format!("{}", BracketRight), "]");
         assert_eq!(format!("{}", Comma), ",");
+        assert_eq!(format!("{}", DoubleColon), "::");
+        assert_eq!(format!("{}", Equals), "=");
         assert_eq!(format!("{}", ParenLeft), "(");
         assert_eq!(format!("{}", ParenRight), ")");
         assert_eq!(format!("{}", Period), ".");
@@ -114,12 +212,28 @@ mod tests {
         use TokenKind::*;
 
         assert_eq!(format!("{}", Bool(true)), "boolean `true`");
+        assert_eq!(format!("{}", DocComment("foo".to_string())), "doc comment");
         assert_eq!(format!("{}", Identifier("foo".to_string())), "identifier `foo`");
+        assert_eq!(format!("{}", InterpolatedSql(vec![])), "SQL fragment with references");
+        assert_eq!(format!("{}", InterpolatedText(vec![])), "string with interpolations");
         assert_eq!(format!("{}", Keyword(As)), "keyword `as`");
         assert_eq!(format!("{}", LineSep), "newline");
         assert_eq!(format!("{}", Number("42".to_string())), "number `42`");
         assert_eq!(format!("{}", QuotedIdentifier("foo".to_string())), "quoted identifier `\"foo\"`");
         assert_eq!(format!("{}", Symbol(Comma)), "symbol `,`");
         assert_eq!(format!("{}", Text("foo".to_string())), "string 'foo'");
+        assert_eq!(format!("{}", Variable("foo".to_string())), "variable `$foo`");
+    }
+
+    #[test]
+    fn test_is_keyword() {
+        use super::Keyword::{As, On};
+        use TokenKind::*;
+
+        assert!(Keyword(As).is_keyword(&As));
+        assert!(Identifier("on".to_string()).is_keyword(&On));
+        assert!(!Identifier("On".to_string()).is_keyword(&On));
+        assert!(!QuotedIdentifier("\"on\"".to_string()).is_keyword(&On));
+        assert!(!Text("on".to_string()).is_keyword(&On));
     }
 }
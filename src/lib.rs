@@ -1,33 +1,104 @@
 pub mod analyzer;
+pub mod catalog;
+pub mod client;
 pub mod error;
+pub mod generator;
 pub mod lexer;
 pub mod loader;
+pub mod manifest;
 pub mod parser;
 mod position;
+pub mod report;
+pub mod rng;
 
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use analyzer::{Analyzer, ValidatedParseTree};
+use catalog::Catalog;
 use error::HldrError;
+use manifest::Manifest;
 pub use position::Position;
+use report::{Outcome, Report};
+use rng::Rng;
 
-#[derive(Clone, Default, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Options {
     #[serde(default)]
     pub commit: bool,
 
-    #[serde(default = "default_data_file")]
-    pub data_file: PathBuf,
+    /// Data files to load in order, which the options file can give as
+    /// either `data_file` or `data_files`
+    #[serde(default = "default_data_files")]
+    pub data_files: Vec<PathBuf>,
 
     #[serde(default)]
     pub database_conn: String,
+
+    /// Roll back only the records that fail to load, along with any records
+    /// referencing them, instead of aborting the whole transaction
+    #[serde(default)]
+    pub keep_going: bool,
+
+    /// Directory to write the manifest of each committed run to, recording the
+    /// rows inserted so that they can be deleted with `hldr teardown`
+    #[serde(default)]
+    pub manifest_dir: Option<PathBuf>,
+
+    /// Values for the params declared in data files, which are always strings
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+
+    /// Schema search path to set for the transaction, eg. `schema1, public`
+    #[serde(default)]
+    pub search_path: Option<String>,
+
+    /// Seed for everything random in a run (eg. generated values), which is logged at
+    /// the start of every run so that it can be reproduced; random if not given
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// Validate data files against the database catalog before inserting anything
+    #[serde(default)]
+    pub validate: bool,
+
+    #[serde(flatten)]
+    pub tls: client::TlsOptions,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            commit: false,
+            data_files: default_data_files(),
+            database_conn: String::new(),
+            keep_going: false,
+            manifest_dir: None,
+            params: HashMap::new(),
+            search_path: None,
+            seed: None,
+            validate: false,
+            tls: client::TlsOptions::default(),
+        }
+    }
 }
 
 impl Options {
-    pub fn new(filepath: &PathBuf) -> Result<Option<Self>, String> {
+    /// Reads the options file, if it exists, with the values of the given profile
+    /// from its `[profiles.<name>]` section taking precedence over top-level values.
+    pub fn new(filepath: &PathBuf, profile: Option<&str>) -> Result<Option<Self>, String> {
         if !filepath.exists() {
-            return Ok(None);
+            return match profile {
+                Some(name) => Err(format!(
+                    "profile `{}` given but {} does not exist",
+                    name,
+                    filepath.display()
+                )),
+                None => Ok(None),
+            };
         }
 
         if !filepath.is_file() {
@@ -35,35 +106,414 @@ impl Options {
         }
 
         let contents = fs::read_to_string(filepath).map_err(|e| e.to_string())?;
+        let mut value: toml::Value = toml::from_str(&contents).map_err(|e| e.to_string())?;
+
+        interpolate_value(&mut value, &|var| env::var(var).ok())?;
+
+        let value = apply_profile(value, profile)?;
+
+        Ok(Some(value.try_into().map_err(|e| e.to_string())?))
+    }
+}
+
+fn default_data_files() -> Vec<PathBuf> {
+    vec![PathBuf::from("place.hldr")]
+}
+
+/// Merges the given profile's values over the top-level values, removing all profiles.
+fn apply_profile(value: toml::Value, profile: Option<&str>) -> Result<toml::Value, String> {
+    let mut options = into_table(value, "options file")?;
+    let profiles = options.remove("profiles");
+
+    normalize_data_files(&mut options)?;
+
+    if let Some(name) = profile {
+        let mut profiles = match profiles {
+            Some(profiles) => into_table(profiles, "`profiles`")?,
+            None => toml::value::Table::new(),
+        };
+        let profile = profiles
+            .remove(name)
+            .ok_or_else(|| format!("profile `{}` not found in options file", name))?;
+        let mut profile = into_table(profile, &format!("profile `{}`", name))?;
+
+        normalize_data_files(&mut profile)?;
+        options.extend(profile);
+    }
+
+    Ok(toml::Value::Table(options))
+}
+
+fn into_table(value: toml::Value, name: &str) -> Result<toml::value::Table, String> {
+    match value {
+        toml::Value::Table(table) => Ok(table),
+        _ => Err(format!("{} must be a table", name)),
+    }
+}
+
+/// Replaces a single `data_file` with the equivalent `data_files` list, so that
+/// either can override the other when applying a profile.
+fn normalize_data_files(table: &mut toml::value::Table) -> Result<(), String> {
+    if let Some(file) = table.remove("data_file") {
+        if table.contains_key("data_files") {
+            return Err("only one of `data_file` and `data_files` can be given".to_owned());
+        }
 
-        Ok(Some(toml::from_str(&contents).map_err(|e| e.to_string())?))
+        table.insert("data_files".to_owned(), toml::Value::Array(vec![file]));
     }
+
+    Ok(())
 }
 
-fn default_data_file() -> PathBuf {
-    PathBuf::from("place.hldr")
+/// Replaces `${VAR}` in every string of the options file with the value of the
+/// environment variable, so that eg. passwords needn't be committed alongside it.
+fn interpolate_value(
+    value: &mut toml::Value,
+    env: &dyn Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    match value {
+        toml::Value::String(s) => *s = interpolate(s, env)?,
+        toml::Value::Array(values) => {
+            for value in values {
+                interpolate_value(value, env)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, value) in table.iter_mut() {
+                interpolate_value(value, env)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Expands `${VAR}` and `${VAR:-default}`, where `$${` is a literal `${`.
+fn interpolate(s: &str, env: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(i) = rest.find("${") {
+        if rest[..i].ends_with('$') {
+            result.push_str(&rest[..i]);
+            result.push('{');
+            rest = &rest[i + 2..];
+            continue;
+        }
+
+        result.push_str(&rest[..i]);
+
+        let end = rest[i..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed `${{` in options file value `{}`", s))?;
+        let expr = &rest[i + 2..i + end];
+        let (var, default) = match expr.split_once(":-") {
+            Some((var, default)) => (var, Some(default)),
+            None => (expr, None),
+        };
+
+        match env(var).or_else(|| default.map(String::from)) {
+            Some(value) => result.push_str(&value),
+            None => return Err(format!("environment variable `{}` is not set", var)),
+        }
+
+        rest = &rest[i + end + 1..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
 }
 
 pub fn place(options: &Options) -> Result<(), HldrError> {
-    let input = fs::read_to_string(&options.data_file)?;
-    let tokens = lexer::tokenize(input.chars())?;
-    let parse_tree = parser::parse(tokens.into_iter())?;
-    let parse_tree = analyzer::analyze(parse_tree)?;
-    let mut client = loader::new_client(&options.database_conn)?;
-    let mut transaction = client.transaction()?;
+    place_with_report(options, &mut Report::default())
+}
+
+/// Same as `place` but also populates the given report with named records,
+/// per-table statistics, and the outcome of the run, including any error.
+///
+/// Records that failed to load with `keep_going` set are added to the report's
+/// `failures` rather than returned as an error.
+pub fn place_with_report(options: &Options, report: &mut Report) -> Result<(), HldrError> {
+    let result = run(options, report);
+
+    if let Err(e) = &result {
+        report.add_error(e);
+    }
+
+    result
+}
+
+/// Validates the data files against the catalog without inserting anything,
+/// reading the catalog from the database if no snapshot is given.
+pub fn check(options: &Options, catalog: Option<Catalog>) -> Result<(), HldrError> {
+    let trees = parse_files(options)?;
+
+    for (file, tree) in &trees {
+        for warning in analyzer::warnings(tree) {
+            log::warn!("{}: {}", file.display(), warning);
+        }
+    }
+
+    match catalog {
+        Some(mut catalog) => {
+            if let Some(search_path) = &options.search_path {
+                catalog.set_search_path(search_path);
+            }
+
+            validate(&trees, &catalog)
+        }
+        None => {
+            let mut client = client::new_client(&options.database_conn, &options.tls)?;
+            let mut transaction = begin(&mut client, options)?;
+            let catalog = Catalog::load(&mut transaction)?;
+
+            validate(&trees, &catalog)
+        }
+    }
+}
+
+/// Reads the catalog from the database, eg. to save as a snapshot for `check`.
+pub fn snapshot(options: &Options) -> Result<Catalog, HldrError> {
+    let mut client = client::new_client(&options.database_conn, &options.tls)?;
+    let mut transaction = begin(&mut client, options)?;
+
+    Ok(Catalog::load(&mut transaction)?)
+}
+
+fn run(options: &Options, report: &mut Report) -> Result<(), HldrError> {
+    // Every run has a seed, so that any run can be reproduced with the one it logs
+    let seed = options.seed.unwrap_or_else(rng::random_seed);
+    let mut rng = Rng::new(seed);
+    log::info!("Using seed {}", seed);
+    report.seed = Some(seed);
+
+    // Parse everything up front so that mistakes are caught before anything is inserted
+    let trees = parse_files(options)?;
+
+    let mut client = client::new_client(&options.database_conn, &options.tls)?;
+    let mut transaction = begin(&mut client, options)?;
+
+    if options.validate {
+        let catalog = Catalog::load(&mut transaction)?;
+
+        validate(&trees, &catalog)?;
+    }
+
+    // Nothing is recorded unless the run is committed, since there would be nothing
+    // to tear down otherwise
+    let mut manifest = match (&options.manifest_dir, options.commit) {
+        (Some(_), true) => Some(Manifest::new(run_id(&mut transaction)?)),
+        _ => None,
+    };
+    let mut failures = Vec::new();
+    // Shared by every file, so that each can refer to what the files before it declared
+    let mut state = loader::LoadState::default();
+
+    for (file, tree) in trees {
+        let file_failures = loader::load(
+            &mut transaction,
+            tree,
+            &mut state,
+            report,
+            manifest.as_mut(),
+            &mut rng,
+            options.keep_going,
+        )
+        .map_err(|e| HldrError::from(e).in_file(file))?;
 
-    loader::load(&mut transaction, parse_tree)?;
+        failures.extend(
+            file_failures
+                .into_iter()
+                .map(|e| HldrError::from(e).in_file(file)),
+        );
+    }
+
+    if !failures.is_empty() {
+        for failure in &failures {
+            log::error!("{}", failure);
+            report.add_failure(failure);
+        }
+
+        log::error!(
+            "{} {} failed to load",
+            failures.len(),
+            if failures.len() == 1 {
+                "record"
+            } else {
+                "records"
+            },
+        );
+    }
+
+    // The manifest is written before committing so that inserted rows are never left
+    // without one, and removed again if the commit fails
+    let manifest_path = match (&options.manifest_dir, &manifest) {
+        (Some(dir), Some(manifest)) if !manifest.is_empty() => {
+            let path = Manifest::path(dir, &manifest.run_id);
+            manifest.write(&path)?;
+            report.run_id = Some(manifest.run_id.clone());
+            Some(path)
+        }
+        _ => None,
+    };
+
+    if options.commit {
+        log::info!("Committing changes");
+
+        if let Err(e) = transaction.commit() {
+            if let Some(path) = &manifest_path {
+                let _ = fs::remove_file(path);
+            }
+            report.run_id = None;
+            return Err(e.into());
+        }
+
+        report.outcome = Outcome::Committed;
+
+        if let Some(run_id) = &report.run_id {
+            log::info!(
+                "Recorded inserted rows in {}, run `hldr teardown {}` to delete them",
+                manifest_path.expect("manifest written").display(),
+                run_id,
+            );
+        }
+    } else {
+        log::info!("Rolling back changes, pass `--commit` to apply");
+        report.outcome = Outcome::RolledBack;
+    }
+
+    Ok(())
+}
+
+/// Identifies a run by when its transaction started, eg. `20240430-225727-333`.
+fn run_id(transaction: &mut postgres::Transaction) -> Result<String, HldrError> {
+    let row = transaction.query_one(
+        r#"SELECT to_char(now() AT TIME ZONE 'UTC', 'YYYYMMDD-HH24MISS-MS')"#,
+        &[],
+    )?;
+
+    Ok(row.get(0))
+}
+
+/// Deletes every row recorded in the manifest of the given run within one transaction,
+/// deleting from tables before the tables their foreign keys reference.
+/// The manifest is removed once the deletion is committed.
+pub fn teardown(options: &Options, manifest_dir: &Path, run_id: &str) -> Result<(), HldrError> {
+    let path = Manifest::path(manifest_dir, run_id);
+    let manifest = Manifest::read(&path)?;
+
+    let mut client = client::new_client(&options.database_conn, &options.tls)?;
+    let mut transaction = begin(&mut client, options)?;
+    let catalog = Catalog::load(&mut transaction)?;
+
+    for table in manifest.deletion_order(&catalog) {
+        let statement = match table.delete_statement() {
+            Some(statement) => statement,
+            None => continue,
+        };
+        log::debug!("{}", statement);
+
+        let deleted = transaction.execute(statement.as_str(), &[])? as usize;
+
+        if deleted < table.rows.len() {
+            log::warn!(
+                "{}: {} of {} recorded rows no longer exist",
+                table.table,
+                table.rows.len() - deleted,
+                table.rows.len(),
+            );
+        }
+
+        log::info!(
+            "{}: deleted {} {}",
+            table.table,
+            deleted,
+            if deleted == 1 { "row" } else { "rows" },
+        );
+    }
 
     if options.commit {
-        println!("Committing changes");
+        log::info!("Committing changes");
         transaction.commit()?;
+        fs::remove_file(&path)?;
     } else {
-        println!("Rolling back changes, pass `--commit` to apply")
+        log::info!("Rolling back changes, pass `--commit` to apply");
     }
 
     Ok(())
 }
 
+fn begin<'a>(
+    client: &'a mut postgres::Client,
+    options: &Options,
+) -> Result<postgres::Transaction<'a>, HldrError> {
+    let mut transaction = client.transaction()?;
+
+    if let Some(search_path) = &options.search_path {
+        transaction.execute("SELECT set_config('search_path', $1, true)", &[search_path])?;
+    }
+
+    Ok(transaction)
+}
+
+/// Parses and analyzes the data files in order, so that each one can refer to
+/// everything declared in the files before it.
+fn parse_files(options: &Options) -> Result<Vec<(&Path, ValidatedParseTree)>, HldrError> {
+    let mut analyzer = Analyzer::default();
+    let mut declared = HashSet::new();
+    let mut trees = Vec::new();
+
+    for file in &options.data_files {
+        let tree = parse_file(file, &options.params, &mut declared, &mut analyzer)
+            .map_err(|e| e.in_file(file))?;
+
+        trees.push((file.as_path(), tree));
+    }
+
+    // Most likely a typo, which would otherwise silently leave the default in place
+    for name in options.params.keys() {
+        if !declared.contains(name) {
+            log::warn!("param `{}` is not declared in any data file", name);
+        }
+    }
+
+    Ok(trees)
+}
+
+fn parse_file(
+    file: &Path,
+    params: &HashMap<String, String>,
+    declared: &mut HashSet<String>,
+    analyzer: &mut Analyzer,
+) -> Result<ValidatedParseTree, HldrError> {
+    let input = fs::read_to_string(file)?;
+    let tokens = lexer::tokenize(input.chars())?;
+    let parse_tree = parser::parse(tokens.into_iter())?;
+
+    declared.extend(parse_tree.nodes.iter().filter_map(|node| match node {
+        parser::nodes::StructuralNode::Param(param) => Some(param.name.clone()),
+        _ => None,
+    }));
+
+    Ok(analyzer.analyze(parse_tree, params)?)
+}
+
+fn validate(trees: &[(&Path, ValidatedParseTree)], catalog: &Catalog) -> Result<(), HldrError> {
+    let mut validator = analyzer::catalog::Validator::new(catalog);
+
+    for (file, tree) in trees {
+        validator
+            .validate(tree)
+            .map_err(|e| HldrError::from(e).in_file(file))?;
+    }
+
+    log::debug!("Validated data files against the database catalog");
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod root_tests {
     /*
@@ -81,4 +531,141 @@ mod root_tests {
             place(&options, false).unwrap();
         }
     */
+
+    use super::{apply_profile, interpolate, parse_files, Options};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn env(var: &str) -> Option<String> {
+        match var {
+            "PASSWORD" => Some("passy".to_owned()),
+            "EMPTY" => Some("".to_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        for (input, expected) in [
+            ("user=me", "user=me"),
+            ("password=${PASSWORD}", "password=passy"),
+            ("${PASSWORD}${PASSWORD}", "passypassy"),
+            ("${EMPTY}", ""),
+            ("${MISSING:-default}", "default"),
+            ("${PASSWORD:-default}", "passy"),
+            ("$${PASSWORD}", "${PASSWORD}"),
+            ("$PASSWORD $", "$PASSWORD $"),
+        ] {
+            assert_eq!(interpolate(input, &env).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        assert!(interpolate("${MISSING}", &env).is_err());
+        assert!(interpolate("${PASSWORD", &env).is_err());
+    }
+
+    fn options(toml: &str, profile: Option<&str>) -> Result<Options, String> {
+        let value = toml::from_str(toml).unwrap();
+
+        Ok(apply_profile(value, profile)?.try_into().unwrap())
+    }
+
+    #[test]
+    fn test_profiles() {
+        let toml = "
+            database_conn = 'dbname=shared'
+            sslmode = 'require'
+            data_file = 'shared.hldr'
+
+            [profiles.dev]
+            commit = true
+            data_files = ['dev1.hldr', 'dev2.hldr']
+            search_path = 'dev, public'
+
+            [profiles.test]
+            database_conn = 'dbname=test'
+        ";
+
+        let shared = options(toml, None).unwrap();
+        assert!(!shared.commit);
+        assert_eq!(shared.data_files, vec![PathBuf::from("shared.hldr")]);
+        assert_eq!(shared.database_conn, "dbname=shared");
+        assert_eq!(shared.search_path, None);
+
+        let dev = options(toml, Some("dev")).unwrap();
+        assert!(dev.commit);
+        assert_eq!(
+            dev.data_files,
+            vec![PathBuf::from("dev1.hldr"), PathBuf::from("dev2.hldr")]
+        );
+        assert_eq!(dev.database_conn, "dbname=shared");
+        assert_eq!(dev.search_path.as_deref(), Some("dev, public"));
+        assert!(dev.tls.sslmode.is_some());
+
+        let test = options(toml, Some("test")).unwrap();
+        assert_eq!(test.data_files, vec![PathBuf::from("shared.hldr")]);
+        assert_eq!(test.database_conn, "dbname=test");
+    }
+
+    #[test]
+    fn test_profile_errors() {
+        assert!(options("", Some("dev")).is_err());
+        assert!(options("[profiles.test]", Some("dev")).is_err());
+        assert!(options("profiles = 1", Some("dev")).is_err());
+        assert!(options("data_file = 'a'\ndata_files = ['b']", None).is_err());
+        assert_eq!(
+            options("", None).unwrap().data_files,
+            vec![PathBuf::from("place.hldr")]
+        );
+    }
+
+    #[test]
+    fn test_references_across_files() {
+        let dir = std::env::temp_dir().join(format!("hldr-files-{}", std::process::id()));
+        let file = |name: &str, contents: &str| {
+            let path = dir.join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        };
+        fs::create_dir_all(&dir).unwrap();
+
+        let users = file(
+            "users.hldr",
+            "let role = 'admin'\ntable users ( alice (name 'Alice') )",
+        );
+        let posts = file(
+            "posts.hldr",
+            "table posts ( (author @users.alice.name, role $role) )",
+        );
+        let typo = file("typo.hldr", "table posts ( (author @users.bob.name) )");
+
+        let error = |data_files: Vec<PathBuf>| {
+            let options = Options {
+                data_files,
+                ..Options::default()
+            };
+            parse_files(&options).err().map(|e| e.to_string())
+        };
+
+        assert_eq!(error(vec![users.clone(), posts.clone()]), None);
+        assert_eq!(
+            error(vec![posts.clone(), users.clone()]),
+            Some(format!(
+                "{}: record `users.alice` not found",
+                posts.display()
+            ))
+        );
+        assert_eq!(
+            error(vec![users.clone(), typo.clone()]),
+            Some(format!("{}: record `users.bob` not found", typo.display()))
+        );
+        assert_eq!(
+            error(vec![users.clone(), users.clone()]),
+            Some(format!("{}: duplicate variable `$role`", users.display()))
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
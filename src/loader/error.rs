@@ -2,68 +2,110 @@ use std::{error::Error, fmt};
 
 use postgres::error::Error as PostgresError;
 
-#[derive(Debug)]
-pub enum ClientErrorKind {
-    Config,
-    Connection,
-}
+use crate::Position;
 
 #[derive(Debug)]
-pub struct ClientError {
-    error: PostgresError,
-    kind: ClientErrorKind,
+pub enum LoadErrorKind {
+    ColumnNotFound { record: String, column: String },
+    ConflictingRowNotFound { table: String },
+    Database(PostgresError),
+    DependencyFailed { record: String },
+    DuplicateRecord { scope: String, record: String },
+    FragmentMultipleColumns { fragment: String, columns: usize },
+    FragmentMultipleRows { fragment: String },
+    FragmentNoRows { fragment: String },
+    LookupMultipleRows { table: String, rows: usize },
+    LookupNoRows { table: String },
+    NullInterpolated { column: String },
+    SqlStatementRows { name: String, rows: usize },
+    UnexpectedRowCount { table: String, expected: u64, rows: usize },
 }
 
-impl ClientError {
-    pub fn config_error(error: PostgresError) -> Self {
-        Self {
-            kind: ClientErrorKind::Config,
-            error,
-        }
-    }
-
-    pub fn connection_error(error: PostgresError) -> Self {
-        Self {
-            kind: ClientErrorKind::Connection,
-            error,
-        }
-    }
-}
-
-impl Error for ClientError {
-    fn source(&self) -> Option<&(dyn Error + 'static)> {
-        Some(&self.error)
-    }
-}
-
-impl fmt::Display for ClientError {
+impl fmt::Display for LoadErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use ClientErrorKind::*;
+        use LoadErrorKind::*;
 
-        match self.kind {
-            Config => write!(f, "Config error: {}", self.error),
-            Connection => write!(f, "Connection error: {}", self.error),
+        match self {
+            ColumnNotFound { record, column } => {
+                write!(f, "no column `{}` in record `{}`", column, record)
+            }
+            ConflictingRowNotFound { table } => {
+                write!(f, "conflicting row in {} not found by the conflict columns", table)
+            }
+            Database(e) => {
+                write!(f, "{}", e)
+            }
+            DependencyFailed { record } => {
+                write!(f, "skipped because referenced record `{}` failed to load", record)
+            }
+            DuplicateRecord { scope, record } => {
+                write!(f, "duplicate record `{}` in scope `{}`", record, scope)
+            }
+            FragmentMultipleColumns { fragment, columns } => {
+                write!(
+                    f,
+                    "expected one column from SQL fragment `{}`, found {}",
+                    fragment, columns
+                )
+            }
+            FragmentMultipleRows { fragment } => {
+                write!(f, "expected one row from SQL fragment `{}`, found multiple", fragment)
+            }
+            FragmentNoRows { fragment } => {
+                write!(f, "expected one row from SQL fragment `{}`, found none", fragment)
+            }
+            LookupMultipleRows { table, rows } => {
+                write!(f, "expected one row in {} to match lookup, found {}", table, rows)
+            }
+            LookupNoRows { table } => {
+                write!(f, "expected one row in {} to match lookup, found none", table)
+            }
+            NullInterpolated { column } => {
+                write!(f, "cannot interpolate null into the value of column `{}`", column)
+            }
+            SqlStatementRows { name, rows } => {
+                write!(f, "expected SQL statement `{}` to return one row, found {}", name, rows)
+            }
+            UnexpectedRowCount { table, expected, rows } => {
+                write!(
+                    f,
+                    "expected {} {} in {} to be affected, found {}",
+                    expected,
+                    if *expected == 1 { "row" } else { "rows" },
+                    table,
+                    rows
+                )
+            }
         }
     }
 }
 
 #[derive(Debug)]
-pub struct LoadError(PostgresError);
+pub struct LoadError {
+    pub kind: LoadErrorKind,
+    pub position: Position,
+}
 
 impl LoadError {
-    pub fn new(e: PostgresError) -> Self {
-        Self(e)
+    pub fn database(error: PostgresError, position: Position) -> Self {
+        Self {
+            kind: LoadErrorKind::Database(error),
+            position,
+        }
     }
 }
 
 impl Error for LoadError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        Some(&self.0)
+        match &self.kind {
+            LoadErrorKind::Database(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
 impl fmt::Display for LoadError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        self.0.fmt(f)
+        write!(f, "{} at {}", self.kind, self.position)
     }
 }
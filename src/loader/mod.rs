@@ -1,47 +1,94 @@
 pub mod error;
 
-use crate::analyzer::ValidatedParseTree;
+use crate::analyzer::{record_level_key, ValidatedParseTree};
+use crate::generator::Fake;
 use crate::parser::nodes::{
     Attribute,
+    ColumnLevelReference,
+    ConflictAction,
+    OnConflict,
+    QualifiedName,
+    Record,
+    RecordKind,
     Reference,
     ReferencedColumn,
+    ResetSequence,
+    Segment,
+    Sql,
     StructuralIdentity,
     StructuralNode,
     Table,
+    Truncate,
     Value,
+    Variable,
 };
-use error::{ClientError, LoadError};
-use postgres::{config::Config, Client, NoTls, SimpleQueryMessage, SimpleQueryRow, Transaction};
-use std::{collections::HashMap, str::FromStr, time::Duration};
+use crate::manifest::{self, Manifest, TableRows};
+use crate::report::{Report, RowCounts};
+use crate::rng::Rng;
+use crate::Position;
+use bytes::BytesMut;
+use error::{LoadError, LoadErrorKind};
+use postgres::types::{to_sql_checked, Format, IsNull, ToSql, Type};
+use postgres::{SimpleQueryMessage, SimpleQueryRow, Transaction};
+use std::{collections::HashMap, error::Error, time::Instant};
 
-// TODO: move this
-pub fn new_client(connstr: &str) -> Result<Client, ClientError> {
-    let mut config = Config::from_str(connstr).map_err(ClientError::config_error)?;
-
-    config.application_name("hldr");
-
-    if config.get_connect_timeout().is_none() {
-        config.connect_timeout(Duration::new(30, 0));
-    }
+type LoadResult<T> = Result<T, LoadError>;
+/// Rows of named records and SQL statements, or `None` for records that failed to load,
+/// so that references resolve to the same record they did when analyzed
+type RefMap = HashMap<String, Option<RefRow>>;
+/// Types of the columns of each table, keyed by qualified table name
+type ColumnTypes = HashMap<String, HashMap<String, Type>>;
+/// Values of the variables declared so far as SQL literals, keyed by name
+type VarMap = HashMap<String, String>;
 
-    config.connect(NoTls).map_err(ClientError::connection_error)
+/// Row returned for a named record or SQL statement, which later records can reference.
+struct RefRow {
+    row: SimpleQueryRow,
+    /// Qualified name of the table the row is from, if any, so that references to it
+    /// in SQL fragments can be bound with the types of its columns
+    table: Option<String>,
 }
 
-type LoadResult<T> = Result<T, LoadError>;
-type RefMap = HashMap<String, SimpleQueryRow>;
+/// Everything loaded so far that later records can refer to, which is shared by every
+/// data file loaded in the same transaction.
+#[derive(Default)]
+pub struct LoadState {
+    column_types: ColumnTypes,
+    refmap: RefMap,
+    variables: VarMap,
+}
 
-struct Loader<'a, 'b>
+struct Loader<'a, 'b, 'r>
 where
     'b: 'a,
 {
-    refmap: RefMap,
+    /// Records that failed to load when continuing on errors
+    failures: Vec<LoadError>,
+    keep_going: bool,
+    /// Primary keys of inserted rows, recorded when the run has a manifest
+    manifest: Option<&'r mut Manifest>,
+    report: &'r mut Report,
+    rng: &'r mut Rng,
+    state: &'r mut LoadState,
     transaction: &'a mut Transaction<'b>,
 }
 
-impl<'a, 'b> Loader<'a, 'b> {
-    fn new(transaction: &'a mut Transaction<'b>) -> Self {
+impl<'a, 'b, 'r> Loader<'a, 'b, 'r> {
+    fn new(
+        transaction: &'a mut Transaction<'b>,
+        state: &'r mut LoadState,
+        report: &'r mut Report,
+        manifest: Option<&'r mut Manifest>,
+        rng: &'r mut Rng,
+        keep_going: bool,
+    ) -> Self {
         Self {
-            refmap: HashMap::new(),
+            failures: Vec::new(),
+            keep_going,
+            manifest,
+            report,
+            rng,
+            state,
             transaction,
         }
     }
@@ -70,45 +117,522 @@ impl<'a, 'b> Loader<'a, 'b> {
             }
         };
 
+        let started = Instant::now();
+        let mut counts = RowCounts::default();
+        let upserts = matches!(
+            table.on_conflict,
+            Some(OnConflict {
+                action: ConflictAction::DoUpdate,
+                ..
+            })
+        );
+        let mut recorded = match self.manifest {
+            Some(_) => self.manifest_rows(&qualified_table_name, table)?,
+            None => None,
+        };
+
         for record in &table.nodes {
-            let row = self.insert(&qualified_table_name, &table_scope, &record.nodes)?;
+            let key = record.name.as_ref().map(|name| format!("{}.{}", table_scope, name));
+
+            if let (Some(key), Some(name)) = (&key, &record.name) {
+                if self.state.refmap.contains_key(key) {
+                    return Err(LoadError {
+                        kind: LoadErrorKind::DuplicateRecord {
+                            scope: table_scope,
+                            record: name.clone(),
+                        },
+                        position: record.position,
+                    });
+                }
+            }
+
+            let target = InsertTarget {
+                qualified_table_name: &qualified_table_name,
+                table_scope: &table_scope,
+                on_conflict: table.on_conflict.as_ref(),
+            };
+            let result = match self.keep_going {
+                true => self.load_in_savepoint(&target, record)?,
+                false => self.load_record(&target, record),
+            };
 
-            if let Some(name) = &record.name {
-                let key = format!("{}.{}", table_scope, name);
+            let loaded = match result {
+                Ok(loaded) => loaded,
+                // Records referencing this one will fail too, since it has no row
+                Err(e) if self.keep_going => {
+                    log::debug!("Rolled back record at {}: {}", record.position, e.kind);
+                    self.failures.push(e);
 
-                if self.refmap.insert(key, row).is_some() {
-                    panic!("duplicate record in table {}: {}", table_scope, name);
+                    if let Some(key) = key {
+                        self.state.refmap.insert(key, None);
+                    }
+                    continue;
                 }
+                Err(e) => return Err(e),
+            };
+
+            match record.kind {
+                RecordKind::Insert if upserts => counts.upserted += loaded.rows,
+                RecordKind::Insert => counts.inserted += loaded.rows,
+                RecordKind::Lookup => {}
+                RecordKind::Update { .. } => counts.updated += loaded.rows,
+                RecordKind::Delete { .. } => counts.deleted += loaded.rows,
+            }
+
+            if let (Some(recorded), Some(row), RecordKind::Insert, 1) =
+                (&mut recorded, &loaded.row, &record.kind, loaded.rows)
+            {
+                let values: Option<Vec<_>> = recorded
+                    .primary_key
+                    .iter()
+                    .map(|column| row.get(column.as_str()).map(str::to_owned))
+                    .collect();
+                recorded.rows.extend(values);
+            }
+
+            if let (Some(key), Some(row)) = (key, loaded.row) {
+                self.report.add_record(key.clone(), &row);
+                self.state.refmap.insert(
+                    key,
+                    Some(RefRow {
+                        row,
+                        table: Some(qualified_table_name.clone()),
+                    }),
+                );
+            }
+        }
+
+        if let (Some(manifest), Some(recorded)) = (&mut self.manifest, recorded) {
+            if !recorded.rows.is_empty() {
+                manifest.tables.push(recorded);
+            }
+        }
+
+        let table_name = match schema {
+            Some(schema) => format!("{}.{}", schema.name, table.identity.name),
+            None => table.identity.name.clone(),
+        };
+        self.report.add_table(table_name, counts, started.elapsed());
+
+        let plural = |n: usize| if n == 1 { "row" } else { "rows" };
+        let mut summary = format!("inserted {} {}", counts.inserted, plural(counts.inserted));
+
+        if counts.updated > 0 {
+            summary.push_str(&format!(", updated {} {}", counts.updated, plural(counts.updated)));
+        }
+        if counts.upserted > 0 {
+            let upserted = format!(", upserted {} {}", counts.upserted, plural(counts.upserted));
+            summary.push_str(&upserted);
+        }
+        if counts.deleted > 0 {
+            summary.push_str(&format!(", deleted {} {}", counts.deleted, plural(counts.deleted)));
+        }
+
+        log::info!("{}: {} in {:.2?}", qualified_table_name, summary, started.elapsed());
+
+        Ok(())
+    }
+
+    /// Executes a raw SQL statement, storing the one row it returns if it is named
+    /// so that later records can reference it.
+    fn run_sql(&mut self, sql: &Sql) -> LoadResult<()> {
+        log::debug!("{}", sql.statement);
+
+        let mut rows: Vec<_> = self
+            .transaction
+            .simple_query(&sql.statement)
+            .map_err(|e| LoadError::database(e, sql.position))?
+            .into_iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => Some(row),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(name) = &sql.name {
+            if rows.len() != 1 {
+                return Err(LoadError {
+                    kind: LoadErrorKind::SqlStatementRows {
+                        name: name.clone(),
+                        rows: rows.len(),
+                    },
+                    position: sql.position,
+                });
             }
+
+            let row = rows.remove(0);
+            self.report.add_record(name.clone(), &row);
+            self.state.refmap.insert(name.clone(), Some(RefRow { row, table: None }));
         }
 
+        log::info!("Executed SQL statement at {}", sql.position);
+
+        Ok(())
+    }
+
+    /// Evaluates the value of the variable, so that a SQL fragment only runs once
+    /// no matter how many times the variable is referenced.
+    fn declare_variable(&mut self, variable: &Variable) -> LoadResult<()> {
+        let value = self.variable_literal(&variable.value, variable.position)?;
+
+        log::debug!("Declared variable ${} = {}", variable.name, value);
+        self.state.variables.insert(variable.name.clone(), value);
+
         Ok(())
     }
 
-    fn insert(
+    /// Returns a variable's value as a SQL literal, keeping any casts.
+    fn variable_literal(&mut self, value: &Value, position: Position) -> LoadResult<String> {
+        let value = match value {
+            Value::Bool(b) => b.to_string(),
+            Value::Cast(cast) => {
+                let value = self.variable_literal(&cast.value, position)?;
+                format!("{}::{}", value, cast.data_type)
+            }
+            Value::Number(n) => n.clone(),
+            Value::SqlFragment(s) => {
+                FragmentRunner { transaction: self.transaction }.select(s, position)?
+            }
+            Value::Text(t) => t.clone(),
+            // The analyzer has already verified that the variable was declared earlier
+            Value::Variable(name) => self.state.variables[name].clone(),
+            // The parser only accepts literals, fragments and other variables
+            Value::Generator(_)
+            | Value::Interpolated(_)
+            | Value::ParameterizedSql(_)
+            | Value::Reference(_) => unreachable!(),
+        };
+
+        Ok(value)
+    }
+
+    fn truncate(&mut self, truncate: &Truncate) -> LoadResult<()> {
+        let tables: Vec<_> = truncate.tables.iter().map(quote_name).collect();
+        let mut statement = format!("TRUNCATE {}", tables.join(", "));
+
+        if truncate.restart_identity {
+            statement.push_str(" RESTART IDENTITY");
+        }
+        if truncate.cascade {
+            statement.push_str(" CASCADE");
+        }
+        log::debug!("{}", statement);
+
+        self.transaction
+            .batch_execute(&statement)
+            .map_err(|e| LoadError::database(e, truncate.position))?;
+
+        log::info!("Truncated {}", tables.join(", "));
+
+        Ok(())
+    }
+
+    fn reset_sequences(&mut self, reset: &ResetSequence) -> LoadResult<()> {
+        for sequence in &reset.sequences {
+            let sequence = quote_name(sequence);
+            let statement = format!("ALTER SEQUENCE {} RESTART", sequence);
+            log::debug!("{}", statement);
+
+            self.transaction
+                .batch_execute(&statement)
+                .map_err(|e| LoadError::database(e, reset.position))?;
+
+            log::info!("Reset sequence {}", sequence);
+        }
+
+        Ok(())
+    }
+
+    /// Starts recording the primary keys of rows inserted into the table, unless
+    /// there is no primary key or rows may be updated rather than inserted.
+    fn manifest_rows(
         &mut self,
         qualified_table_name: &str,
-        table_scope: &str,
-        attributes: &[Attribute],
+        table: &Table,
+    ) -> LoadResult<Option<TableRows>> {
+        if let Some(OnConflict {
+            action: ConflictAction::DoUpdate,
+            ..
+        }) = table.on_conflict
+        {
+            log::warn!(
+                "{}: rows can't be torn down, since `do update` may update existing rows",
+                qualified_table_name,
+            );
+            return Ok(None);
+        }
+
+        let (name, primary_key) = manifest::table_key(self.transaction, qualified_table_name)
+            .map_err(|e| LoadError::database(e, table.position))?;
+
+        if primary_key.is_empty() {
+            log::warn!("{}: rows can't be torn down without a primary key", qualified_table_name);
+            return Ok(None);
+        }
+
+        Ok(Some(TableRows {
+            table: name,
+            primary_key,
+            rows: Vec::new(),
+        }))
+    }
+
+    /// Loads the record within a savepoint, so that a failure only rolls back the
+    /// record itself. The outer error is for failing to manage the savepoint, which
+    /// can't be recovered from.
+    fn load_in_savepoint(
+        &mut self,
+        target: &InsertTarget,
+        record: &Record,
+    ) -> LoadResult<LoadResult<Loaded>> {
+        let savepoint = |transaction: &mut Transaction, command| {
+            transaction
+                .batch_execute(command)
+                .map_err(|e| LoadError::database(e, record.position))
+        };
+
+        savepoint(self.transaction, "SAVEPOINT hldr_record")?;
+
+        let result = self.load_record(target, record);
+
+        match result {
+            Ok(_) => savepoint(self.transaction, "RELEASE SAVEPOINT hldr_record")?,
+            Err(_) => savepoint(self.transaction, "ROLLBACK TO SAVEPOINT hldr_record")?,
+        }
+
+        Ok(result)
+    }
+
+    fn load_record(
+        &mut self,
+        target: &InsertTarget,
+        record: &Record,
+    ) -> Result<Loaded, LoadError> {
+        match &record.kind {
+            RecordKind::Insert => self.insert(target, record),
+            RecordKind::Lookup => {
+                let row = self.lookup(target, record)?;
+                Ok(Loaded { row: Some(row), rows: 0 })
+            }
+            RecordKind::Update { expected_rows, .. } | RecordKind::Delete { expected_rows } => {
+                self.modify(target, record, *expected_rows)
+            }
+        }
+    }
+
+    /// Updates or deletes the rows matching the record, checking how many were affected.
+    /// Named records must affect exactly one row, which is returned to be referenced.
+    fn modify(
+        &mut self,
+        target: &InsertTarget,
+        record: &Record,
+        expected_rows: Option<u64>,
+    ) -> Result<Loaded, LoadError> {
+        let builder = InsertStatement::build(self.transaction, self.rng)
+            .attributes(&record.nodes)
+            .current_scope(target.table_scope)
+            .qualified_table_name(target.qualified_table_name)
+            .refmap(&self.state.refmap)
+            .column_types(&mut self.state.column_types)
+            .variables(&self.state.variables);
+        let statement = match &record.kind {
+            RecordKind::Update { set, .. } => builder.finish_update(set)?,
+            _ => builder.finish_delete()?,
+        };
+
+        let mut rows: Vec<_> = self
+            .transaction
+            .simple_query(&statement)
+            .map_err(|e| LoadError::database(e, record.position))?
+            .into_iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => Some(row),
+                _ => None,
+            })
+            .collect();
+
+        // Named records must affect exactly one row to be referenced
+        let named = record.name.as_ref().map(|_| 1);
+
+        for expected in [expected_rows, named].into_iter().flatten() {
+            if expected != rows.len() as u64 {
+                return Err(LoadError {
+                    kind: LoadErrorKind::UnexpectedRowCount {
+                        table: target.qualified_table_name.to_owned(),
+                        expected,
+                        rows: rows.len(),
+                    },
+                    position: record.position,
+                });
+            }
+        }
+
+        log::debug!(
+            "{}: {} {} {} at {}",
+            target.qualified_table_name,
+            match record.kind {
+                RecordKind::Update { .. } => "updated",
+                _ => "deleted",
+            },
+            rows.len(),
+            if rows.len() == 1 { "row" } else { "rows" },
+            record.position,
+        );
+
+        let count = rows.len();
+        let row = record.name.as_ref().map(|_| rows.remove(0));
+
+        Ok(Loaded { row, rows: count })
+    }
+
+    /// Selects the one existing row matching every attribute of the record.
+    fn lookup(
+        &mut self,
+        target: &InsertTarget,
+        record: &Record,
     ) -> Result<SimpleQueryRow, LoadError> {
-        let statement = InsertStatement::build(self.transaction)
-            .attributes(attributes)
-            .current_scope(table_scope)
-            .qualified_table_name(qualified_table_name)
-            .refmap(&self.refmap)
-            .finish()?;
+        let query = InsertStatement::build(self.transaction, self.rng)
+            .attributes(&record.nodes)
+            .current_scope(target.table_scope)
+            .qualified_table_name(target.qualified_table_name)
+            .refmap(&self.state.refmap)
+            .column_types(&mut self.state.column_types)
+            .variables(&self.state.variables)
+            .finish_lookup()?;
 
-        let resp = self
+        let mut rows: Vec<_> = self
             .transaction
-            .simple_query(statement.as_ref())
-            .map_err(LoadError::new)?
-            .remove(0);
+            .simple_query(&query)
+            .map_err(|e| LoadError::database(e, record.position))?
+            .into_iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => Some(row),
+                _ => None,
+            })
+            .collect();
 
-        match resp {
-            SimpleQueryMessage::Row(row) => Ok(row),
-            _ => unreachable!(),
+        let error = |kind| Err(LoadError { kind, position: record.position });
+        let table = target.qualified_table_name.to_owned();
+
+        match rows.len() {
+            1 => Ok(rows.remove(0)),
+            0 => error(LoadErrorKind::LookupNoRows { table }),
+            rows => error(LoadErrorKind::LookupMultipleRows { table, rows }),
         }
     }
+
+    /// Inserts the record, returning the row unless it was an anonymous record
+    /// left as is due to a conflict.
+    fn insert(&mut self, target: &InsertTarget, record: &Record) -> Result<Loaded, LoadError> {
+        let statement = InsertStatement::build(self.transaction, self.rng)
+            .attributes(&record.nodes)
+            .current_scope(target.table_scope)
+            .on_conflict(target.on_conflict)
+            .qualified_table_name(target.qualified_table_name)
+            .refmap(&self.state.refmap)
+            .column_types(&mut self.state.column_types)
+            .variables(&self.state.variables)
+            .finish()?;
+
+        let row = first_row(
+            self.transaction
+                .simple_query(statement.as_ref())
+                .map_err(|e| LoadError::database(e, record.position))?,
+        );
+
+        match (row, &statement.lookup) {
+            (Some(row), _) => Ok(Loaded {
+                row: Some(row),
+                rows: 1,
+            }),
+            // Nothing is returned when the conflicting row is left as is, so named
+            // records have to select it to be referenced
+            (None, Some(lookup)) if record.name.is_some() => {
+                log::debug!("{}", lookup);
+
+                let row = first_row(
+                    self.transaction
+                        .simple_query(lookup)
+                        .map_err(|e| LoadError::database(e, record.position))?,
+                );
+
+                match row {
+                    Some(row) => Ok(Loaded {
+                        row: Some(row),
+                        rows: 0,
+                    }),
+                    None => Err(LoadError {
+                        kind: LoadErrorKind::ConflictingRowNotFound {
+                            table: target.qualified_table_name.to_owned(),
+                        },
+                        position: record.position,
+                    }),
+                }
+            }
+            (None, _) => Ok(Loaded { row: None, rows: 0 }),
+        }
+    }
+}
+
+/// The outcome of loading a single record.
+struct Loaded {
+    /// Returned row to store for references, if any
+    row: Option<SimpleQueryRow>,
+    /// Rows inserted, updated or deleted
+    rows: usize,
+}
+
+/// Where records are inserted, shared by every record in a table.
+struct InsertTarget<'a> {
+    qualified_table_name: &'a str,
+    table_scope: &'a str,
+    on_conflict: Option<&'a OnConflict>,
+}
+
+/// Quotes a possibly schema-qualified name the same way as table declarations,
+/// leaving names that were quoted in the data file as they are.
+fn quote_name(name: &QualifiedName) -> String {
+    let quote = |ident: &str| match ident.starts_with('"') {
+        true => ident.to_owned(),
+        false => format!(r#""{}""#, ident),
+    };
+
+    match &name.schema {
+        Some(schema) => format!("{}.{}", quote(schema), quote(&name.name)),
+        None => quote(&name.name),
+    }
+}
+
+/// Matches the column against a value written as a SQL literal, using `IS NULL` for null
+/// since `=` never matches it, but only then so that indexes can still be used.
+fn condition(column: &str, value: &str) -> String {
+    match unquote_literal(value) {
+        Some(_) => format!("{} = {}", column, value),
+        None => format!("{} IS NULL", column),
+    }
+}
+
+fn first_row(messages: Vec<SimpleQueryMessage>) -> Option<SimpleQueryRow> {
+    messages.into_iter().find_map(|message| match message {
+        SimpleQueryMessage::Row(row) => Some(row),
+        _ => None,
+    })
+}
+
+/// Converts a value written as a SQL literal back to its text, or `None` if it is null.
+/// Any casts are dropped, leaving the text of the literal being cast.
+fn unquote_literal(literal: &str) -> Option<String> {
+    // Type names can't contain quotes, so the casts of a string follow its closing quote
+    let literal = match literal.starts_with('\'') {
+        true => &literal[..=literal.rfind('\'').expect("unreachable")],
+        false => literal.split("::").next().expect("unreachable"),
+    };
+
+    match literal.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+        Some(inner) => Some(inner.replace("''", "'")),
+        None if literal == "null" => None,
+        None => Some(literal.to_owned()),
+    }
 }
 
 struct FragmentRunner<'a, 'b>
@@ -119,16 +643,28 @@ where
 }
 
 impl<'a, 'b> FragmentRunner<'a, 'b> {
-    fn select(&mut self, fragment: &str) -> Result<String, LoadError> {
+    fn select(&mut self, fragment: &str, position: Position) -> Result<String, LoadError> {
         let query = format!("SELECT {}", fragment);
 
         let mut rows = self
             .transaction
             .simple_query(&query)
-            .map_err(LoadError::new)?;
+            .map_err(|e| LoadError::database(e, position))?;
+
+        let error = |kind| Err(LoadError { kind, position });
 
-        if !matches!(rows[..], [SimpleQueryMessage::Row(_), SimpleQueryMessage::CommandComplete(1)]) {
-            panic!("expected single row from SQL fragment `{}`", fragment);
+        match rows[..] {
+            [SimpleQueryMessage::Row(_), SimpleQueryMessage::CommandComplete(1)] => {}
+            [SimpleQueryMessage::CommandComplete(0)] => {
+                return error(LoadErrorKind::FragmentNoRows {
+                    fragment: fragment.to_owned(),
+                });
+            }
+            _ => {
+                return error(LoadErrorKind::FragmentMultipleRows {
+                    fragment: fragment.to_owned(),
+                });
+            }
         }
 
         let row = match rows.remove(0) {
@@ -137,10 +673,13 @@ impl<'a, 'b> FragmentRunner<'a, 'b> {
         };
 
         if row.len() != 1 {
-            panic!("expected one column in SQL fragment result `{}`", fragment);
+            return error(LoadErrorKind::FragmentMultipleColumns {
+                fragment: fragment.to_owned(),
+                columns: row.len(),
+            });
         }
 
-        let value = row.get(0).expect("unreachable");
+        let value = row.get(0);
 
         // TODO: Using simple query protocol to select value is messy because
         // it requires stringifying selected values, which also means manually
@@ -158,13 +697,110 @@ impl<'a, 'b> FragmentRunner<'a, 'b> {
         // insert statement and the inserted values could be selected from the CTE
         // and completely avoid the round-tripping in either protocol, but this
         // would require a rewrite of the insert statement builder.
-        let value = format!("'{}'", value.replace("'", "''"));
-        println!("VALUE: {}", value);
+        let value = value.map_or_else(
+            || "null".to_owned(),
+            |v| format!("'{}'", v.replace('\'', "''")),
+        );
+        log::trace!("SQL fragment `{}` returned {}", fragment, value);
+
+        Ok(value)
+    }
+
+    /// Selects the value of a fragment with bind parameters, eg. `$1 * 2`, which requires
+    /// the extended query protocol and so casts the value to text to return it as a literal.
+    fn select_with_params(
+        &mut self,
+        fragment: &str,
+        params: &[TextParam],
+        position: Position,
+    ) -> Result<String, LoadError> {
+        let database_error = |e| LoadError::database(e, position);
+        let error = |kind| Err(LoadError { kind, position });
+
+        // The fragment's own columns follow its value, so that the one statement both
+        // returns the value and shows how many columns the fragment has
+        let query = format!(
+            r#"SELECT "value"::text, * FROM (SELECT {}) AS fragment("value")"#,
+            fragment
+        );
+        let types: Vec<_> = params.iter().map(|p| p.ty.clone()).collect();
+        let statement = self
+            .transaction
+            .prepare_typed(&query, &types)
+            .map_err(database_error)?;
+
+        if statement.columns().len() != 2 {
+            return error(LoadErrorKind::FragmentMultipleColumns {
+                fragment: fragment.to_owned(),
+                columns: statement.columns().len() - 1,
+            });
+        }
+
+        let values: Vec<_> = params.iter().map(|p| p.value.as_deref().unwrap_or("null")).collect();
+        log::debug!("{} with {:?}", query, values);
+
+        let params: Vec<_> = params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+
+        let rows = self.transaction.query(&statement, &params).map_err(database_error)?;
+
+        let value: Option<String> = match &rows[..] {
+            [row] => row.get(0),
+            [] => {
+                return error(LoadErrorKind::FragmentNoRows {
+                    fragment: fragment.to_owned(),
+                });
+            }
+            _ => {
+                return error(LoadErrorKind::FragmentMultipleRows {
+                    fragment: fragment.to_owned(),
+                });
+            }
+        };
+
+        let value = value.map_or_else(
+            || "null".to_owned(),
+            |v| format!("'{}'", v.replace('\'', "''")),
+        );
+        log::trace!("SQL fragment `{}` returned {}", fragment, value);
 
         Ok(value)
     }
 }
 
+/// Bind parameter sent as text, which the database parses as the given type, or as
+/// whichever type it infers for the parameter if `Type::UNKNOWN`.
+#[derive(Debug)]
+struct TextParam {
+    value: Option<String>,
+    ty: Type,
+}
+
+impl ToSql for TextParam {
+    fn to_sql(
+        &self,
+        _ty: &Type,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+        match &self.value {
+            Some(value) => {
+                out.extend_from_slice(value.as_bytes());
+                Ok(IsNull::No)
+            }
+            None => Ok(IsNull::Yes),
+        }
+    }
+
+    fn accepts(_ty: &Type) -> bool {
+        true
+    }
+
+    fn encode_format(&self, _ty: &Type) -> Format {
+        Format::Text
+    }
+
+    to_sql_checked!();
+}
+
 struct InsertStatementBuilder<
     'attribute,
     'current_scope,
@@ -178,16 +814,29 @@ where
 {
     attributes: &'attribute [Attribute],
     attribute_indexes: HashMap<&'attribute str, usize>,
+    column_types: Option<&'refmap mut ColumnTypes>,
     current_scope: &'current_scope str,
     fragment_runner: FragmentRunner<'fragment1, 'fragment2>,
+    on_conflict: Option<&'attribute OnConflict>,
     qualified_table_name: &'qualified_table_name str,
     refmap: Option<&'refmap RefMap>,
+    rng: &'fragment1 mut Rng,
+    variables: Option<&'refmap VarMap>,
+    /// Values written for the attributes so far, which column-level references reuse
+    /// so that fragments and generators are only evaluated once
+    values: Vec<String>,
 }
 
 impl<'a, 'c, 'f1, 'f2, 'q, 'r> InsertStatementBuilder<'a, 'c, 'f1, 'f2, 'q, 'r> {
     fn attributes(mut self, attributes: &'a [Attribute]) -> Self {
         self.attributes = attributes;
         self.attribute_indexes = HashMap::new();
+        self.values = Vec::new();
+        self
+    }
+
+    fn column_types(mut self, column_types: &'r mut ColumnTypes) -> Self {
+        self.column_types = Some(column_types);
         self
     }
 
@@ -196,6 +845,11 @@ impl<'a, 'c, 'f1, 'f2, 'q, 'r> InsertStatementBuilder<'a, 'c, 'f1, 'f2, 'q, 'r>
         self
     }
 
+    fn on_conflict(mut self, on_conflict: Option<&'a OnConflict>) -> Self {
+        self.on_conflict = on_conflict;
+        self
+    }
+
     fn qualified_table_name(mut self, qualified_table_name: &'q str) -> Self {
         self.qualified_table_name = qualified_table_name;
         self
@@ -206,128 +860,461 @@ impl<'a, 'c, 'f1, 'f2, 'q, 'r> InsertStatementBuilder<'a, 'c, 'f1, 'f2, 'q, 'r>
         self
     }
 
-    fn finish(mut self) -> Result<InsertStatement, LoadError> {
+    fn variables(mut self, variables: &'r VarMap) -> Self {
+        self.variables = Some(variables);
+        self
+    }
+
+    /// Returns the quoted column names and their values as SQL literals.
+    fn write_values(&mut self) -> Result<(Vec<String>, Vec<String>), LoadError> {
         // TODO: Use bind params and clean this up in general
-        let mut columns = String::new();
-        let mut values = String::new();
+        let mut columns = Vec::new();
 
         for (i, attribute) in self.attributes.iter().enumerate() {
-            columns.push('"');
-            columns.push_str(&attribute.name);
-            columns.push('"');
-
-            self.write_value(attribute, &mut values)?;
+            let mut value = String::new();
+            self.write_value(attribute, &attribute.value, &mut value)?;
 
             // Only add this after to prevent cyclic references
             self.attribute_indexes.insert(&attribute.name, i);
 
-            if i < self.attributes.len() - 1 {
-                columns.push_str(", ");
-                values.push_str(", ");
-            }
+            columns.push(format!(r#""{}""#, attribute.name));
+            self.values.push(value);
         }
 
+        Ok((columns, self.values.clone()))
+    }
+
+    /// Returns a `WHERE` clause matching every attribute, or nothing without attributes.
+    fn write_conditions(&mut self) -> Result<String, LoadError> {
+        let (columns, values) = self.write_values()?;
+        let conditions: Vec<_> = columns
+            .iter()
+            .zip(&values)
+            .map(|(column, value)| condition(column, value))
+            .collect();
+
+        Ok(match conditions.is_empty() {
+            true => String::new(),
+            false => format!(" WHERE {}", conditions.join(" AND ")),
+        })
+    }
+
+    fn finish_lookup(mut self) -> Result<String, LoadError> {
+        let conditions = self.write_conditions()?;
+        let query = format!("SELECT * FROM {}{}", self.qualified_table_name, conditions);
+        log::debug!("{}", query);
+
+        Ok(query)
+    }
+
+    /// Updates the rows matching the attributes with the `set` attributes, whose
+    /// column-level references can only refer to each other.
+    fn finish_update(mut self, set: &'a [Attribute]) -> Result<String, LoadError> {
+        let conditions = self.write_conditions()?;
+        let mut builder = self.attributes(set);
+        let (columns, values) = builder.write_values()?;
+        let updates: Vec<_> = columns
+            .iter()
+            .zip(&values)
+            .map(|(column, value)| format!("{} = {}", column, value))
+            .collect();
+
+        let statement = format!(
+            "UPDATE {} SET {}{} RETURNING *",
+            builder.qualified_table_name,
+            updates.join(", "),
+            conditions,
+        );
+        log::debug!("{}", statement);
+
+        Ok(statement)
+    }
+
+    fn finish_delete(mut self) -> Result<String, LoadError> {
+        let conditions = self.write_conditions()?;
         let statement = format!(
-            r#"
-            INSERT INTO {} ({}) VALUES ({})
-            RETURNING *
-        "#,
-            self.qualified_table_name, columns, values,
+            "DELETE FROM {}{} RETURNING *",
+            self.qualified_table_name, conditions
         );
-        println!("{}", statement);
+        log::debug!("{}", statement);
 
-        Ok(InsertStatement(statement))
+        Ok(statement)
     }
 
-    fn write_value(&mut self, attribute: &Attribute, out: &mut String) -> Result<(), LoadError> {
-        match &attribute.value {
+    fn finish(mut self) -> Result<InsertStatement, LoadError> {
+        let (columns, values) = self.write_values()?;
+
+        let mut statement = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            self.qualified_table_name,
+            columns.join(", "),
+            values.join(", "),
+        );
+        let mut lookup = None;
+
+        if let Some(on_conflict) = self.on_conflict {
+            let quote = |column: &String| format!(r#""{}""#, column);
+            let target: Vec<_> = on_conflict.columns.iter().map(quote).collect();
+
+            match on_conflict.action {
+                ConflictAction::DoNothing => {
+                    statement.push_str(&format!(" ON CONFLICT ({}) DO NOTHING", target.join(", ")));
+
+                    // The existing row can only be found if every conflict column was given
+                    let conditions: Option<Vec<_>> = on_conflict
+                        .columns
+                        .iter()
+                        .map(|column| {
+                            let i = self.attribute_indexes.get(column.as_str())?;
+                            Some(condition(&format!(r#""{}""#, column), &values[*i]))
+                        })
+                        .collect();
+
+                    lookup = conditions.map(|conditions| {
+                        format!(
+                            "SELECT * FROM {} WHERE {}",
+                            self.qualified_table_name,
+                            conditions.join(" AND "),
+                        )
+                    });
+                }
+                ConflictAction::DoUpdate => {
+                    let mut updates: Vec<_> = self
+                        .attributes
+                        .iter()
+                        .map(|attribute| &attribute.name)
+                        .filter(|name| !on_conflict.columns.contains(name))
+                        .collect();
+
+                    // Setting the conflict columns to the same values still updates
+                    // the row, so that it is returned
+                    if updates.is_empty() {
+                        updates = on_conflict.columns.iter().collect();
+                    }
+
+                    let updates: Vec<_> = updates
+                        .into_iter()
+                        .map(|name| format!(r#""{0}" = EXCLUDED."{0}""#, name))
+                        .collect();
+
+                    statement.push_str(&format!(
+                        " ON CONFLICT ({}) DO UPDATE SET {}",
+                        target.join(", "),
+                        updates.join(", "),
+                    ));
+                }
+            }
+        }
+
+        statement.push_str(" RETURNING *");
+        log::debug!("{}", statement);
+
+        Ok(InsertStatement { statement, lookup })
+    }
+
+    fn write_value(
+        &mut self,
+        attribute: &Attribute,
+        value: &Value,
+        out: &mut String,
+    ) -> Result<(), LoadError> {
+        match value {
             Value::Bool(b) => out.push_str(&b.to_string()),
+            Value::Cast(cast) => {
+                self.write_value(attribute, &cast.value, out)?;
+                out.push_str(&format!("::{}", cast.data_type));
+            }
+            Value::Generator(generator) => {
+                // The analyzer has already resolved every generator
+                let fake = Fake::resolve(generator).expect("invalid generator");
+                out.push_str(&fake.generate(self.rng));
+            }
+            Value::Interpolated(segments) => {
+                let text = self.interpolate(attribute, segments)?;
+                out.push_str(&format!("'{}'", text.replace('\'', "''")));
+            }
             Value::Number(n) => out.push_str(n),
+            Value::ParameterizedSql(segments) => {
+                let (fragment, params) = self.parameterize(attribute, segments)?;
+                let value = self.fragment_runner.select_with_params(
+                    &fragment,
+                    &params,
+                    attribute.position,
+                )?;
+                out.push_str(&value);
+            }
             Value::Reference(Reference::ColumnLevel(colref)) => {
-                // Column-reference could refer to a literal value, another
-                // column reference, or a reference to a different record
-                let index = self
-                    .attribute_indexes
-                    .get(&colref.column.as_ref())
-                    .expect("missing column");
-
-                let attribute = &self.attributes[*index];
-
-                // TODO: Probably best to avoid the recursion?
-                self.write_value(attribute, out)?;
+                out.push_str(self.column_value(colref));
             }
             Value::Reference(refval) => {
                 let val = self.follow_ref(attribute, refval)?;
                 out.push_str(&val);
             }
             Value::SqlFragment(s) => {
-                let value = self.fragment_runner.select(s)?;
+                let value = self.fragment_runner.select(s, attribute.position)?;
                 out.push_str(&value);
             }
             Value::Text(t) => out.push_str(t),
+            Value::Variable(name) => out.push_str(self.variable_value(name)),
         }
 
         Ok(())
     }
 
+    /// Returns the text of a string with interpolations, before it is quoted.
+    fn interpolate(&self, attribute: &Attribute, segments: &[Segment]) -> LoadResult<String> {
+        let mut text = String::new();
+
+        for segment in segments {
+            let value = match segment {
+                Segment::Text(t) => Some(t.clone()),
+                Segment::Reference(Reference::ColumnLevel(colref)) => {
+                    unquote_literal(self.column_value(colref))
+                }
+                Segment::Reference(refval) => self.ref_value(attribute, refval)?,
+                Segment::Variable(name) => unquote_literal(self.variable_value(name)),
+            };
+            let value = value.ok_or_else(|| LoadError {
+                kind: LoadErrorKind::NullInterpolated {
+                    column: attribute.name.clone(),
+                },
+                position: attribute.position,
+            })?;
+
+            text.push_str(&value);
+        }
+
+        Ok(text)
+    }
+
+    /// Returns a SQL fragment with its references replaced by bind parameters, eg. `$1`,
+    /// along with their values. References to columns are bound with the column's type,
+    /// while the types of variables and of columns returned by SQL statements are inferred.
+    fn parameterize(
+        &mut self,
+        attribute: &Attribute,
+        segments: &[Segment],
+    ) -> LoadResult<(String, Vec<TextParam>)> {
+        let mut fragment = String::new();
+        let mut params = Vec::new();
+
+        for segment in segments {
+            let (value, ty) = match segment {
+                Segment::Text(sql) => {
+                    fragment.push_str(sql);
+                    continue;
+                }
+                Segment::Reference(Reference::ColumnLevel(colref)) => {
+                    let value = unquote_literal(self.column_value(colref));
+                    let table = self.qualified_table_name;
+                    (value, self.column_type(table, &colref.column, attribute.position)?)
+                }
+                Segment::Reference(refval) => {
+                    let (key, row, column) = self.ref_row(attribute, refval)?;
+                    let value = row_value(attribute, key, row, column)?;
+                    let ty = match &row.table {
+                        Some(table) => self.column_type(table, column, attribute.position)?,
+                        None => None,
+                    };
+                    (value, ty)
+                }
+                Segment::Variable(name) => (unquote_literal(self.variable_value(name)), None),
+            };
+
+            params.push(TextParam {
+                value,
+                ty: ty.unwrap_or(Type::UNKNOWN),
+            });
+            fragment.push_str(&format!("${}", params.len()));
+        }
+
+        Ok((fragment, params))
+    }
+
+    /// Returns the type of a column of the table, describing the table the first time
+    /// one of its columns is needed.
+    fn column_type(
+        &mut self,
+        table: &str,
+        column: &str,
+        position: Position,
+    ) -> LoadResult<Option<Type>> {
+        let column_types = self.column_types.as_deref_mut().expect("no column types set");
+
+        if !column_types.contains_key(table) {
+            let statement = self
+                .fragment_runner
+                .transaction
+                .prepare(&format!("SELECT * FROM {}", table))
+                .map_err(|e| LoadError::database(e, position))?;
+            let types = statement
+                .columns()
+                .iter()
+                .map(|column| (column.name().to_owned(), column.type_().clone()))
+                .collect();
+
+            column_types.insert(table.to_owned(), types);
+        }
+
+        Ok(column_types[table].get(column).cloned())
+    }
+
+    /// Returns the value written for an earlier attribute of the record.
+    fn column_value(&self, colref: &ColumnLevelReference) -> &str {
+        // Column-reference could refer to a literal value, another
+        // column reference, or a reference to a different record,
+        // all of which have already been written
+        let index = self
+            .attribute_indexes
+            .get(&colref.column.as_ref())
+            .expect("missing column");
+
+        &self.values[*index]
+    }
+
+    fn variable_value(&self, name: &str) -> &str {
+        // The analyzer has already verified that the variable was declared
+        let value = self.variables.expect("no variables set").get(name);
+        value.expect("missing variable")
+    }
+
     fn follow_ref(&self, attribute: &Attribute, refval: &Reference) -> Result<String, LoadError> {
+        let val = self.ref_value(attribute, refval)?;
+
+        Ok(val.map_or_else(|| "null".to_owned(), |v| format!("'{}'", v)))
+    }
+
+    /// Returns the value of the referenced column as text, or `None` if it is null.
+    fn ref_value(
+        &self,
+        attribute: &Attribute,
+        refval: &Reference,
+    ) -> Result<Option<String>, LoadError> {
+        let (key, row, column) = self.ref_row(attribute, refval)?;
+
+        row_value(attribute, key, row, column)
+    }
+
+    /// Returns the key and row of the referenced record, along with the referenced column.
+    fn ref_row<'s>(
+        &self,
+        attribute: &'s Attribute,
+        refval: &'s Reference,
+    ) -> Result<(String, &'r RefRow, &'s str), LoadError> {
         use ReferencedColumn::*;
 
         let mut col = &attribute.name;
         let key = match refval {
             Reference::SchemaLevel(s) => {
                 if let Explicit(c) = &s.column {
-                    col = &c;
+                    col = c;
                 }
                 format!("{}.{}.{}", s.schema, s.table, s.record)
             }
             Reference::TableLevel(t) => {
                 if let Explicit(c) = &t.column {
-                    col = &c;
+                    col = c;
                 }
                 format!("{}.{}", t.table, t.record)
             }
             Reference::RecordLevel(r) => {
                 if let Explicit(c) = &r.column {
-                    col = &c;
+                    col = c;
                 }
-                format!("{}.{}", self.current_scope, r.record)
+                record_level_key(self.current_scope, &r.record, |key| {
+                    self.refmap.is_some_and(|refmap| refmap.contains_key(key))
+                })
             }
             // Column-references are handled differently, as there is no record in
             // the map to look up
             Reference::ColumnLevel(_) => unreachable!(),
         };
 
-        let row = self.refmap.expect("no refmap set").get(&key).unwrap();
-        let val = row.try_get(col.as_str());
+        // The analyzer has already verified that the record exists, so it can only
+        // be missing if it failed to load
+        let row = self
+            .refmap
+            .expect("no refmap set")
+            .get(&key)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| LoadError {
+                kind: LoadErrorKind::DependencyFailed {
+                    record: key.clone(),
+                },
+                position: attribute.position,
+            })?;
 
-        Ok(val
-            .unwrap_or_else(|_| panic!("no column '{}' in record {}", col, key))
-            .map_or_else(|| "null".to_owned(), |v| format!("'{}'", v)))
+        Ok((key, row, col))
     }
 }
 
-struct InsertStatement(String);
+/// Returns the value of the column of a referenced row as text, or `None` if it is null.
+/// The column can only be verified against the row returned by the database.
+fn row_value(
+    attribute: &Attribute,
+    key: String,
+    row: &RefRow,
+    column: &str,
+) -> Result<Option<String>, LoadError> {
+    let val = row.row.try_get(column).map_err(|_| LoadError {
+        kind: LoadErrorKind::ColumnNotFound {
+            record: key,
+            column: column.to_owned(),
+        },
+        position: attribute.position,
+    })?;
+
+    Ok(val.map(str::to_owned))
+}
+
+struct InsertStatement {
+    statement: String,
+    /// Selects the conflicting row when it is left as is and so not returned
+    lookup: Option<String>,
+}
 
 impl InsertStatement {
-    fn build<'f1, 'f2>(t: &'f1 mut Transaction<'f2>) -> InsertStatementBuilder<'static, 'static, 'f1, 'f2, 'static, 'static> {
+    fn build<'f1, 'f2>(
+        t: &'f1 mut Transaction<'f2>,
+        rng: &'f1 mut Rng,
+    ) -> InsertStatementBuilder<'static, 'static, 'f1, 'f2, 'static, 'static> {
         InsertStatementBuilder {
             attributes: &[],
             attribute_indexes: HashMap::new(),
+            column_types: None,
             current_scope: "",
             fragment_runner: FragmentRunner { transaction: t },
+            on_conflict: None,
             qualified_table_name: "",
             refmap: None,
+            rng,
+            values: Vec::new(),
+            variables: None,
         }
     }
 
     fn as_ref(&self) -> &str {
-        &self.0
+        &self.statement
     }
 }
 
-pub fn load(transaction: &mut Transaction, tree: ValidatedParseTree) -> LoadResult<()> {
-    let mut loader = Loader::new(transaction);
+/// Inserts every record in the tree. When `keep_going` is set, records that fail
+/// are rolled back on their own and returned rather than aborting the load.
+///
+/// Records can refer to anything in the state, which is updated with what the tree
+/// declares so that trees loaded later can refer to it in turn.
+/// The primary keys of inserted rows are added to the manifest, if given, and
+/// anything random is drawn from the run's generator so that it can be reproduced.
+pub fn load(
+    transaction: &mut Transaction,
+    tree: ValidatedParseTree,
+    state: &mut LoadState,
+    report: &mut Report,
+    manifest: Option<&mut Manifest>,
+    rng: &mut Rng,
+    keep_going: bool,
+) -> LoadResult<Vec<LoadError>> {
+    let mut loader = Loader::new(transaction, state, report, manifest, rng, keep_going);
 
     for node in tree.into_inner().nodes {
         match node {
@@ -340,8 +1327,281 @@ pub fn load(transaction: &mut Transaction, tree: ValidatedParseTree) -> LoadResu
             StructuralNode::Table(table) => {
                 loader.load_table(None, &table)?;
             }
+            StructuralNode::Truncate(truncate) => loader.truncate(&truncate)?,
+            StructuralNode::ResetSequence(reset) => loader.reset_sequences(&reset)?,
+            StructuralNode::Sql(sql) => loader.run_sql(&sql)?,
+            StructuralNode::Variable(variable) => loader.declare_variable(&variable)?,
+            StructuralNode::Param(_) => unreachable!("params are bound by the analyzer"),
         }
     }
 
-    Ok(())
+    Ok(loader.failures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{analyze, Analyzer};
+    use crate::client::{self, TlsOptions};
+    use crate::{lexer, parser};
+    use std::env;
+
+    /// Runs the test in a transaction that is always rolled back, or skips it unless
+    /// `HLDR_TEST_DATABASE_URL` is set to a database where tables can be created.
+    fn with_transaction(test: impl FnOnce(&mut Transaction)) {
+        let url = match env::var("HLDR_TEST_DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let mut client = client::new_client(&url, &TlsOptions::default()).unwrap();
+        let mut transaction = client.transaction().unwrap();
+
+        test(&mut transaction);
+    }
+
+    fn load_str(transaction: &mut Transaction, input: &str) -> LoadResult<Vec<LoadError>> {
+        let tokens = lexer::tokenize(input.chars()).unwrap();
+        let tree = analyze(parser::parse(tokens.into_iter()).unwrap()).unwrap();
+
+        let mut state = LoadState::default();
+        let mut report = Report::default();
+
+        load(transaction, tree, &mut state, &mut report, None, &mut Rng::new(0), false)
+    }
+
+    /// Returns the first column of every row returned by the query.
+    fn select(transaction: &mut Transaction, query: &str) -> Vec<Option<String>> {
+        let rows = transaction.simple_query(query).unwrap().into_iter();
+
+        rows.filter_map(|message| match message {
+            SimpleQueryMessage::Row(row) => Some(row.get(0).map(str::to_owned)),
+            _ => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_null_fragments() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute("CREATE TEMPORARY TABLE notes (id int, txt text)")
+                .unwrap();
+
+            let input = "
+                let n = `null`
+                table notes (
+                    (id 1, txt `null`)
+                    (id 2, txt `null::text`)
+                    (id 3, txt $n)
+                    (id 4, txt `'it''s'`)
+                )
+            ";
+            assert!(load_str(transaction, input).unwrap().is_empty());
+            assert_eq!(
+                select(transaction, "SELECT txt FROM notes ORDER BY id"),
+                vec![None, None, None, Some("it's".to_owned())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_fragment_reference_types() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute(
+                    "SET LOCAL timezone = 'UTC';
+                    CREATE TEMPORARY TABLE orders (
+                        id int,
+                        total numeric,
+                        placed_at timestamptz,
+                        shipped_at timestamptz
+                    )",
+                )
+                .unwrap();
+
+            let input = "
+                config = sql `SELECT 3 AS days`
+                table orders (
+                    first (id 1, total 40, placed_at '2024-01-01 10:00')
+                    (
+                        id 2
+                        total `@first.total * 2`
+                        placed_at `@first.placed_at + interval '1 day'`
+                        shipped_at `@placed_at + make_interval(days => @config.days)`
+                    )
+                )
+            ";
+            assert!(load_str(transaction, input).unwrap().is_empty());
+            let query = "SELECT concat_ws(' | ', total, placed_at, shipped_at) FROM orders WHERE id = 2";
+            assert_eq!(
+                select(transaction, query),
+                vec![Some("80 | 2024-01-02 10:00:00+00 | 2024-01-05 10:00:00+00".to_owned())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_lookup_null_values() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute(
+                    "CREATE TEMPORARY TABLE person (id int, name text, nickname text);
+                    INSERT INTO person VALUES (1, 'Kevin', null), (2, 'Kevin', 'Kev');",
+                )
+                .unwrap();
+
+            let input = "
+                table person (
+                    kevin = lookup (name 'Kevin', nickname `null`)
+                    (id 3, name @kevin.nickname, nickname @kevin.nickname)
+                    same = lookup (id 3, name @kevin.nickname)
+                )
+            ";
+            assert!(load_str(transaction, input).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_modify_null_values() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute(
+                    "CREATE TEMPORARY TABLE flag (name text, enabled bool);
+                    INSERT INTO flag VALUES ('beta', true), (null, true), (null, false);",
+                )
+                .unwrap();
+
+            let input = "
+                table flag (
+                    update (name `null`, enabled true) set (enabled false) expect 1
+                    delete (name `null`) expect 2
+                )
+            ";
+            assert!(load_str(transaction, input).unwrap().is_empty());
+            assert_eq!(select(transaction, "SELECT name FROM flag"), vec![Some("beta".to_owned())]);
+        });
+    }
+
+    #[test]
+    fn test_shared_state() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute(
+                    "CREATE TEMPORARY TABLE person (id int, name text);
+                    CREATE TEMPORARY TABLE pet (name text, person_id int);",
+                )
+                .unwrap();
+
+            let mut analyzer = Analyzer::default();
+            let mut state = LoadState::default();
+            let mut report = Report::default();
+            let mut rng = Rng::new(0);
+
+            for input in [
+                "let pet = 'Eiyuu' table person ( kevin (id 1, name 'Kevin') )",
+                "table pet ( (name $pet, person_id @person.kevin.id) )",
+            ] {
+                let tokens = lexer::tokenize(input.chars()).unwrap();
+                let parse_tree = parser::parse(tokens.into_iter()).unwrap();
+                let tree = analyzer.analyze(parse_tree, &HashMap::new()).unwrap();
+                let failures =
+                    load(transaction, tree, &mut state, &mut report, None, &mut rng, false);
+
+                assert!(failures.unwrap().is_empty());
+            }
+
+            assert_eq!(
+                select(transaction, "SELECT concat_ws(' | ', name, person_id) FROM pet"),
+                vec![Some("Eiyuu | 1".to_owned())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_cast_variables() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute("CREATE TEMPORARY TABLE notes (id int, txt text)")
+                .unwrap();
+
+            let input = "
+                let ratio = '1.50'::numeric::double precision
+                let copy = $ratio
+                table notes (
+                    (id 1, txt $copy)
+                    (id 2, txt 'ratio {$ratio}')
+                )
+            ";
+
+            assert!(load_str(transaction, input).unwrap().is_empty());
+            assert_eq!(
+                select(transaction, "SELECT txt FROM notes ORDER BY id"),
+                vec![Some("1.5".to_owned()), Some("ratio 1.50".to_owned())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_failed_record_references() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute("CREATE TEMPORARY TABLE person (name text NOT NULL)")
+                .unwrap();
+
+            let input = "
+                kevin = sql `SELECT 'Kevin' AS name`
+                table person (
+                    kevin (name `null`)
+                    (name @kevin.name)
+                )
+            ";
+            let tokens = lexer::tokenize(input.chars()).unwrap();
+            let tree = analyze(parser::parse(tokens.into_iter()).unwrap()).unwrap();
+            let mut state = LoadState::default();
+            let mut report = Report::default();
+            let failures =
+                load(transaction, tree, &mut state, &mut report, None, &mut Rng::new(0), true);
+            let failures: Vec<_> = failures.unwrap().into_iter().map(|e| e.kind).collect();
+
+            assert!(matches!(failures[0], LoadErrorKind::Database(_)));
+            assert!(matches!(
+                &failures[1],
+                LoadErrorKind::DependencyFailed { record } if record == "person.kevin"
+            ));
+            assert_eq!(select(transaction, "SELECT name FROM person"), vec![]);
+        });
+    }
+
+    #[test]
+    fn test_upsert_counts() {
+        with_transaction(|transaction| {
+            transaction
+                .batch_execute(
+                    "CREATE TEMPORARY TABLE tag (name text PRIMARY KEY, color text);
+                    INSERT INTO tag VALUES ('bug', 'red');",
+                )
+                .unwrap();
+
+            let input = "
+                table tag on conflict (name) do update (
+                    (name 'bug', color 'orange')
+                    (name 'docs', color 'blue')
+                )
+                table tag on conflict (name) do nothing (
+                    (name 'bug', color 'green')
+                    (name 'chore', color 'grey')
+                )
+            ";
+            let tokens = lexer::tokenize(input.chars()).unwrap();
+            let tree = analyze(parser::parse(tokens.into_iter()).unwrap()).unwrap();
+            let mut state = LoadState::default();
+            let mut report = Report::default();
+            let failures =
+                load(transaction, tree, &mut state, &mut report, None, &mut Rng::new(0), false);
+            assert!(failures.unwrap().is_empty());
+
+            let counts: Vec<_> = report.tables.iter().map(|t| (t.rows, t.upserted)).collect();
+            assert_eq!(counts, vec![(0, 2), (1, 0)]);
+        });
+    }
 }
@@ -1,50 +1,219 @@
-use std::path::PathBuf;
+use std::{fs, io, mem, path::PathBuf, process};
 
-use clap::{crate_version, Parser};
+use clap::{crate_version, ArgEnum, Parser, Subcommand};
+use hldr::{catalog::Catalog, error::HldrError};
+use log::{Level, LevelFilter, Log, Metadata, Record};
 
 /// Placeholder: Easy PostgreSQL data seeding
 #[derive(Parser, Debug)]
 #[clap(version = crate_version!())]
 struct Command {
+    #[clap(subcommand)]
+    command: Option<Action>,
+
     /// Commit the transaction
-    #[clap(long = "commit")]
+    #[clap(long = "commit", global = true)]
     commit: Option<bool>,
 
-    /// Path to the .hldr data file to load [default: place.hldr if not specified in options file]
-    #[clap(short = 'f', long = "data-file", name = "DATA-FILE")]
-    file: Option<PathBuf>,
+    /// Path to the .hldr data file to load, which can be given multiple times
+    /// [default: place.hldr if not specified in options file]
+    #[clap(
+        short = 'f',
+        long = "data-file",
+        name = "DATA-FILE",
+        multiple_occurrences = true,
+        global = true
+    )]
+    files: Vec<PathBuf>,
 
     /// Path to the optional .toml options file
     #[clap(
         short = 'o',
         long = "opts-file",
         name = "OPTS-FILE",
-        default_value = "hldr-opts.toml"
+        default_value = "hldr-opts.toml",
+        global = true
     )]
     opts_file: PathBuf,
 
+    /// Profile from the options file to use, eg. `dev` for `[profiles.dev]`
+    #[clap(short = 'p', long = "profile", name = "PROFILE", global = true)]
+    profile: Option<String>,
+
     /// Database connection string, either key/value pair or URI style
-    #[clap(short = 'c', long = "database-conn", name = "CONN")]
+    #[clap(short = 'c', long = "database-conn", name = "CONN", global = true)]
     database_conn: Option<String>,
+
+    /// Validate data files against the database catalog before inserting anything
+    #[clap(long = "validate")]
+    validate: bool,
+
+    /// Roll back only the records that fail to load (and records referencing them),
+    /// then report every failure at the end instead of stopping at the first
+    #[clap(long = "keep-going")]
+    keep_going: bool,
+
+    /// Directory to write a manifest of the rows inserted by each committed run to,
+    /// which `teardown` reads to delete them
+    #[clap(long = "manifest-dir", name = "DIR", global = true)]
+    manifest_dir: Option<PathBuf>,
+
+    /// Value for a param declared in the data files, which can be given multiple times
+    #[clap(
+        long = "param",
+        name = "NAME=VALUE",
+        multiple_occurrences = true,
+        global = true,
+        parse(try_from_str = parse_param)
+    )]
+    params: Vec<(String, String)>,
+
+    /// Seed for generated values, making them the same on every run with the same seed
+    #[clap(long = "seed", name = "SEED")]
+    seed: Option<u64>,
+
+    /// Only print warnings and errors
+    #[clap(short = 'q', long = "quiet", conflicts_with = "verbose", global = true)]
+    quiet: bool,
+
+    /// Print executed statements (-v) or also SQL fragment results (-vv)
+    #[clap(short = 'v', long = "verbose", parse(from_occurrences), global = true)]
+    verbose: u8,
+
+    /// Write a report of named records, tables loaded, and the outcome to stdout
+    #[clap(long = "report", name = "FORMAT", arg_enum)]
+    report: Option<ReportFormat>,
+}
+
+#[derive(ArgEnum, Clone, Debug)]
+enum ReportFormat {
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// Validate data files against the database catalog without inserting anything
+    Check {
+        /// Path to a catalog snapshot to validate against instead of connecting to the database
+        #[clap(long = "catalog", name = "CATALOG-FILE")]
+        catalog: Option<PathBuf>,
+    },
+
+    /// Delete every row inserted by an earlier run, as recorded in its manifest
+    Teardown {
+        /// Run to tear down, as printed when it was committed
+        #[clap(name = "RUN-ID")]
+        run_id: String,
+    },
+
+    /// Manage snapshots of the database catalog
+    Catalog {
+        #[clap(subcommand)]
+        command: CatalogCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CatalogCommand {
+    /// Write the tables, columns, and foreign keys in the database to a JSON snapshot
+    Snapshot {
+        /// Path to write the snapshot to [default: stdout]
+        #[clap(name = "FILE")]
+        file: Option<PathBuf>,
+    },
+}
+
+impl Command {
+    fn log_level(&self) -> LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => LevelFilter::Warn,
+            (false, 0) => LevelFilter::Info,
+            (false, 1) => LevelFilter::Debug,
+            (false, _) => LevelFilter::Trace,
+        }
+    }
+}
+
+/// Splits a `--param` value like `plan=pro` at the first `=`, so that values can contain it.
+fn parse_param(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_owned(), value.to_owned())),
+        _ => Err(format!("expected `NAME=VALUE` but got `{}`", s)),
+    }
+}
+
+/// Minimal logger that writes messages to stderr, since the library itself
+/// only emits output through `log` and leaves presentation to the binary.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies (eg. the postgres driver) log at debug & trace levels,
+        // which would drown out the statements being executed
+        metadata.target().starts_with("hldr") && metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        match record.level() {
+            Level::Error => eprintln!("Error: {}", record.args()),
+            Level::Warn => eprintln!("Warning: {}", record.args()),
+            _ => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
 }
 
+static LOGGER: Logger = Logger;
+
 fn main() {
-    let cmd = Command::parse();
+    let mut cmd = Command::parse();
+
+    log::set_logger(&LOGGER).expect("logger should only be set once");
+    log::set_max_level(cmd.log_level());
+
     let options = {
-        let mut options = hldr::Options::new(&cmd.opts_file)
-            .unwrap() // consume result
-            .unwrap_or_default();
+        let mut options = match hldr::Options::new(&cmd.opts_file, cmd.profile.as_deref()) {
+            Ok(options) => options.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("Error: {}: {}", cmd.opts_file.display(), e);
+                process::exit(1);
+            }
+        };
 
-        // The options file can specify the data file and connection string,
+        // The options file can specify the data files and connection string,
         // which should be overridden by command-line options
-        if let Some(f) = cmd.file {
-            options.data_file = f.clone();
+        if !cmd.files.is_empty() {
+            options.data_files = mem::take(&mut cmd.files);
         }
 
-        if let Some(dc) = cmd.database_conn {
+        if let Some(dc) = &cmd.database_conn {
             options.database_conn = dc.clone();
         }
 
+        if cmd.validate {
+            options.validate = true;
+        }
+
+        if cmd.keep_going {
+            options.keep_going = true;
+        }
+
+        if let Some(dir) = &cmd.manifest_dir {
+            options.manifest_dir = Some(dir.clone());
+        }
+
+        // Params are merged so that the options file can give defaults for some
+        options.params.extend(mem::take(&mut cmd.params));
+
+        if let Some(seed) = cmd.seed {
+            options.seed = Some(seed);
+        }
+
         if let Some(commit) = cmd.commit {
             options.commit = commit;
         }
@@ -52,7 +221,76 @@ fn main() {
         options
     };
 
-    if let Err(e) = hldr::place(&options) {
+    let result = match cmd.command {
+        None => place(&options, cmd.report),
+        Some(Action::Check { catalog }) => check(&options, catalog),
+        Some(Action::Teardown { run_id }) => teardown(&options, &run_id),
+        Some(Action::Catalog {
+            command: CatalogCommand::Snapshot { file },
+        }) => snapshot(&options, file),
+    };
+
+    if let Err(e) = result {
         eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn place(options: &hldr::Options, format: Option<ReportFormat>) -> Result<(), HldrError> {
+    let mut report = hldr::report::Report::default();
+    let result = hldr::place_with_report(options, &mut report);
+
+    match format {
+        Some(ReportFormat::Json) => {
+            let json = serde_json::to_string_pretty(&report).expect("report should serialize");
+            println!("{}", json);
+        }
+        None => {}
     }
+
+    // Exit with an error if any records failed, even though the rest were loaded
+    if result.is_ok() && !report.failures.is_empty() {
+        process::exit(1);
+    }
+
+    result
+}
+
+fn check(options: &hldr::Options, catalog: Option<PathBuf>) -> Result<(), HldrError> {
+    let catalog = catalog
+        .map(|path| Catalog::read_snapshot(&path))
+        .transpose()?;
+
+    hldr::check(options, catalog)?;
+    log::info!("No problems found");
+
+    Ok(())
+}
+
+fn teardown(options: &hldr::Options, run_id: &str) -> Result<(), HldrError> {
+    let manifest_dir = match &options.manifest_dir {
+        Some(dir) => dir,
+        None => {
+            eprintln!("Error: `--manifest-dir` or `manifest_dir` in the options file is required");
+            process::exit(1);
+        }
+    };
+
+    hldr::teardown(options, manifest_dir, run_id)
+}
+
+fn snapshot(options: &hldr::Options, file: Option<PathBuf>) -> Result<(), HldrError> {
+    let catalog = hldr::snapshot(options)?;
+
+    match &file {
+        Some(path) => catalog.write_snapshot(fs::File::create(path)?),
+        None => catalog.write_snapshot(io::stdout().lock()),
+    }
+    .map_err(io::Error::from)?;
+
+    if let Some(path) = file {
+        log::info!("Wrote catalog snapshot to {}", path.display());
+    }
+
+    Ok(())
 }
@@ -0,0 +1,39 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+#[derive(Debug)]
+pub enum ManifestErrorKind {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for ManifestErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ManifestErrorKind::*;
+
+        match self {
+            Io(e) => write!(f, "{}", e),
+            Json(e) => write!(f, "invalid run manifest: {}", e),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ManifestError {
+    pub kind: ManifestErrorKind,
+    pub path: PathBuf,
+}
+
+impl Error for ManifestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ManifestErrorKind::Io(e) => Some(e),
+            ManifestErrorKind::Json(e) => Some(e),
+        }
+    }
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.kind)
+    }
+}
@@ -0,0 +1,337 @@
+//! Primary keys of every row inserted by a run, written to a manifest file so that
+//! `hldr teardown` can delete them later without resetting the whole database.
+
+pub mod error;
+
+use crate::catalog::Catalog;
+use error::{ManifestError, ManifestErrorKind};
+use postgres::GenericClient;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Manifest {
+    pub run_id: String,
+    /// Rows inserted by each table declaration, in the order they were loaded
+    pub tables: Vec<TableRows>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct TableRows {
+    /// Schema-qualified table name, quoted where needed, eg. `public."Person"`
+    pub table: String,
+    pub primary_key: Vec<String>,
+    /// Values of the primary key columns of each row, in the same order
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Manifest {
+    pub fn new(run_id: String) -> Self {
+        Self {
+            run_id,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Path of the manifest for the given run within the manifest directory.
+    pub fn path(dir: &Path, run_id: &str) -> PathBuf {
+        dir.join(format!("{}.json", run_id))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, ManifestError> {
+        let error = |kind| ManifestError {
+            kind,
+            path: path.to_owned(),
+        };
+
+        let contents = fs::read_to_string(path).map_err(|e| error(ManifestErrorKind::Io(e)))?;
+
+        serde_json::from_str(&contents).map_err(|e| error(ManifestErrorKind::Json(e)))
+    }
+
+    /// Writes the manifest as pretty-printed JSON, creating the directory if needed
+    /// but never overwriting the manifest of another run.
+    pub fn write(&self, path: &Path) -> Result<(), ManifestError> {
+        let error = |kind| ManifestError {
+            kind,
+            path: path.to_owned(),
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| error(ManifestErrorKind::Io(e)))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| error(ManifestErrorKind::Io(e)))?;
+
+        serde_json::to_writer_pretty(&mut file, self)
+            .map_err(|e| error(ManifestErrorKind::Json(e)))?;
+        writeln!(file).map_err(|e| error(ManifestErrorKind::Io(e)))
+    }
+
+    /// Total number of rows recorded.
+    pub fn len(&self) -> usize {
+        self.tables.iter().map(|table| table.rows.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Orders the recorded tables for deletion, so that each table is deleted from
+    /// before the tables its foreign keys reference, whatever order they were declared in.
+    /// Otherwise tables are deleted from in the reverse order they were loaded, which
+    /// is also what happens to tables whose foreign keys reference each other.
+    pub fn deletion_order(&self, catalog: &Catalog) -> Vec<&TableRows> {
+        let names: Vec<_> = self
+            .tables
+            .iter()
+            .map(|table| split_qualified_name(&table.table))
+            .collect();
+        let references = |from: usize, to: usize| {
+            let (schema, table) = &names[from];
+
+            names[from] != names[to]
+                && catalog
+                    .schemas
+                    .get(schema)
+                    .and_then(|s| s.tables.get(table))
+                    .is_some_and(|table| {
+                        table.foreign_keys.iter().any(|foreign_key| {
+                            let target = &foreign_key.references;
+                            (&target.schema, &target.table) == (&names[to].0, &names[to].1)
+                        })
+                    })
+        };
+
+        let mut remaining: Vec<usize> = (0..self.tables.len()).rev().collect();
+        let mut order = Vec::with_capacity(remaining.len());
+
+        while !remaining.is_empty() {
+            // The first table no other remaining table references, unless they all are
+            let next = remaining
+                .iter()
+                .position(|&i| !remaining.iter().any(|&j| references(j, i)))
+                .unwrap_or(0);
+            order.push(&self.tables[remaining.remove(next)]);
+        }
+
+        order
+    }
+}
+
+impl TableRows {
+    /// Deletes every recorded row in a single statement, so that rows referencing
+    /// others in the same table don't need to be ordered.
+    pub fn delete_statement(&self) -> Option<String> {
+        if self.rows.is_empty() {
+            return None;
+        }
+
+        let quote_ident = |ident: &String| format!(r#""{}""#, ident.replace('"', "\"\""));
+        let quote_literal = |value: &String| format!("'{}'", value.replace('\'', "''"));
+
+        let columns: Vec<_> = self.primary_key.iter().map(quote_ident).collect();
+        let rows: Vec<_> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let values: Vec<_> = row.iter().map(quote_literal).collect();
+                format!("({})", values.join(", "))
+            })
+            .collect();
+
+        Some(format!(
+            "DELETE FROM {} WHERE ({}) IN ({})",
+            self.table,
+            columns.join(", "),
+            rows.join(", "),
+        ))
+    }
+}
+
+/// Splits a schema-qualified name formatted like `%I.%I` by Postgres into its schema
+/// and table names, unquoting them.
+fn split_qualified_name(name: &str) -> (String, String) {
+    let mut parts = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = name.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                parts.last_mut().expect("unreachable").push('"');
+            }
+            '"' => quoted = !quoted,
+            '.' if !quoted => parts.push(String::new()),
+            c => parts.last_mut().expect("unreachable").push(c),
+        }
+    }
+
+    let table = parts.pop().expect("unreachable");
+    (parts.join("."), table)
+}
+
+/// Resolves the table name as used in statements to its schema-qualified name,
+/// along with its primary key columns in order (if it has a primary key).
+pub(crate) fn table_key(
+    client: &mut impl GenericClient,
+    qualified_table_name: &str,
+) -> Result<(String, Vec<String>), postgres::Error> {
+    let row = client.query_one(
+        "SELECT format('%I.%I', n.nspname, c.relname),
+                array(
+                    SELECT a.attname::text
+                      FROM pg_index i
+                      JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+                     WHERE i.indrelid = c.oid AND i.indisprimary
+                     ORDER BY array_position(i.indkey::int2[], a.attnum)
+                )
+           FROM pg_class c
+           JOIN pg_namespace n ON n.oid = c.relnamespace
+          WHERE c.oid = $1::text::regclass",
+        &[&qualified_table_name],
+    )?;
+
+    Ok((row.get(0), row.get(1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delete_statement() {
+        let table = TableRows {
+            table: r#""public"."Person""#.to_owned(),
+            primary_key: vec!["org_id".to_owned(), "Na\"me".to_owned()],
+            rows: vec![
+                vec!["1".to_owned(), "Kevin".to_owned()],
+                vec!["2".to_owned(), "O'Brien".to_owned()],
+            ],
+        };
+
+        assert_eq!(
+            table.delete_statement().as_deref(),
+            Some(
+                r#"DELETE FROM "public"."Person" WHERE ("org_id", "Na""me") IN (('1', 'Kevin'), ('2', 'O''Brien'))"#
+            ),
+        );
+
+        let empty = TableRows {
+            rows: Vec::new(),
+            ..table
+        };
+        assert_eq!(empty.delete_statement(), None);
+    }
+
+    #[test]
+    fn test_split_qualified_name() {
+        assert_eq!(
+            split_qualified_name("public.person"),
+            ("public".to_owned(), "person".to_owned())
+        );
+        assert_eq!(
+            split_qualified_name(r#""My.App"."Say ""hi""""#),
+            ("My.App".to_owned(), r#"Say "hi""#.to_owned())
+        );
+    }
+
+    #[test]
+    fn test_deletion_order() {
+        use crate::catalog::{ForeignKey, ForeignKeyTarget, Table};
+
+        let references = |table: &str| ForeignKey {
+            name: format!("{}_fkey", table),
+            columns: vec![format!("{}_id", table)],
+            references: ForeignKeyTarget {
+                schema: "public".to_owned(),
+                table: table.to_owned(),
+                columns: vec!["id".to_owned()],
+            },
+        };
+        let mut catalog = Catalog::default();
+        let tables = &mut catalog
+            .schemas
+            .entry("public".to_owned())
+            .or_default()
+            .tables;
+        tables.insert(
+            "Order".to_owned(),
+            Table {
+                foreign_keys: vec![references("person"), references("Order")],
+                ..Table::default()
+            },
+        );
+        tables.insert(
+            "pet".to_owned(),
+            Table {
+                foreign_keys: vec![references("person")],
+                ..Table::default()
+            },
+        );
+        tables.insert("person".to_owned(), Table::default());
+        tables.insert("note".to_owned(), Table::default());
+
+        let rows = |table: &str| TableRows {
+            table: table.to_owned(),
+            primary_key: vec!["id".to_owned()],
+            rows: vec![vec!["1".to_owned()]],
+        };
+        // Tables referencing `person` are declared both before and after it
+        let manifest = Manifest {
+            run_id: "20240430-225727-333".to_owned(),
+            tables: vec![
+                rows(r#"public."Order""#),
+                rows("public.person"),
+                rows("public.note"),
+                rows("public.pet"),
+            ],
+        };
+        let order: Vec<_> = manifest
+            .deletion_order(&catalog)
+            .into_iter()
+            .map(|table| table.table.as_str())
+            .collect();
+
+        assert_eq!(
+            order,
+            vec![
+                "public.pet",
+                "public.note",
+                r#"public."Order""#,
+                "public.person"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let manifest = Manifest {
+            run_id: "20240430-225727-333".to_owned(),
+            tables: vec![TableRows {
+                table: r#""public"."person""#.to_owned(),
+                primary_key: vec!["id".to_owned()],
+                rows: vec![vec!["1".to_owned()], vec!["2".to_owned()]],
+            }],
+        };
+        assert_eq!(manifest.len(), 2);
+
+        let dir = std::env::temp_dir().join(format!("hldr-manifest-{}", std::process::id()));
+        let path = Manifest::path(&dir, &manifest.run_id);
+
+        manifest.write(&path).unwrap();
+        assert_eq!(Manifest::read(&path).unwrap(), manifest);
+
+        // Manifests of earlier runs are never overwritten
+        assert!(manifest.write(&path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::lexer::tokens::Token;
+use crate::lexer::tokens::{Keyword, Token};
 use crate::Position;
 use std::error::Error;
 use std::fmt;
@@ -11,10 +11,16 @@ pub enum ParseErrorKind {
     ExpectedAliasName(Token),
     ExpectedAliasOrScope(Token),
     ExpectedCloseAttribute(Token),
+    ExpectedConflictAction(Token),
     ExpectedIdentifier(Token),
+    ExpectedKeyword(Keyword, Token),
+    ExpectedRecordKind(Token),
+    ExpectedRowCount(Token),
     ExpectedScope(Token),
     ExpectedSchemaName(Token),
+    ExpectedSql(Token),
     ExpectedTableName(Token),
+    ExpectedTypeName(Token),
     ExpectedValue(Token),
     UnexpectedInSchema(Token),
     UnexpectedInTable(Token),
@@ -22,6 +28,7 @@ pub enum ParseErrorKind {
     UnexpectedToken(Token),
     // But this one breaks the Token pattern
     RecordNameQuoted(String, Position),
+    IncompleteInterpolation(Position),
 }
 
 impl fmt::Display for ParseErrorKind {
@@ -42,21 +49,54 @@ impl fmt::Display for ParseErrorKind {
                     t.kind
                 )
             }
+            ExpectedConflictAction(t) => {
+                write!(
+                    f,
+                    "expected keyword `nothing` or `update`, found {}",
+                    t.kind
+                )
+            }
             ExpectedIdentifier(t) => {
                 write!(f, "expected identifier, found {}", t.kind)
             }
+            ExpectedKeyword(k, t) => {
+                write!(f, "expected keyword `{}`, found {}", k, t.kind)
+            }
+            ExpectedRecordKind(t) => {
+                write!(
+                    f,
+                    "expected keyword `lookup`, `update`, or `delete`, found {}",
+                    t.kind
+                )
+            }
+            ExpectedRowCount(t) => {
+                write!(
+                    f,
+                    "expected non-negative integer row count, found {}",
+                    t.kind
+                )
+            }
             ExpectedSchemaName(t) => {
                 write!(f, "expected identifier for schema name, found {}", t.kind)
             }
+            ExpectedSql(t) => {
+                write!(f, "expected SQL statement in backticks, found {}", t.kind)
+            }
             ExpectedTableName(t) => {
                 write!(f, "expected identifier for table name, found {}", t.kind)
             }
             ExpectedScope(t) => {
                 write!(f, "expected opening parenthesis, found {}", t.kind)
             }
+            ExpectedTypeName(t) => {
+                write!(f, "expected identifier for type name, found {}", t.kind)
+            }
             ExpectedValue(t) => {
                 write!(f, "expected value, found {}", t.kind)
             }
+            IncompleteInterpolation(_) => {
+                write!(f, "incomplete reference in string interpolation")
+            }
             RecordNameQuoted(s, _) => {
                 write!(
                     f,
@@ -101,6 +141,33 @@ pub struct ParseError {
 }
 
 impl ParseError {
+    pub fn position(&self) -> Option<Position> {
+        use ParseErrorKind::*;
+
+        match self.kind {
+            ExpectedAliasName(ref t)
+            | ExpectedAliasOrScope(ref t)
+            | ExpectedCloseAttribute(ref t)
+            | ExpectedConflictAction(ref t)
+            | ExpectedIdentifier(ref t)
+            | ExpectedKeyword(_, ref t)
+            | ExpectedRecordKind(ref t)
+            | ExpectedRowCount(ref t)
+            | ExpectedScope(ref t)
+            | ExpectedSchemaName(ref t)
+            | ExpectedSql(ref t)
+            | ExpectedTableName(ref t)
+            | ExpectedTypeName(ref t)
+            | ExpectedValue(ref t)
+            | UnexpectedInSchema(ref t)
+            | UnexpectedInTable(ref t)
+            | UnexpectedInRecord(ref t)
+            | UnexpectedToken(ref t) => Some(t.position),
+            RecordNameQuoted(_, p) | IncompleteInterpolation(p) => Some(p),
+            UnexpectedEOF => None,
+        }
+    }
+
     pub(crate) fn alias_or_scope(t: Token) -> Self {
         Self {
             kind: ParseErrorKind::ExpectedAliasOrScope(t),
@@ -125,12 +192,42 @@ impl ParseError {
         }
     }
 
+    pub(crate) fn exp_conflict_action(t: Token) -> Self {
+        Self {
+            kind: ParseErrorKind::ExpectedConflictAction(t),
+        }
+    }
+
     pub(crate) fn exp_ident(t: Token) -> Self {
         Self {
             kind: ParseErrorKind::ExpectedIdentifier(t),
         }
     }
 
+    pub(crate) fn exp_keyword(k: Keyword, t: Token) -> Self {
+        Self {
+            kind: ParseErrorKind::ExpectedKeyword(k, t),
+        }
+    }
+
+    pub(crate) fn exp_record_kind(t: Token) -> Self {
+        Self {
+            kind: ParseErrorKind::ExpectedRecordKind(t),
+        }
+    }
+
+    pub(crate) fn exp_row_count(t: Token) -> Self {
+        Self {
+            kind: ParseErrorKind::ExpectedRowCount(t),
+        }
+    }
+
+    pub(crate) fn exp_sql(t: Token) -> Self {
+        Self {
+            kind: ParseErrorKind::ExpectedSql(t),
+        }
+    }
+
     pub(crate) fn exp_scope(t: Token) -> Self {
         Self {
             kind: ParseErrorKind::ExpectedScope(t),
@@ -149,6 +246,12 @@ impl ParseError {
         }
     }
 
+    pub(crate) fn exp_type(t: Token) -> Self {
+        Self {
+            kind: ParseErrorKind::ExpectedTypeName(t),
+        }
+    }
+
     pub(crate) fn exp_value(t: Token) -> Self {
         Self {
             kind: ParseErrorKind::ExpectedValue(t),
@@ -173,6 +276,12 @@ impl ParseError {
         }
     }
 
+    pub(crate) fn incomplete_interp(p: Position) -> Self {
+        Self {
+            kind: ParseErrorKind::IncompleteInterpolation(p),
+        }
+    }
+
     pub(crate) fn rec_quot(s: String, p: Position) -> Self {
         Self {
             kind: ParseErrorKind::RecordNameQuoted(s, p),
@@ -198,10 +307,13 @@ impl fmt::Display for ParseError {
             ExpectedAliasName(ref t)
             | ExpectedAliasOrScope(ref t)
             | ExpectedCloseAttribute(ref t)
+            | ExpectedConflictAction(ref t)
             | ExpectedIdentifier(ref t)
+            | ExpectedKeyword(_, ref t)
             | ExpectedScope(ref t)
             | ExpectedSchemaName(ref t)
             | ExpectedTableName(ref t)
+            | ExpectedTypeName(ref t)
             | ExpectedValue(ref t)
             | UnexpectedInSchema(ref t)
             | UnexpectedInTable(ref t)
@@ -212,7 +324,7 @@ impl fmt::Display for ParseError {
                 // the column is less relevant for parser errors than it is for lexer?
                 write!(f, "{} on line {}", self.kind, t.position.line)
             }
-            RecordNameQuoted(_, p) => {
+            RecordNameQuoted(_, p) | IncompleteInterpolation(p) => {
                 write!(f, "{} on line {}", self.kind, p.line)
             }
             _ => {
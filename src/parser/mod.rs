@@ -2,7 +2,7 @@ pub mod error;
 pub mod nodes;
 mod states;
 
-use super::lexer::tokens::Token;
+use super::lexer::tokens::{Token, TokenKind};
 
 use error::{ParseError, ParseErrorKind};
 
@@ -14,6 +14,11 @@ pub fn parse(input: impl Iterator<Item = Token>) -> Result<nodes::ParseTree, Par
     let mut state: Box<dyn states::State> = Box::new(states::Root);
 
     for token in input {
+        // Doc comments aren't part of the grammar, just kept for the next table or record
+        if let TokenKind::DocComment(line) = token.kind {
+            context.push_doc(line);
+            continue;
+        }
         state = state.receive(&mut context, Some(token))?;
     }
 
@@ -33,6 +38,7 @@ mod tests {
     use crate::lexer::tokenize;
     use crate::lexer::tokens::Token;
     use crate::parser::nodes::*;
+    use crate::Position;
 
     fn tokens(input: &str) -> impl Iterator<Item = Token> {
         tokenize(input.chars()).unwrap().into_iter()
@@ -67,6 +73,7 @@ mod tests {
                         name: "my_schema".to_owned(),
                     },
                     nodes: Vec::new(),
+                    position: Position { line: 1, column: 8 },
                 })),],
             }),
         );
@@ -97,6 +104,7 @@ mod tests {
                         name: "my_other_schema".to_owned(),
                     },
                     nodes: Vec::new(),
+                    position: Position { line: 1, column: 8 },
                 })),],
             }),
         );
@@ -132,11 +140,14 @@ mod tests {
             parse(input),
             Ok(ParseTree {
                 nodes: vec![StructuralNode::Table(Box::new(Table {
+                    doc: None,
                     identity: StructuralIdentity {
                         alias: None,
                         name: "my_table".to_owned(),
                     },
                     nodes: Vec::new(),
+                    on_conflict: None,
+                    position: Position { line: 1, column: 7 },
                 })),],
             }),
         );
@@ -180,11 +191,14 @@ mod tests {
             parse(input.into_iter()),
             Ok(ParseTree {
                 nodes: vec![StructuralNode::Table(Box::new(Table {
+                    doc: None,
                     identity: StructuralIdentity {
                         alias: Some("another_alias".to_owned()),
                         name: "my_other_table".to_owned(),
                     },
                     nodes: Vec::new(),
+                    on_conflict: None,
+                    position: Position { line: 1, column: 7 },
                 })),],
             }),
         );
@@ -210,12 +224,16 @@ mod tests {
                         name: "myschema".to_owned(),
                     },
                     nodes: vec![Table {
+                        doc: None,
                         identity: StructuralIdentity {
                             alias: None,
                             name: "mytable".to_owned(),
                         },
                         nodes: Vec::new(),
+                        on_conflict: None,
+                        position: Position { line: 3, column: 23 },
                     },],
+                    position: Position { line: 2, column: 20 },
                 })),],
             }),
         );
@@ -241,12 +259,16 @@ mod tests {
                         name: "myschema".to_owned(),
                     },
                     nodes: vec![Table {
+                        doc: None,
                         identity: StructuralIdentity {
                             alias: Some("t1".to_owned()),
                             name: "mytable".to_owned(),
                         },
                         nodes: Vec::new(),
+                        on_conflict: None,
+                        position: Position { line: 3, column: 23 },
                     },],
+                    position: Position { line: 2, column: 20 },
                 })),],
             }),
         );
@@ -281,33 +303,70 @@ mod tests {
                             name: "s1".to_owned(),
                         },
                         nodes: vec![Table {
+                            doc: None,
                             identity: StructuralIdentity {
                                 alias: None,
                                 name: "t1".to_owned(),
                             },
                             nodes: vec![
                                 Record {
+                                    doc: None,
+                                    kind: RecordKind::Insert,
                                     name: Some("record1".to_owned()),
                                     nodes: Vec::new(),
+                                    position: Position { line: 4, column: 21 },
+                                },
+                                Record {
+                                    doc: None,
+                                    kind: RecordKind::Insert,
+                                    name: None,
+                                    nodes: Vec::new(),
+                                    position: Position { line: 5, column: 21 },
+                                },
+                                Record {
+                                    doc: None,
+                                    kind: RecordKind::Insert,
+                                    name: None,
+                                    nodes: Vec::new(),
+                                    position: Position { line: 6, column: 21 },
                                 },
-                                Record::default(),
-                                Record::default(),
                             ],
+                            on_conflict: None,
+                            position: Position { line: 3, column: 23 },
                         },],
+                        position: Position { line: 2, column: 20 },
                     })),
                     StructuralNode::Table(Box::new(Table {
+                        doc: None,
                         identity: StructuralIdentity {
                             alias: None,
                             name: "t2".to_owned(),
                         },
                         nodes: vec![
-                            Record::default(),
-                            Record::default(),
                             Record {
+                                doc: None,
+                                kind: RecordKind::Insert,
+                                name: None,
+                                nodes: Vec::new(),
+                                position: Position { line: 10, column: 17 },
+                            },
+                            Record {
+                                doc: None,
+                                kind: RecordKind::Insert,
+                                name: None,
+                                nodes: Vec::new(),
+                                position: Position { line: 11, column: 17 },
+                            },
+                            Record {
+                                doc: None,
+                                kind: RecordKind::Insert,
                                 name: Some("record2".to_owned()),
                                 nodes: Vec::new(),
+                                position: Position { line: 12, column: 17 },
                             },
                         ],
+                        on_conflict: None,
+                        position: Position { line: 9, column: 19 },
                     })),
                 ],
             })
@@ -355,109 +414,145 @@ mod tests {
         );
 
         let t1 = Table {
+            doc: None,
             identity: StructuralIdentity {
                 alias: None,
                 name: "t1".to_owned(),
             },
             nodes: vec![
                 Record {
+                    doc: None,
+                    kind: RecordKind::Insert,
                     name: Some("record1".to_owned()),
                     nodes: vec![
                         Attribute {
                             name: "col1".to_owned(),
-                            value: Value::Number(Box::new("123".to_owned())),
+                            value: Value::Number("123".to_owned()),
+                            position: Position { line: 6, column: 25 },
                         },
                         Attribute {
                             name: "col2".to_owned(),
                             value: Value::Bool(true),
+                            position: Position { line: 7, column: 25 },
                         },
                         Attribute {
                             name: "col3".to_owned(),
-                            value: Value::Text(Box::new("'hello!'".to_owned())),
+                            value: Value::Text("'hello!'".to_owned()),
+                            position: Position { line: 8, column: 25 },
                         },
                         Attribute {
                             name: "col4".to_owned(),
-                            value: Value::Reference(Box::new(Reference {
-                                schema: None,
-                                table: None,
-                                record: None,
+                            value: Value::Reference(Reference::ColumnLevel(ColumnLevelReference {
                                 column: "col3".to_owned(),
                             })),
+                            position: Position { line: 11, column: 25 },
                         },
                     ],
+                    position: Position { line: 4, column: 21 },
                 },
                 Record {
+                    doc: None,
+                    kind: RecordKind::Insert,
                     name: None,
                     nodes: vec![Attribute {
                         name: "col".to_owned(),
-                        value: Value::Reference(Box::new(Reference {
-                            schema: None,
-                            table: None,
-                            record: Some("record1".to_owned()),
-                            column: "col1".to_owned(),
+                        value: Value::Reference(Reference::RecordLevel(RecordLevelReference {
+                            record: "record1".to_owned(),
+                            column: ReferencedColumn::Explicit("col1".to_owned()),
                         })),
+                        position: Position { line: 15, column: 25 },
                     }],
+                    position: Position { line: 13, column: 21 },
                 },
             ],
+            on_conflict: None,
+            position: Position { line: 3, column: 23 },
         };
         let t2 = Table {
+            doc: None,
             identity: StructuralIdentity {
                 alias: None,
                 name: "t2".to_owned(),
             },
             nodes: vec![
                 Record {
+                    doc: None,
+                    kind: RecordKind::Insert,
                     name: None,
                     nodes: vec![Attribute {
                         name: "colx".to_owned(),
-                        value: Value::Reference(Box::new(Reference {
-                            schema: Some("s1".to_owned()),
-                            table: Some("t1".to_owned()),
-                            record: Some("record1".to_owned()),
-                            column: "col2".to_owned(),
+                        value: Value::Reference(Reference::SchemaLevel(SchemaLevelReference {
+                            schema: "s1".to_owned(),
+                            table: "t1".to_owned(),
+                            record: "record1".to_owned(),
+                            column: ReferencedColumn::Explicit("col2".to_owned()),
                         })),
+                        position: Position { line: 22, column: 21 },
                     }],
+                    position: Position { line: 20, column: 17 },
                 },
                 Record {
+                    doc: None,
+                    kind: RecordKind::Insert,
                     name: None,
                     nodes: vec![Attribute {
                         name: "coly".to_owned(),
-                        value: Value::Reference(Box::new(Reference {
+                        value: Value::Reference(Reference::SchemaLevel(SchemaLevelReference {
                             // TODO: Should these actually be explicitly quoted?
-                            schema: Some("\"s1\"".to_owned()),
-                            table: Some("\"t1\"".to_owned()),
-                            record: Some("record1".to_owned()),
-                            column: "\"col2\"".to_owned(),
+                            schema: "\"s1\"".to_owned(),
+                            table: "\"t1\"".to_owned(),
+                            record: "record1".to_owned(),
+                            column: ReferencedColumn::Explicit("\"col2\"".to_owned()),
                         })),
+                        position: Position { line: 26, column: 21 },
                     }],
+                    position: Position { line: 24, column: 17 },
                 },
                 Record {
+                    doc: None,
+                    kind: RecordKind::Insert,
                     name: Some("record2".to_owned()),
                     nodes: vec![Attribute {
                         name: "col".to_owned(),
-                        value: Value::Number(Box::new("1234".to_owned())),
+                        value: Value::Number("1234".to_owned()),
+                        position: Position { line: 28, column: 26 },
                     }],
+                    position: Position { line: 28, column: 17 },
+                },
+                Record {
+                    doc: None,
+                    kind: RecordKind::Insert,
+                    name: None,
+                    nodes: Vec::new(),
+                    position: Position { line: 29, column: 17 },
                 },
-                Record::default(),
             ],
+            on_conflict: None,
+            position: Position { line: 19, column: 19 },
         };
         let t3 = Table {
+            doc: None,
             identity: StructuralIdentity {
                 alias: None,
                 name: "t3".to_owned(),
             },
             nodes: vec![Record {
+                doc: None,
+                kind: RecordKind::Insert,
                 name: None,
                 nodes: vec![Attribute {
                     name: "col".to_owned(),
-                    value: Value::Reference(Box::new(Reference {
-                        schema: None,
-                        table: Some("t2".to_owned()),
-                        record: Some("record2".to_owned()),
-                        column: "col".to_owned(),
+                    value: Value::Reference(Reference::TableLevel(TableLevelReference {
+                        table: "t2".to_owned(),
+                        record: "record2".to_owned(),
+                        column: ReferencedColumn::Explicit("col".to_owned()),
                     })),
+                    position: Position { line: 33, column: 18 },
                 }],
+                position: Position { line: 33, column: 17 },
             }],
+            on_conflict: None,
+            position: Position { line: 31, column: 19 },
         };
 
         let expected = Ok(ParseTree {
@@ -468,6 +563,7 @@ mod tests {
                         name: "s1".to_owned(),
                     },
                     nodes: vec![t1],
+                    position: Position { line: 2, column: 20 },
                 })),
                 StructuralNode::Table(Box::new(t2)),
                 StructuralNode::Table(Box::new(t3)),
@@ -477,4 +573,906 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_generators() {
+        let input = tokens(
+            "
+            table users (
+                (email fake.email(), born fake.date(1950, -5), bio fake.words(3))
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let records = match &result.nodes[0] {
+            StructuralNode::Table(table) => &table.nodes,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            records[0].nodes,
+            vec![
+                Attribute {
+                    name: "email".to_owned(),
+                    value: Value::Generator(Generator {
+                        name: "email".to_owned(),
+                        arguments: vec![],
+                    }),
+                    position: Position { line: 3, column: 18 },
+                },
+                Attribute {
+                    name: "born".to_owned(),
+                    value: Value::Generator(Generator {
+                        name: "date".to_owned(),
+                        arguments: vec![
+                            Value::Number("1950".to_owned()),
+                            Value::Number("-5".to_owned()),
+                        ],
+                    }),
+                    position: Position { line: 3, column: 38 },
+                },
+                Attribute {
+                    name: "bio".to_owned(),
+                    value: Value::Generator(Generator {
+                        name: "words".to_owned(),
+                        arguments: vec![Value::Number("3".to_owned())],
+                    }),
+                    position: Position { line: 3, column: 64 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_generators() {
+        for input in [
+            "table users ( (email fake) )",
+            "table users ( (email fake.) )",
+            "table users ( (email fake.email) )",
+            "table users ( (email fake.email( ) )",
+            "table users ( (email fake.email(,) )",
+            "table users ( (email fake.date(1950,) )",
+            "table users ( (email fake.date(1950 2005) )",
+            "table users ( (email fake.date(@born) )",
+            "table users ( (email fake.\"email\"() )",
+            "table users ( (email faker.email() )",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_interpolated_text() {
+        let input = tokens(
+            "
+            table users (
+                (email '{@alice.username}@{@\"Site\".main.}', label 'user-{$i}', bio '{@name}')
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let records = match &result.nodes[0] {
+            StructuralNode::Table(table) => &table.nodes,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            records[0].nodes,
+            vec![
+                Attribute {
+                    name: "email".to_owned(),
+                    value: Value::Interpolated(vec![
+                        Segment::Reference(Reference::RecordLevel(RecordLevelReference {
+                            record: "alice".to_owned(),
+                            column: ReferencedColumn::Explicit("username".to_owned()),
+                        })),
+                        Segment::Text("@".to_owned()),
+                        Segment::Reference(Reference::TableLevel(TableLevelReference {
+                            table: "\"Site\"".to_owned(),
+                            record: "main".to_owned(),
+                            column: ReferencedColumn::Implicit,
+                        })),
+                    ]),
+                    position: Position { line: 3, column: 18 },
+                },
+                Attribute {
+                    name: "label".to_owned(),
+                    value: Value::Interpolated(vec![
+                        Segment::Text("user-".to_owned()),
+                        Segment::Variable("i".to_owned()),
+                    ]),
+                    position: Position { line: 3, column: 61 },
+                },
+                Attribute {
+                    name: "bio".to_owned(),
+                    value: Value::Interpolated(vec![Segment::Reference(Reference::ColumnLevel(
+                        ColumnLevelReference {
+                            column: "name".to_owned(),
+                        },
+                    ))]),
+                    position: Position { line: 3, column: 80 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_interpolated_text() {
+        for input in [
+            "table users ( (email '{@}') )",
+            "table users ( (email '{@.}') )",
+            "table users ( (email '{@alice..email}') )",
+            "table users ( (email '{@a.b.c.d.e}') )",
+            "table users ( (email '{@a.b.c.d.}') )",
+            "table users ( (email '{$a $b}') )",
+            "table users ( (email '{$a.b}') )",
+            "table users ( (email '{@alice.email, 1}') )",
+            "table users ( (email '{@alice email}') )",
+            "let email = '{$a}'",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let input = tokens(
+            "
+            --- Users seeded
+            --- for demos
+            table users (
+                --- The admin
+                alice (name 'Alice')
+                bob (name 'Bob')
+            )
+            --- Not for the table after the variable
+            let n = 1
+            table posts (
+                (title 'a' --- Not for the record after the attribute
+                )
+                --- The last post
+                (title 'b')
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let docs: Vec<_> = result
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                StructuralNode::Table(table) => Some(table),
+                _ => None,
+            })
+            .map(|table| {
+                let records = table.nodes.iter().map(|r| r.doc.as_deref()).collect::<Vec<_>>();
+                (table.doc.as_deref(), records)
+            })
+            .collect();
+
+        assert_eq!(
+            docs,
+            vec![
+                (Some("Users seeded\nfor demos"), vec![Some("The admin"), None]),
+                (None, vec![None, Some("The last post")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_multiline_block_comments() {
+        let input = tokens(
+            "
+            table users (
+                (name 'x' /* a
+                b */, id 1)
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let attributes = match &result.nodes[0] {
+            StructuralNode::Table(table) => &table.nodes[0].nodes,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            attributes.iter().map(|a| a.name.as_str()).collect::<Vec<_>>(),
+            vec!["name", "id"]
+        );
+    }
+
+    #[test]
+    fn test_casts() {
+        let input = tokens(
+            "
+            table events (
+                (day '2024-01-01'::date, tags '{}'::text[], ok 1::int::bool, total 9::numeric(5, 2))
+                (owner @alice.::public.\"Id\", price @price::varchar(10)[])
+                (at 'now'::timestamp(3) with time zone, ratio 0.5::Double Precision)
+            )
+            let since = '2024-01-01'::date
+            let ratio = $since::text::character varying
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let records = match &result.nodes[0] {
+            StructuralNode::Table(table) => &table.nodes,
+            _ => unreachable!(),
+        };
+        let cast = |value: Value, data_type: &str| {
+            Value::Cast(Cast {
+                value: Box::new(value),
+                data_type: data_type.to_owned(),
+            })
+        };
+        let values: Vec<_> = records.iter().flat_map(|r| &r.nodes).map(|a| &a.value).collect();
+
+        assert_eq!(
+            values,
+            vec![
+                &cast(Value::Text("'2024-01-01'".to_owned()), "date"),
+                &cast(Value::Text("'{}'".to_owned()), "text[]"),
+                &cast(cast(Value::Number("1".to_owned()), "int"), "bool"),
+                &cast(Value::Number("9".to_owned()), "numeric(5, 2)"),
+                &cast(
+                    Value::Reference(Reference::RecordLevel(RecordLevelReference {
+                        record: "alice".to_owned(),
+                        column: ReferencedColumn::Implicit,
+                    })),
+                    "public.\"Id\"",
+                ),
+                &cast(
+                    Value::Reference(Reference::ColumnLevel(ColumnLevelReference {
+                        column: "price".to_owned(),
+                    })),
+                    "varchar(10)[]",
+                ),
+                &cast(Value::Text("'now'".to_owned()), "timestamp(3) with time zone"),
+                &cast(Value::Number("0.5".to_owned()), "Double Precision"),
+            ]
+        );
+
+        let variables: Vec<_> = result.nodes[1..]
+            .iter()
+            .map(|node| match node {
+                StructuralNode::Variable(variable) => &variable.value,
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            variables,
+            vec![
+                &cast(Value::Text("'2024-01-01'".to_owned()), "date"),
+                &cast(
+                    cast(Value::Variable("since".to_owned()), "text"),
+                    "character varying"
+                ),
+            ]
+        );
+
+        for input in [
+            "table t ( (day '2024-01-01'::) )",
+            "table t ( (day '2024-01-01'::'date') )",
+            "table t ( (day '2024-01-01'::public.) )",
+            "table t ( (day 'a'::varchar() )",
+            "table t ( (day 'a'::varchar(1.5)) )",
+            "table t ( (day 'a'::varchar(1,)) )",
+            "table t ( (day 'a'::int[) )",
+            "table t ( (day 'a'::int date) )",
+            "table t ( (day 'a'::double table) )",
+            "let day = '2024-01-01'::",
+            "let day = '2024-01-01'::date(",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parameterized_sql() {
+        let input = tokens(
+            "
+            table posts (
+                (views `@alice.views * 2`, published_at `@created_at + interval '1 day'`)
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let records = match &result.nodes[0] {
+            StructuralNode::Table(table) => &table.nodes,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            records[0].nodes,
+            vec![
+                Attribute {
+                    name: "views".to_owned(),
+                    value: Value::ParameterizedSql(vec![
+                        Segment::Reference(Reference::RecordLevel(RecordLevelReference {
+                            record: "alice".to_owned(),
+                            column: ReferencedColumn::Explicit("views".to_owned()),
+                        })),
+                        Segment::Text(" * 2".to_owned()),
+                    ]),
+                    position: Position { line: 3, column: 18 },
+                },
+                Attribute {
+                    name: "published_at".to_owned(),
+                    value: Value::ParameterizedSql(vec![
+                        Segment::Reference(Reference::ColumnLevel(ColumnLevelReference {
+                            column: "created_at".to_owned(),
+                        })),
+                        Segment::Text(" + interval '1 day'".to_owned()),
+                    ]),
+                    position: Position { line: 3, column: 44 },
+                },
+            ]
+        );
+
+        for input in [
+            "table posts ( (views `@alice..views * 2`) )",
+            "sql `delete from posts where id = @alice.id`",
+            "let views = `@alice.views * 2`",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_tables_on_conflict() {
+        let input = tokens(
+            "
+            table users on conflict (email) do nothing ()
+            table users as u on conflict (org_id, \"Name\") do update (
+                kevin (email 'kevin@example.com')
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let conflicts: Vec<_> = result
+            .nodes
+            .iter()
+            .map(|node| match node {
+                StructuralNode::Table(table) => table.on_conflict.as_ref().unwrap(),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(
+            conflicts,
+            vec![
+                &OnConflict {
+                    columns: vec!["email".to_owned()],
+                    action: ConflictAction::DoNothing,
+                    position: Position { line: 2, column: 25 },
+                },
+                &OnConflict {
+                    columns: vec!["org_id".to_owned(), "\"Name\"".to_owned()],
+                    action: ConflictAction::DoUpdate,
+                    position: Position { line: 3, column: 30 },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_on_conflict() {
+        for input in [
+            "table users on (email) do nothing ()",
+            "table users on conflict email do nothing ()",
+            "table users on conflict () do nothing ()",
+            "table users on conflict (email) nothing ()",
+            "table users on conflict (email) do delete ()",
+            "table users on conflict (email) do nothing",
+            "table users as u on conflict (email) do update on ()",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_conflict_keywords_as_names() {
+        let input = tokens(
+            "
+            table on on conflict (do) do update (
+                nothing (on 1, conflict 2, do 3, nothing 4, update 5)
+                update = lookup (update 6)
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let table = match &result.nodes[0] {
+            StructuralNode::Table(table) => table,
+            _ => unreachable!(),
+        };
+        let columns: Vec<_> = table.nodes[0]
+            .nodes
+            .iter()
+            .map(|attribute| attribute.name.as_str())
+            .collect();
+
+        assert_eq!(table.identity.name, "on");
+        assert_eq!(table.on_conflict.as_ref().unwrap().columns, vec!["do".to_owned()]);
+        assert_eq!(table.nodes[0].name.as_deref(), Some("nothing"));
+        assert_eq!(columns, vec!["on", "conflict", "do", "nothing", "update"]);
+        assert_eq!(table.nodes[1].name.as_deref(), Some("update"));
+        assert_eq!(table.nodes[1].kind, RecordKind::Lookup);
+        assert_eq!(table.nodes[1].nodes[0].name, "update");
+    }
+
+    #[test]
+    fn test_lookup_records() {
+        let input = tokens(
+            "
+            table roles (
+                admin = lookup (name 'admin')
+                (name 'guest')
+                lookup = lookup (lookup 'owner')
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let records = match &result.nodes[0] {
+            StructuralNode::Table(table) => &table.nodes,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(records[0].kind, RecordKind::Lookup);
+        assert_eq!(records[0].name.as_deref(), Some("admin"));
+        assert_eq!(records[0].position, Position { line: 3, column: 17 });
+        assert_eq!(records[1].kind, RecordKind::Insert);
+        assert_eq!(records[2].kind, RecordKind::Lookup);
+        assert_eq!(records[2].name.as_deref(), Some("lookup"));
+        assert_eq!(records[2].nodes[0].name, "lookup");
+    }
+
+    #[test]
+    fn test_invalid_lookup_records() {
+        for input in [
+            "table roles ( admin = (name 'admin') )",
+            "table roles ( admin = lookup name )",
+            "table roles ( _ = lookup (name 'admin') )",
+            "table roles ( = lookup (name 'admin') )",
+            "table roles ( admin lookup (name 'admin') )",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+    #[test]
+    fn test_update_and_delete_records() {
+        let input = tokens(
+            "
+            table flags (
+                update (name 'beta') set (enabled true)
+                beta = update (name 'beta')
+                    set (enabled false, note @enabled) expect 1
+                delete () expect 0
+                old = delete (name 'old'))
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let records = match &result.nodes[0] {
+            StructuralNode::Table(table) => &table.nodes,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].name, None);
+        assert_eq!(records[0].position, Position { line: 3, column: 17 });
+        assert_eq!(
+            records[0].nodes,
+            vec![Attribute {
+                name: "name".to_owned(),
+                value: Value::Text("'beta'".to_owned()),
+                position: Position { line: 3, column: 25 },
+            }]
+        );
+        assert_eq!(
+            records[0].kind,
+            RecordKind::Update {
+                set: vec![Attribute {
+                    name: "enabled".to_owned(),
+                    value: Value::Bool(true),
+                    position: Position { line: 3, column: 43 },
+                }],
+                expected_rows: None,
+            }
+        );
+
+        assert_eq!(records[1].name.as_deref(), Some("beta"));
+        match &records[1].kind {
+            RecordKind::Update { set, expected_rows } => {
+                assert_eq!(set.len(), 2);
+                assert_eq!(*expected_rows, Some(1));
+            }
+            kind => panic!("expected update, found {:?}", kind),
+        }
+
+        assert!(records[2].nodes.is_empty());
+        assert_eq!(records[2].kind, RecordKind::Delete { expected_rows: Some(0) });
+        assert_eq!(records[3].name.as_deref(), Some("old"));
+        assert_eq!(records[3].kind, RecordKind::Delete { expected_rows: None });
+    }
+
+    #[test]
+    fn test_modification_keywords_as_names() {
+        let input = tokens(
+            "
+            table set (
+                expect (set 'a', delete 1, expect 2)
+                delete = lookup (set 'b')
+                update (set 'c') set (expect 3) expect 1
+            )
+        ",
+        );
+
+        let result = parse(input).unwrap();
+        let table = match &result.nodes[0] {
+            StructuralNode::Table(table) => table,
+            _ => unreachable!(),
+        };
+        let columns: Vec<_> = table.nodes[0]
+            .nodes
+            .iter()
+            .map(|attribute| attribute.name.as_str())
+            .collect();
+
+        assert_eq!(table.identity.name, "set");
+        assert_eq!(table.nodes[0].name.as_deref(), Some("expect"));
+        assert_eq!(columns, vec!["set", "delete", "expect"]);
+        assert_eq!(table.nodes[1].name.as_deref(), Some("delete"));
+        assert_eq!(table.nodes[1].kind, RecordKind::Lookup);
+        assert_eq!(
+            table.nodes[2].kind,
+            RecordKind::Update {
+                set: vec![Attribute {
+                    name: "expect".to_owned(),
+                    value: Value::Number("3".to_owned()),
+                    position: Position { line: 5, column: 39 },
+                }],
+                expected_rows: Some(1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_update_and_delete_records() {
+        for input in [
+            "table flags ( update (name 'beta') )",
+            "table flags ( update (name 'beta') (enabled true) )",
+            "table flags ( update (name 'beta') set () )",
+            "table flags ( update (name 'beta') set set (enabled true) )",
+            "table flags ( update set (enabled true) )",
+            "table flags ( delete name )",
+            "table flags ( delete (name 'old') expect )",
+            "table flags ( delete (name 'old') expect -1 )",
+            "table flags ( delete (name 'old') expect 1.5 )",
+            "table flags ( beta = insert (name 'beta') )",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+    #[test]
+    fn test_directives() {
+        let input = tokens(
+            "
+            truncate users, app.\"Orders\" restart identity cascade
+            table users ()
+            reset sequence users_id_seq
+            truncate users",
+        );
+
+        assert_eq!(
+            parse(input).unwrap().nodes,
+            vec![
+                StructuralNode::Truncate(Box::new(Truncate {
+                    tables: vec![
+                        QualifiedName {
+                            schema: None,
+                            name: "users".to_owned(),
+                            position: Position { line: 2, column: 22 },
+                        },
+                        QualifiedName {
+                            schema: Some("app".to_owned()),
+                            name: "\"Orders\"".to_owned(),
+                            position: Position { line: 2, column: 29 },
+                        },
+                    ],
+                    restart_identity: true,
+                    cascade: true,
+                    position: Position { line: 2, column: 13 },
+                })),
+                StructuralNode::Table(Box::new(Table {
+                    doc: None,
+                    identity: StructuralIdentity {
+                        alias: None,
+                        name: "users".to_owned(),
+                    },
+                    nodes: vec![],
+                    on_conflict: None,
+                    position: Position { line: 3, column: 19 },
+                })),
+                StructuralNode::ResetSequence(Box::new(ResetSequence {
+                    sequences: vec![QualifiedName {
+                        schema: None,
+                        name: "users_id_seq".to_owned(),
+                        position: Position { line: 4, column: 28 },
+                    }],
+                    position: Position { line: 4, column: 13 },
+                })),
+                StructuralNode::Truncate(Box::new(Truncate {
+                    tables: vec![QualifiedName {
+                        schema: None,
+                        name: "users".to_owned(),
+                        position: Position { line: 5, column: 22 },
+                    }],
+                    restart_identity: false,
+                    cascade: false,
+                    position: Position { line: 5, column: 13 },
+                })),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_directive_keywords_as_names() {
+        let input = tokens(
+            "
+            truncate identity, restart restart identity
+            table sequence ( (identity 1, sequence 2, reset 3, restart 4, cascade 5, truncate 6) )
+            reset sequence sequence",
+        );
+
+        let result = parse(input).unwrap();
+        let (truncate, table, reset) = match &result.nodes[..] {
+            [
+                StructuralNode::Truncate(truncate),
+                StructuralNode::Table(table),
+                StructuralNode::ResetSequence(reset),
+            ] => (truncate, table, reset),
+            nodes => panic!("unexpected nodes {:?}", nodes),
+        };
+        let tables: Vec<_> = truncate.tables.iter().map(|name| name.name.as_str()).collect();
+        let columns: Vec<_> = table.nodes[0]
+            .nodes
+            .iter()
+            .map(|attribute| attribute.name.as_str())
+            .collect();
+
+        assert_eq!(tables, vec!["identity", "restart"]);
+        assert!(truncate.restart_identity);
+        assert_eq!(table.identity.name, "sequence");
+        assert_eq!(
+            columns,
+            vec!["identity", "sequence", "reset", "restart", "cascade", "truncate"]
+        );
+        assert_eq!(reset.sequences[0].name, "sequence");
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        for input in [
+            "truncate",
+            "truncate users,",
+            "truncate users orders",
+            "truncate users restart",
+            "truncate users restart cascade",
+            "truncate users cascade restart identity",
+            "truncate users cascade cascade",
+            "truncate app.users.orders",
+            "truncate users table users ()",
+            "reset users_id_seq",
+            "reset sequence",
+            "reset sequence users_id_seq cascade",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_sql() {
+        let input = tokens(
+            "
+            sql `set local app.user_id = 1`
+            admin = sql `select id from users where admin limit 1`
+            table sql ( sql (sql 'x') )",
+        );
+
+        assert_eq!(
+            parse(input).unwrap().nodes,
+            vec![
+                StructuralNode::Sql(Box::new(Sql {
+                    name: None,
+                    statement: "set local app.user_id = 1".to_owned(),
+                    position: Position { line: 2, column: 13 },
+                })),
+                StructuralNode::Sql(Box::new(Sql {
+                    name: Some("admin".to_owned()),
+                    statement: "select id from users where admin limit 1".to_owned(),
+                    position: Position { line: 3, column: 13 },
+                })),
+                StructuralNode::Table(Box::new(Table {
+                    doc: None,
+                    identity: StructuralIdentity {
+                        alias: None,
+                        name: "sql".to_owned(),
+                    },
+                    nodes: vec![Record {
+                        nodes: vec![Attribute {
+                            name: "sql".to_owned(),
+                            value: Value::Text("'x'".to_owned()),
+                            position: Position { line: 4, column: 30 },
+                        }],
+                        ..Record::new(Some("sql".to_owned()), Position { line: 4, column: 25 })
+                    }],
+                    on_conflict: None,
+                    position: Position { line: 4, column: 19 },
+                })),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_sql() {
+        for input in [
+            "sql",
+            "sql select 1",
+            "sql `select 1` `select 2`",
+            "admin sql `select 1`",
+            "admin = `select 1`",
+            "admin = table users ()",
+            "admin =",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_variables() {
+        let input = tokens(
+            "
+            let tenant = 'acme'
+            let started = `now()`
+            let owner = $tenant
+            table users ( (tenant $tenant) )
+            table let ( let (let $owner) )",
+        );
+
+        let result = parse(input).unwrap();
+
+        assert_eq!(
+            result.nodes[..3],
+            [
+                StructuralNode::Variable(Box::new(Variable {
+                    name: "tenant".to_owned(),
+                    value: Value::Text("'acme'".to_owned()),
+                    position: Position { line: 2, column: 13 },
+                })),
+                StructuralNode::Variable(Box::new(Variable {
+                    name: "started".to_owned(),
+                    value: Value::SqlFragment("now()".to_owned()),
+                    position: Position { line: 3, column: 13 },
+                })),
+                StructuralNode::Variable(Box::new(Variable {
+                    name: "owner".to_owned(),
+                    value: Value::Variable("tenant".to_owned()),
+                    position: Position { line: 4, column: 13 },
+                })),
+            ]
+        );
+
+        let records = match &result.nodes[3] {
+            StructuralNode::Table(table) => &table.nodes,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(
+            records[0].nodes,
+            vec![Attribute {
+                name: "tenant".to_owned(),
+                value: Value::Variable("tenant".to_owned()),
+                position: Position { line: 5, column: 28 },
+            }]
+        );
+
+        let table = match &result.nodes[4] {
+            StructuralNode::Table(table) => table,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(table.identity.name, "let");
+        assert_eq!(table.nodes[0].name.as_deref(), Some("let"));
+        assert_eq!(
+            table.nodes[0].nodes,
+            vec![Attribute {
+                name: "let".to_owned(),
+                value: Value::Variable("owner".to_owned()),
+                position: Position { line: 6, column: 30 },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_variables() {
+        for input in [
+            "let",
+            "let tenant",
+            "let tenant 'acme'",
+            "let tenant =",
+            "let tenant = @users.kevin",
+            "let tenant = fake.uuid()",
+            "let $tenant = 'acme'",
+            "let tenant = 'acme' 'beta'",
+            "table users ( ($tenant 'acme') )",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_params() {
+        let input = tokens(
+            "
+            param tenant_name
+            param plan = 'free' param seats = $default_seats
+            param region
+            param param",
+        );
+
+        assert_eq!(
+            parse(input).unwrap().nodes,
+            vec![
+                StructuralNode::Param(Box::new(Param {
+                    name: "tenant_name".to_owned(),
+                    default: None,
+                    position: Position { line: 2, column: 13 },
+                })),
+                StructuralNode::Param(Box::new(Param {
+                    name: "plan".to_owned(),
+                    default: Some(Value::Text("'free'".to_owned())),
+                    position: Position { line: 3, column: 13 },
+                })),
+                StructuralNode::Param(Box::new(Param {
+                    name: "seats".to_owned(),
+                    default: Some(Value::Variable("default_seats".to_owned())),
+                    position: Position { line: 3, column: 33 },
+                })),
+                StructuralNode::Param(Box::new(Param {
+                    name: "region".to_owned(),
+                    default: None,
+                    position: Position { line: 4, column: 13 },
+                })),
+                StructuralNode::Param(Box::new(Param {
+                    name: "param".to_owned(),
+                    default: None,
+                    position: Position { line: 5, column: 13 },
+                })),
+            ]
+        );
+        assert!(parse(tokens("table plans ( (param $param) )")).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        for input in [
+            "param",
+            "param = 'free'",
+            "param plan =",
+            "param plan = @users.kevin",
+            "param plan 'free'",
+            "param $plan",
+        ] {
+            assert!(parse(tokens(input)).is_err(), "{}", input);
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use crate::Position;
+
 #[derive(Debug, Default, PartialEq)]
 pub struct ParseTree {
     pub nodes: Vec<StructuralNode>,
@@ -7,6 +9,11 @@ pub struct ParseTree {
 pub enum StructuralNode {
     Schema(Box<Schema>),
     Table(Box<Table>),
+    Truncate(Box<Truncate>),
+    ResetSequence(Box<ResetSequence>),
+    Sql(Box<Sql>),
+    Variable(Box<Variable>),
+    Param(Box<Param>),
 }
 
 #[derive(Debug, PartialEq)]
@@ -21,72 +28,261 @@ impl StructuralIdentity {
     }
 }
 
+/// A possibly schema-qualified name of a table or sequence in a directive.
+#[derive(Debug, PartialEq)]
+pub struct QualifiedName {
+    pub schema: Option<String>,
+    pub name: String,
+    pub position: Position,
+}
+
+/// Tables to empty before loading the declarations that follow, eg:
+///
+/// ```text
+/// truncate users, public.orders restart identity cascade
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Truncate {
+    pub tables: Vec<QualifiedName>,
+    pub restart_identity: bool,
+    pub cascade: bool,
+    pub position: Position,
+}
+
+/// Sequences to restart from their start values, eg:
+///
+/// ```text
+/// reset sequence users_id_seq, orders_id_seq
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct ResetSequence {
+    pub sequences: Vec<QualifiedName>,
+    pub position: Position,
+}
+
+/// Raw SQL to execute at this point in the load, eg. to set a GUC or call a procedure.
+/// Named statements must return one row, which can be referenced like a record:
+///
+/// ```text
+/// sql `SET LOCAL app.tenant_id = '42'`
+/// tenant = sql `SELECT id FROM tenant WHERE slug = 'acme'`
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Sql {
+    pub name: Option<String>,
+    pub statement: String,
+    pub position: Position,
+}
+
+/// A value bound to a name for the rest of the file, which values refer to with `$`:
+///
+/// ```text
+/// let tenant = 'b1c4a0e2-6d1f-4c3e-9a57-2f0d8e4b6c1a'
+/// let started = `now()`
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub value: Value,
+    pub position: Position,
+}
+
+/// A variable whose value is given when running the file, which is required unless
+/// it has a default:
+///
+/// ```text
+/// param tenant_name
+/// param plan = 'free'
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub default: Option<Value>,
+    pub position: Position,
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Schema {
     pub identity: StructuralIdentity,
     pub nodes: Vec<Table>,
+    pub position: Position,
 }
 
 impl Schema {
-    pub fn new(name: String, alias: Option<String>) -> Self {
+    pub fn new(name: String, alias: Option<String>, position: Position) -> Self {
         let identity = StructuralIdentity::new(name, alias);
         Self {
             identity,
             nodes: Vec::new(),
+            position,
         }
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Table {
+    /// Text of the `---` comments right before the table
+    pub doc: Option<String>,
     pub identity: StructuralIdentity,
     pub nodes: Vec<Record>,
+    pub on_conflict: Option<OnConflict>,
+    pub position: Position,
 }
 
 impl Table {
-    pub fn new(name: String, alias: Option<String>) -> Self {
+    pub fn new(name: String, alias: Option<String>, position: Position) -> Self {
         let identity = StructuralIdentity::new(name, alias);
         Self {
+            doc: None,
             identity,
             nodes: Vec::new(),
+            on_conflict: None,
+            position,
         }
     }
 }
 
+/// What to do when inserting a record conflicts with an existing row, eg:
+///
+/// ```text
+/// table users on conflict (email) do nothing ( ... )
+/// table users on conflict (email) do update ( ... )
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct OnConflict {
+    /// Columns of the unique constraint or index to detect conflicts on
+    pub columns: Vec<String>,
+    pub action: ConflictAction,
+    pub position: Position,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConflictAction {
+    /// Leave the existing row as is
+    DoNothing,
+    /// Update the existing row with the values given in the record
+    DoUpdate,
+}
+
 #[derive(Debug, Default, PartialEq)]
 pub struct Record {
+    /// Text of the `---` comments right before the record
+    pub doc: Option<String>,
+    pub kind: RecordKind,
     pub name: Option<String>,
     pub nodes: Vec<Attribute>,
+    pub position: Position,
 }
 
 impl Record {
-    pub fn new(name: Option<String>) -> Self {
+    pub fn new(name: Option<String>, position: Position) -> Self {
         Self {
+            doc: None,
+            kind: RecordKind::Insert,
             name,
             nodes: Vec::new(),
+            position,
         }
     }
 }
 
+#[derive(Debug, Default, PartialEq)]
+pub enum RecordKind {
+    /// A row to insert
+    #[default]
+    Insert,
+    /// An existing row to select by its attributes so that it can be referenced, eg:
+    ///
+    /// ```text
+    /// admin_role = lookup (name 'admin')
+    /// ```
+    Lookup,
+    /// Existing rows matching the attributes to update with the `set` attributes, eg:
+    ///
+    /// ```text
+    /// update (name 'beta') set (enabled true) expect 1
+    /// ```
+    Update {
+        set: Vec<Attribute>,
+        expected_rows: Option<u64>,
+    },
+    /// Existing rows matching the attributes to delete, eg:
+    ///
+    /// ```text
+    /// delete (name 'default')
+    /// ```
+    Delete { expected_rows: Option<u64> },
+}
+
 #[derive(Debug, PartialEq)]
 pub struct Attribute {
     pub name: String,
     pub value: Value,
+    pub position: Position,
 }
 
 impl Attribute {
-    pub fn new(name: String, value: Value) -> Self {
-        Self { name, value }
+    pub fn new(name: String, value: Value, position: Position) -> Self {
+        Self { name, value, position }
     }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Value {
     Bool(bool),
+    Cast(Cast),
+    Generator(Generator),
+    Interpolated(Vec<Segment>),
     Number(String),
+    /// SQL fragment with references, which are passed as bind parameters
+    ParameterizedSql(Vec<Segment>),
     Reference(Reference),
     SqlFragment(String),
     Text(String),
+    Variable(String),
+}
+
+impl Value {
+    /// Returns the value being cast, however many times, or the value itself otherwise.
+    pub fn uncast(&self) -> &Value {
+        match self {
+            Value::Cast(cast) => cast.value.uncast(),
+            value => value,
+        }
+    }
+}
+
+/// A value cast to a type, eg:
+///
+/// ```text
+/// '{}'::jsonb
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Cast {
+    pub value: Box<Value>,
+    /// Name of the type as written, eg. `varchar(20)` or `public."Mood"[]`
+    pub data_type: String,
+}
+
+/// A call to generate a fake value, with literal arguments, eg:
+///
+/// ```text
+/// fake.date(1950, 2005)
+/// ```
+#[derive(Debug, Default, PartialEq)]
+pub struct Generator {
+    pub name: String,
+    pub arguments: Vec<Value>,
+}
+
+/// Part of a string with interpolations, eg. `'user-{$i}@{@company.domain}'`, or of a
+/// SQL fragment with references, eg. `` `@order.total * 2` ``.
+#[derive(Debug, PartialEq)]
+pub enum Segment {
+    /// Text between interpolations, unquoted and unescaped, or SQL as is
+    Text(String),
+    Reference(Reference),
+    Variable(String),
 }
 
 /// The set of possible reference types, with varying levels
@@ -110,7 +306,9 @@ pub enum ReferencedColumn {
 
 /// References to a column in the same record, eg:
 ///
-///     @column
+/// ```text
+/// @column
+/// ```
 #[derive(Debug, PartialEq)]
 pub struct ColumnLevelReference {
     pub column: String,
@@ -119,8 +317,10 @@ pub struct ColumnLevelReference {
 /// References that are record-qualified with either explicit or implicit
 /// column reference, eg:
 ///
-///     @record.column  -- explicit column
-///     @record.        -- implicit column
+/// ```text
+/// @record.column  -- explicit column
+/// @record.        -- implicit column
+/// ```
 #[derive(Debug, PartialEq)]
 pub struct RecordLevelReference {
     pub record: String,
@@ -130,8 +330,10 @@ pub struct RecordLevelReference {
 /// References that are table-qualified with either explicit or implicit
 /// column reference, eg:
 ///
-///     @table.record.column  -- explicit column
-///     @table.record.        -- implicit column
+/// ```text
+/// @table.record.column  -- explicit column
+/// @table.record.        -- implicit column
+/// ```
 #[derive(Debug, PartialEq)]
 pub struct TableLevelReference {
     pub table: String,
@@ -142,8 +344,10 @@ pub struct TableLevelReference {
 /// References that are schema-qualified with either explicit or implicit
 /// column reference, eg:
 ///
-///     @schema.table.record.column -- explicit column
-///     @schema.table.record.       -- implicit column
+/// ```text
+/// @schema.table.record.column -- explicit column
+/// @schema.table.record.       -- implicit column
+/// ```
 #[derive(Debug, PartialEq)]
 pub struct SchemaLevelReference {
     pub schema: String,
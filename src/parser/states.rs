@@ -1,7 +1,7 @@
 use super::error::ParseError;
 use super::nodes;
 use crate::Position;
-use crate::lexer::tokens::{Keyword, Symbol, Token, TokenKind};
+use crate::lexer::tokens::{Keyword, Symbol, TextPart, Token, TokenKind};
 use std::mem;
 
 type ParseResult = Result<Box<dyn State>, ParseError>;
@@ -36,26 +36,62 @@ enum PushedTableTo {
 #[derive(Default)]
 pub struct Context {
     pub stack: Vec<StackItem>,
+    /// Doc comment lines waiting for the next table or record
+    doc: Option<String>,
 }
 
 impl Context {
-    fn push_schema(&mut self, schema_name: String, alias: Option<String>) {
-        let schema = nodes::Schema::new(schema_name, alias);
+    /// Adds a line of a doc comment, which only documents a table or record declared
+    /// before anything else is.
+    pub fn push_doc(&mut self, line: String) {
+        match &mut self.doc {
+            Some(doc) => {
+                doc.push('\n');
+                doc.push_str(&line);
+            }
+            None => self.doc = Some(line),
+        }
+    }
+
+    fn push_schema(&mut self, schema_name: String, alias: Option<String>, position: Position) {
+        let schema = nodes::Schema::new(schema_name, alias, position);
+        self.doc = None;
         self.stack.push(StackItem::Schema(Box::new(schema)));
     }
 
-    fn push_table(&mut self, table_name: String, alias: Option<String>) {
-        let table = nodes::Table::new(table_name, alias);
+    fn push_table(&mut self, table_name: String, alias: Option<String>, position: Position) {
+        let table = nodes::Table {
+            doc: self.doc.take(),
+            ..nodes::Table::new(table_name, alias, position)
+        };
         self.stack.push(StackItem::Table(Box::new(table)));
     }
 
-    fn push_record(&mut self, record_name: Option<String>) {
-        let record = nodes::Record::new(record_name);
+    fn push_record(&mut self, record_name: Option<String>, position: Position) {
+        let record = nodes::Record {
+            doc: self.doc.take(),
+            ..nodes::Record::new(record_name, position)
+        };
+        self.stack.push(StackItem::Record(Box::new(record)));
+    }
+
+    fn push_record_of_kind(
+        &mut self,
+        record_name: Option<String>,
+        kind: nodes::RecordKind,
+        position: Position,
+    ) {
+        let record = nodes::Record {
+            doc: self.doc.take(),
+            kind,
+            ..nodes::Record::new(record_name, position)
+        };
         self.stack.push(StackItem::Record(Box::new(record)));
     }
 
-    fn push_attribute(&mut self, name: String, value: nodes::Value) {
-        let attribute = nodes::Attribute::new(name, value);
+    fn push_attribute(&mut self, name: String, value: nodes::Value, position: Position) {
+        let attribute = nodes::Attribute::new(name, value, position);
+        self.doc = None;
         self.stack.push(StackItem::Attribute(Box::new(attribute)));
     }
 
@@ -102,6 +138,14 @@ impl Context {
         }
     }
 
+    fn push_node_to_root_or_panic(&mut self, node: nodes::StructuralNode) {
+        self.doc = None;
+        match self.stack.last_mut() {
+            Some(StackItem::TreeRoot(tree)) => tree.nodes.push(node),
+            elt => panic!("expected tree root on stack; received {:?}", elt),
+        }
+    }
+
     fn push_table_to_parent_or_panic(&mut self, table: nodes::Table) -> PushedTableTo {
         match self.stack.last_mut() {
             Some(StackItem::TreeRoot(tree)) => {
@@ -126,7 +170,59 @@ impl Context {
         }
     }
 
+    fn set_update_attributes_or_panic(&mut self, attributes: Vec<nodes::Attribute>) {
+        match self.stack.last_mut() {
+            Some(StackItem::Record(record)) => match &mut record.kind {
+                nodes::RecordKind::Update { set, .. } => *set = attributes,
+                kind => panic!("expected update record on stack; received {:?}", kind),
+            },
+            elt => panic!("expected record on stack; received {:?}", elt),
+        }
+    }
+
+    fn set_expected_rows_or_panic(&mut self, rows: u64) {
+        match self.stack.last_mut() {
+            Some(StackItem::Record(record)) => match &mut record.kind {
+                nodes::RecordKind::Update { expected_rows, .. }
+                | nodes::RecordKind::Delete { expected_rows } => *expected_rows = Some(rows),
+                kind => panic!("expected update or delete record on stack; received {:?}", kind),
+            },
+            elt => panic!("expected record on stack; received {:?}", elt),
+        }
+    }
+
+    fn set_table_conflict_or_panic(&mut self, on_conflict: nodes::OnConflict) {
+        match self.stack.last_mut() {
+            Some(StackItem::Table(table)) => {
+                table.on_conflict = Some(on_conflict);
+            }
+            elt => panic!("expected table on stack; received {:?}", elt),
+        }
+    }
+
+    fn cast_attribute_or_panic(&mut self, data_type: String) {
+        let mut attribute = self.pop_attribute_or_panic();
+        let value = Box::new(attribute.value);
+        attribute.value = nodes::Value::Cast(nodes::Cast { value, data_type });
+        self.stack.push(StackItem::Attribute(Box::new(attribute)));
+    }
+
+    fn cast_variable_or_panic(&mut self, data_type: String) {
+        match self.stack.last_mut() {
+            Some(StackItem::TreeRoot(tree)) => match tree.nodes.last_mut() {
+                Some(nodes::StructuralNode::Variable(variable)) => {
+                    let value = mem::replace(&mut variable.value, nodes::Value::Bool(false));
+                    let value = Box::new(value);
+                    variable.value = nodes::Value::Cast(nodes::Cast { value, data_type });
+                }
+                node => panic!("expected variable in tree root; received {:?}", node),
+            },
+            elt => panic!("expected tree root on stack; received {:?}", elt),
+        }
+    }
+
     fn push_attribute_to_record_or_panic(&mut self, attribute: nodes::Attribute) {
+        self.doc = None;
         match self.stack.last_mut() {
             Some(StackItem::Record(record)) => {
                 record.nodes.push(attribute);
@@ -155,19 +251,97 @@ impl State for Root {
             TokenKind::LineSep => to(Root),
             TokenKind::Keyword(Keyword::Schema) => to(schema_states::DeclaringSchema),
             TokenKind::Keyword(Keyword::Table) => to(table_states::DeclaringTable),
+            _ if t.kind.is_keyword(&Keyword::Truncate) => {
+                let truncate = nodes::Truncate {
+                    tables: Vec::new(),
+                    restart_identity: false,
+                    cascade: false,
+                    position: t.position,
+                };
+                let directive = directive_states::Directive::Truncate(truncate);
+                to(directive_states::ExpectingName(Some(directive)))
+            }
+            _ if t.kind.is_keyword(&Keyword::Reset) => {
+                to(directive_states::DeclaringReset(t.position))
+            }
+            _ if t.kind.is_keyword(&Keyword::Sql) => to(sql_states::ReceivedSql(None, t.position)),
+            _ if t.kind.is_keyword(&Keyword::Let) => {
+                to(variable_states::DeclaringVariable(t.position))
+            }
+            _ if t.kind.is_keyword(&Keyword::Param) => {
+                to(variable_states::DeclaringParam(t.position))
+            }
+            TokenKind::Identifier(ident) => to(sql_states::ReceivedSqlName(ident, t.position)),
             _ => Err(ParseError::token(t)),
         }
     }
 }
 
-mod schema_states {
+mod directive_states {
     use super::*;
 
-    /// State after receiving the `schema` keyword for declaration.
+    /// A top-level directive being parsed, which ends at a newline or end of file.
     #[derive(Debug)]
-    pub struct DeclaringSchema;
+    pub enum Directive {
+        Truncate(nodes::Truncate),
+        ResetSequence(nodes::ResetSequence),
+    }
 
-    impl State for DeclaringSchema {
+    impl Directive {
+        fn push_name(&mut self, name: nodes::QualifiedName) {
+            match self {
+                Directive::Truncate(truncate) => truncate.tables.push(name),
+                Directive::ResetSequence(reset) => reset.sequences.push(name),
+            }
+        }
+
+        fn finish(self, ctx: &mut Context) -> ParseResult {
+            let node = match self {
+                Directive::Truncate(truncate) => {
+                    nodes::StructuralNode::Truncate(Box::new(truncate))
+                }
+                Directive::ResetSequence(reset) => {
+                    nodes::StructuralNode::ResetSequence(Box::new(reset))
+                }
+            };
+            ctx.push_node_to_root_or_panic(node);
+            to(Root)
+        }
+    }
+
+    /// Takes the directive out of a state, which is only ever received once.
+    fn take(directive: &mut Option<Directive>) -> Directive {
+        directive.take().expect("directive already taken")
+    }
+
+    /// State after receiving the `reset` keyword.
+    #[derive(Debug)]
+    pub struct DeclaringReset(pub Position);
+
+    impl State for DeclaringReset {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                _ if t.kind.is_keyword(&Keyword::Sequence) => {
+                    let reset = nodes::ResetSequence {
+                        sequences: Vec::new(),
+                        position: self.0,
+                    };
+                    to(ExpectingName(Some(Directive::ResetSequence(reset))))
+                }
+                _ => Err(ParseError::exp_keyword(Keyword::Sequence, t)),
+            }
+        }
+    }
+
+    /// State expecting the next table or sequence name in a directive.
+    #[derive(Debug)]
+    pub struct ExpectingName(pub Option<Directive>);
+
+    impl State for ExpectingName {
         fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
             let t = match t {
                 Some(t) => t,
@@ -175,163 +349,867 @@ mod schema_states {
             };
             match t.kind {
                 TokenKind::Identifier(ident) | TokenKind::QuotedIdentifier(ident) => {
-                    to(ReceivedSchemaName(ident))
+                    to(ReceivedName {
+                        directive: self.0.take(),
+                        schema: None,
+                        name: ident,
+                        position: t.position,
+                    })
                 }
-                _ => Err(ParseError::exp_schema(t)),
+                _ => Err(ParseError::exp_ident(t)),
+            }
+        }
+    }
+
+    /// State after receiving a name, which may be qualified by a schema with `.`
+    #[derive(Debug)]
+    struct ReceivedName {
+        directive: Option<Directive>,
+        schema: Option<String>,
+        name: String,
+        position: Position,
+    }
+
+    impl ReceivedName {
+        fn push_name(&mut self) -> Directive {
+            let mut directive = take(&mut self.directive);
+            directive.push_name(nodes::QualifiedName {
+                schema: self.schema.take(),
+                name: mem::take(&mut self.name),
+                position: self.position,
+            });
+            directive
+        }
+    }
+
+    impl State for ReceivedName {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return self.push_name().finish(ctx),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::Period) if self.schema.is_none() => {
+                    to(ReceivingQualifiedName {
+                        directive: self.directive.take(),
+                        schema: mem::take(&mut self.name),
+                        position: self.position,
+                    })
+                }
+                TokenKind::Symbol(Symbol::Comma) => to(ExpectingName(Some(self.push_name()))),
+                _ => defer_to(&mut ReceivedNames(Some(self.push_name())), ctx, Some(t)),
+            }
+        }
+    }
+
+    /// State after receiving `.` following a schema name.
+    #[derive(Debug)]
+    struct ReceivingQualifiedName {
+        directive: Option<Directive>,
+        schema: String,
+        position: Position,
+    }
+
+    impl State for ReceivingQualifiedName {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(ident) | TokenKind::QuotedIdentifier(ident) => {
+                    to(ReceivedName {
+                        directive: self.directive.take(),
+                        schema: Some(mem::take(&mut self.schema)),
+                        name: ident,
+                        position: self.position,
+                    })
+                }
+                _ => Err(ParseError::exp_ident(t)),
+            }
+        }
+    }
+
+    /// State after the last name, expecting the options of a truncate or the end
+    /// of the directive.
+    #[derive(Debug)]
+    struct ReceivedNames(Option<Directive>);
+
+    impl State for ReceivedNames {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let mut directive = take(&mut self.0);
+            let t = match t {
+                Some(t) => t,
+                None => return directive.finish(ctx),
+            };
+            match (&mut directive, &t.kind) {
+                (_, TokenKind::LineSep) => directive.finish(ctx),
+                (Directive::Truncate(truncate), kind)
+                    if kind.is_keyword(&Keyword::Restart)
+                        && !truncate.restart_identity
+                        && !truncate.cascade =>
+                {
+                    to(ReceivedRestart(Some(directive)))
+                }
+                (Directive::Truncate(truncate), kind)
+                    if kind.is_keyword(&Keyword::Cascade) && !truncate.cascade =>
+                {
+                    truncate.cascade = true;
+                    to(ReceivedNames(Some(directive)))
+                }
+                _ => Err(ParseError::token(t)),
+            }
+        }
+    }
+
+    /// State after receiving `restart`, which must be followed by `identity`.
+    #[derive(Debug)]
+    struct ReceivedRestart(Option<Directive>);
+
+    impl State for ReceivedRestart {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let mut directive = take(&mut self.0);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match (&mut directive, &t.kind) {
+                (Directive::Truncate(truncate), kind) if kind.is_keyword(&Keyword::Identity) => {
+                    truncate.restart_identity = true;
+                    to(ReceivedNames(Some(directive)))
+                }
+                _ => Err(ParseError::exp_keyword(Keyword::Identity, t)),
+            }
+        }
+    }
+}
+
+mod sql_states {
+    use super::*;
+
+    /// State after receiving an identifier at the top level, which can only name
+    /// a SQL statement.
+    #[derive(Debug)]
+    pub struct ReceivedSqlName(pub String, pub Position);
+
+    impl State for ReceivedSqlName {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::Equals) => {
+                    to(DeclaringNamedSql(mem::take(&mut self.0), self.1))
+                }
+                _ => Err(ParseError::token(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct DeclaringNamedSql(String, Position);
+
+    impl State for DeclaringNamedSql {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                _ if t.kind.is_keyword(&Keyword::Sql) => {
+                    to(ReceivedSql(Some(mem::take(&mut self.0)), self.1))
+                }
+                _ => Err(ParseError::exp_keyword(Keyword::Sql, t)),
+            }
+        }
+    }
+
+    /// State after receiving the `sql` keyword, expecting the statement itself.
+    #[derive(Debug)]
+    pub struct ReceivedSql(pub Option<String>, pub Position);
+
+    impl State for ReceivedSql {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::SqlFragment(statement) => {
+                    let sql = nodes::Sql {
+                        name: self.0.take(),
+                        statement,
+                        position: self.1,
+                    };
+                    ctx.push_node_to_root_or_panic(nodes::StructuralNode::Sql(Box::new(sql)));
+                    to(Root)
+                }
+                _ => Err(ParseError::exp_sql(t)),
+            }
+        }
+    }
+}
+
+mod variable_states {
+    use self::attribute_states::{CastOf, ReceivingCastType};
+
+    use super::*;
+
+    /// State after receiving the `let` keyword, expecting the variable's name.
+    #[derive(Debug)]
+    pub struct DeclaringVariable(pub Position);
+
+    impl State for DeclaringVariable {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(name) => to(ReceivedVariableName(name, self.0)),
+                _ => Err(ParseError::exp_ident(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedVariableName(String, Position);
+
+    impl State for ReceivedVariableName {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::Equals) => {
+                    to(ReceivedVariableEquals(mem::take(&mut self.0), self.1))
+                }
+                _ => Err(ParseError::token(t)),
+            }
+        }
+    }
+
+    /// State after receiving `let <name> =`, expecting a literal, SQL fragment, or
+    /// another variable as the value.
+    #[derive(Debug)]
+    struct ReceivedVariableEquals(String, Position);
+
+    impl State for ReceivedVariableEquals {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            let variable = nodes::Variable {
+                name: mem::take(&mut self.0),
+                value: variable_value(t)?,
+                position: self.1,
+            };
+            ctx.push_node_to_root_or_panic(nodes::StructuralNode::Variable(Box::new(variable)));
+
+            to(ReceivedVariableValue)
+        }
+    }
+
+    /// State after receiving a variable's value, which can be cast to a type, eg.
+    /// `let since = '2024-01-01'::date`.
+    #[derive(Debug)]
+    pub struct ReceivedVariableValue;
+
+    impl State for ReceivedVariableValue {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            match t {
+                Some(Token { kind: TokenKind::Symbol(Symbol::DoubleColon), .. }) => {
+                    to(ReceivingCastType::new(CastOf::Variable))
+                }
+                _ => defer_to(&mut Root, ctx, t),
+            }
+        }
+    }
+
+    /// State after receiving the `param` keyword, expecting the param's name.
+    #[derive(Debug)]
+    pub struct DeclaringParam(pub Position);
+
+    impl State for DeclaringParam {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(name) => to(ReceivedParamName(name, self.0)),
+                _ => Err(ParseError::exp_ident(t)),
+            }
+        }
+    }
+
+    /// State after receiving `param <name>`, which is complete unless followed by
+    /// `=` and a default value.
+    #[derive(Debug)]
+    struct ReceivedParamName(String, Position);
+
+    impl State for ReceivedParamName {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            if let Some(Token { kind: TokenKind::Symbol(Symbol::Equals), .. }) = t {
+                return to(ReceivedParamEquals(mem::take(&mut self.0), self.1));
+            }
+
+            let param = nodes::Param {
+                name: mem::take(&mut self.0),
+                default: None,
+                position: self.1,
+            };
+            ctx.push_node_to_root_or_panic(nodes::StructuralNode::Param(Box::new(param)));
+
+            defer_to(&mut Root, ctx, t)
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedParamEquals(String, Position);
+
+    impl State for ReceivedParamEquals {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            let param = nodes::Param {
+                name: mem::take(&mut self.0),
+                default: Some(variable_value(t)?),
+                position: self.1,
+            };
+            ctx.push_node_to_root_or_panic(nodes::StructuralNode::Param(Box::new(param)));
+
+            to(Root)
+        }
+    }
+
+    fn variable_value(t: Token) -> Result<nodes::Value, ParseError> {
+        match t.kind {
+            TokenKind::Bool(b) => Ok(nodes::Value::Bool(b)),
+            TokenKind::Number(n) => Ok(nodes::Value::Number(n)),
+            TokenKind::SqlFragment(s) => Ok(nodes::Value::SqlFragment(s)),
+            TokenKind::Text(t) => Ok(nodes::Value::Text(t)),
+            TokenKind::Variable(v) => Ok(nodes::Value::Variable(v)),
+            _ => Err(ParseError::exp_value(t)),
+        }
+    }
+}
+
+mod schema_states {
+    use super::*;
+
+    /// State after receiving the `schema` keyword for declaration.
+    #[derive(Debug)]
+    pub struct DeclaringSchema;
+
+    impl State for DeclaringSchema {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(ident) | TokenKind::QuotedIdentifier(ident) => {
+                    to(ReceivedSchemaName(ident, t.position))
+                }
+                _ => Err(ParseError::exp_schema(t)),
+            }
+        }
+    }
+
+    /// State after receiving the schema name during declaration.
+    #[derive(Debug)]
+    struct ReceivedSchemaName(String, Position);
+
+    impl State for ReceivedSchemaName {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let schema_name = mem::take(&mut self.0);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Keyword(Keyword::As) => to(DeclaringSchemaAlias(schema_name, self.1)),
+                TokenKind::Symbol(Symbol::ParenLeft) => {
+                    ctx.push_schema(schema_name, None, self.1);
+                    to(InSchemaScope)
+                }
+                _ => Err(ParseError::alias_or_scope(t)),
+            }
+        }
+    }
+
+    /// State after receiving the `as` keyword during schema declaration.
+    #[derive(Debug)]
+    struct DeclaringSchemaAlias(String, Position);
+
+    impl State for DeclaringSchemaAlias {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let schema_name = mem::take(&mut self.0);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                // Unlike the true database name, aliases do not support quoted identifiers
+                TokenKind::Identifier(ident) => to(ReceivedSchemaAlias(schema_name, ident, self.1)),
+                _ => Err(ParseError::exp_alias(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedSchemaAlias(String, String, Position);
+
+    impl State for ReceivedSchemaAlias {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::ParenLeft) => {
+                    let schema_name = mem::take(&mut self.0);
+                    let alias = mem::take(&mut self.1);
+                    ctx.push_schema(schema_name, Some(alias), self.2);
+                    to(InSchemaScope)
+                }
+                _ => Err(ParseError::exp_scope(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct InSchemaScope;
+
+    impl State for InSchemaScope {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::ParenRight) => {
+                    let schema = ctx.pop_schema_or_panic();
+                    ctx.push_schema_to_root_or_panic(schema);
+                    to(Root)
+                }
+                TokenKind::Keyword(Keyword::Table) => to(table_states::DeclaringTable),
+                TokenKind::LineSep => to(InSchemaScope),
+                _ => Err(ParseError::in_schema(t)),
+            }
+        }
+    }
+}
+
+mod table_states {
+    use super::*;
+
+    /// State after receiving the `table` keyword for declaration.
+    #[derive(Debug)]
+    pub struct DeclaringTable;
+
+    impl State for DeclaringTable {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(ident) | TokenKind::QuotedIdentifier(ident) => {
+                    to(ReceivedTableName(ident, t.position))
+                }
+                _ => Err(ParseError::exp_table(t)),
+            }
+        }
+    }
+
+    /// State after receiving the table name during declaration.
+    #[derive(Debug)]
+    struct ReceivedTableName(String, Position);
+
+    impl State for ReceivedTableName {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let table_name = mem::take(&mut self.0);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Keyword(Keyword::As) => to(DeclaringTableAlias(table_name, self.1)),
+                _ if t.kind.is_keyword(&Keyword::On) => {
+                    ctx.push_table(table_name, None, self.1);
+                    to(conflict_states::DeclaringConflict(t.position))
+                }
+                TokenKind::Symbol(Symbol::ParenLeft) => {
+                    ctx.push_table(table_name, None, self.1);
+                    to(InTableScope)
+                }
+                _ => Err(ParseError::alias_or_scope(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct DeclaringTableAlias(String, Position);
+
+    impl State for DeclaringTableAlias {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let table_name = mem::take(&mut self.0);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(ident) => to(ReceivedTableAlias(table_name, ident, self.1)),
+                _ => Err(ParseError::exp_alias(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedTableAlias(String, String, Position);
+
+    impl State for ReceivedTableAlias {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                _ if t.kind.is_keyword(&Keyword::On) => {
+                    let table_name = mem::take(&mut self.0);
+                    let alias = mem::take(&mut self.1);
+                    ctx.push_table(table_name, Some(alias), self.2);
+                    to(conflict_states::DeclaringConflict(t.position))
+                }
+                TokenKind::Symbol(Symbol::ParenLeft) => {
+                    let table_name = mem::take(&mut self.0);
+                    let alias = mem::take(&mut self.1);
+                    ctx.push_table(table_name, Some(alias), self.2);
+                    to(InTableScope)
+                }
+                _ => Err(ParseError::exp_scope(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct InTableScope;
+
+    impl State for InTableScope {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::ParenRight) => {
+                    let table = ctx.pop_table_or_panic();
+
+                    match ctx.push_table_to_parent_or_panic(table) {
+                        PushedTableTo::TreeRoot => to(Root),
+                        PushedTableTo::Schema => to(schema_states::InSchemaScope),
+                    }
+                }
+                _ if t.kind.is_keyword(&Keyword::Update) => {
+                    let kind = nodes::RecordKind::Update {
+                        set: Vec::new(),
+                        expected_rows: None,
+                    };
+                    let name = Keyword::Update.to_string();
+                    to(record_states::ReceivedModificationKeyword(name, kind, t.position))
+                }
+                _ if t.kind.is_keyword(&Keyword::Delete) => {
+                    let kind = nodes::RecordKind::Delete { expected_rows: None };
+                    let name = Keyword::Delete.to_string();
+                    to(record_states::ReceivedModificationKeyword(name, kind, t.position))
+                }
+                TokenKind::Identifier(ident) => {
+                    to(record_states::ReceivedRecordName(ident, t.position))
+                }
+                TokenKind::Symbol(Symbol::Underscore) => {
+                    to(record_states::ReceivedExplicitAnonymousRecord(t.position))
+                }
+                TokenKind::Symbol(Symbol::ParenLeft) => {
+                    ctx.push_record(None, t.position);
+                    to(record_states::InRecordScope)
+                }
+                TokenKind::LineSep => to(InTableScope),
+                _ => Err(ParseError::in_table(t)),
+            }
+        }
+    }
+}
+
+mod conflict_states {
+    use super::*;
+
+    /// State after receiving the `on` keyword during table declaration, with the
+    /// table already pushed to the stack.
+    #[derive(Debug)]
+    pub struct DeclaringConflict(pub Position);
+
+    impl State for DeclaringConflict {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                _ if t.kind.is_keyword(&Keyword::Conflict) => to(ReceivedConflict(self.0)),
+                _ => Err(ParseError::exp_keyword(Keyword::Conflict, t)),
+            }
+        }
+    }
+
+    /// State after receiving `on conflict`, which must be followed by the conflict target.
+    #[derive(Debug)]
+    struct ReceivedConflict(Position);
+
+    impl State for ReceivedConflict {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::ParenLeft) => to(InConflictTarget(self.0, Vec::new())),
+                _ => Err(ParseError::exp_scope(t)),
+            }
+        }
+    }
+
+    /// State within the parentheses of the conflict target, expecting a column name.
+    #[derive(Debug)]
+    struct InConflictTarget(Position, Vec<String>);
+
+    impl State for InConflictTarget {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let mut columns = mem::take(&mut self.1);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(ident) | TokenKind::QuotedIdentifier(ident) => {
+                    columns.push(ident);
+                    to(ReceivedConflictColumn(self.0, columns))
+                }
+                TokenKind::LineSep => to(InConflictTarget(self.0, columns)),
+                _ => Err(ParseError::exp_ident(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedConflictColumn(Position, Vec<String>);
+
+    impl State for ReceivedConflictColumn {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let columns = mem::take(&mut self.1);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::Comma) | TokenKind::LineSep => {
+                    to(InConflictTarget(self.0, columns))
+                }
+                TokenKind::Symbol(Symbol::ParenRight) => {
+                    to(ReceivedConflictTarget(self.0, columns))
+                }
+                _ => Err(ParseError::exp_close_attr(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedConflictTarget(Position, Vec<String>);
+
+    impl State for ReceivedConflictTarget {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let columns = mem::take(&mut self.1);
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                _ if t.kind.is_keyword(&Keyword::Do) => {
+                    to(DeclaringConflictAction(self.0, columns))
+                }
+                _ => Err(ParseError::exp_keyword(Keyword::Do, t)),
             }
         }
     }
 
-    /// State after receiving the schema name during declaration.
     #[derive(Debug)]
-    struct ReceivedSchemaName(String);
+    struct DeclaringConflictAction(Position, Vec<String>);
 
-    impl State for ReceivedSchemaName {
+    impl State for DeclaringConflictAction {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
-            let schema_name = mem::take(&mut self.0);
+            let columns = mem::take(&mut self.1);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
-            match t.kind {
-                TokenKind::Keyword(Keyword::As) => to(DeclaringSchemaAlias(schema_name)),
-                TokenKind::Symbol(Symbol::ParenLeft) => {
-                    ctx.push_schema(schema_name, None);
-                    to(InSchemaScope)
-                }
-                _ => Err(ParseError::alias_or_scope(t)),
-            }
+            let action = match t.kind {
+                _ if t.kind.is_keyword(&Keyword::Nothing) => nodes::ConflictAction::DoNothing,
+                _ if t.kind.is_keyword(&Keyword::Update) => nodes::ConflictAction::DoUpdate,
+                _ => return Err(ParseError::exp_conflict_action(t)),
+            };
+
+            ctx.set_table_conflict_or_panic(nodes::OnConflict {
+                columns,
+                action,
+                position: self.0,
+            });
+            to(ReceivedConflictAction)
         }
     }
 
-    /// State after receiving the `as` keyword during schema declaration.
+    /// State after the conflict clause, which must be followed by the table scope.
     #[derive(Debug)]
-    struct DeclaringSchemaAlias(String);
+    struct ReceivedConflictAction;
 
-    impl State for DeclaringSchemaAlias {
+    impl State for ReceivedConflictAction {
         fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
-            let schema_name = mem::take(&mut self.0);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
             match t.kind {
-                // Unlike the true database name, aliases do not support quoted identifiers
-                TokenKind::Identifier(ident) => to(ReceivedSchemaAlias(schema_name, ident)),
-                _ => Err(ParseError::exp_alias(t)),
+                TokenKind::Symbol(Symbol::ParenLeft) => to(table_states::InTableScope),
+                _ => Err(ParseError::exp_scope(t)),
             }
         }
     }
+}
+
+mod record_states {
+    use super::*;
 
+    /// State after receiving a record name in the table scope.
     #[derive(Debug)]
-    struct ReceivedSchemaAlias(String, String);
+    pub struct ReceivedRecordName(pub String, pub Position);
 
-    impl State for ReceivedSchemaAlias {
+    impl State for ReceivedRecordName {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let record_name = mem::take(&mut self.0);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
             match t.kind {
                 TokenKind::Symbol(Symbol::ParenLeft) => {
-                    let schema_name = mem::take(&mut self.0);
-                    let alias = mem::take(&mut self.1);
-                    ctx.push_schema(schema_name, Some(alias));
-                    to(InSchemaScope)
+                    ctx.push_record(Some(record_name), self.1);
+                    to(InRecordScope)
+                }
+                TokenKind::Symbol(Symbol::Equals) => {
+                    to(DeclaringRecordKind(record_name, self.1))
                 }
                 _ => Err(ParseError::exp_scope(t)),
             }
         }
     }
 
+    /// State after receiving `update` or `delete` in the table scope, which starts an
+    /// anonymous modification unless `=` follows, in which case it names a record.
     #[derive(Debug)]
-    pub struct InSchemaScope;
+    pub struct ReceivedModificationKeyword(pub String, pub nodes::RecordKind, pub Position);
 
-    impl State for InSchemaScope {
+    impl State for ReceivedModificationKeyword {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
-            let t = match t {
-                Some(t) => t,
-                None => return Err(ParseError::eof()),
-            };
-            match t.kind {
-                TokenKind::Symbol(Symbol::ParenRight) => {
-                    let schema = ctx.pop_schema_or_panic();
-                    ctx.push_schema_to_root_or_panic(schema);
-                    to(Root)
+            match t {
+                Some(Token {
+                    kind: TokenKind::Symbol(Symbol::Equals),
+                    ..
+                }) => to(DeclaringRecordKind(mem::take(&mut self.0), self.2)),
+                t => {
+                    let kind = mem::take(&mut self.1);
+                    defer_to(&mut ReceivedRecordKind(None, kind, self.2), ctx, t)
                 }
-                TokenKind::Keyword(Keyword::Table) => to(table_states::DeclaringTable),
-                TokenKind::LineSep => to(InSchemaScope),
-                _ => Err(ParseError::in_schema(t)),
             }
         }
     }
-}
 
-mod table_states {
-    use super::*;
-
-    /// State after receiving the `table` keyword for declaration.
+    /// State after receiving `=` following a record name, which must be followed by
+    /// `lookup`, `update`, or `delete`.
     #[derive(Debug)]
-    pub struct DeclaringTable;
+    struct DeclaringRecordKind(String, Position);
 
-    impl State for DeclaringTable {
+    impl State for DeclaringRecordKind {
         fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let record_name = Some(mem::take(&mut self.0));
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
-            match t.kind {
-                TokenKind::Identifier(ident) | TokenKind::QuotedIdentifier(ident) => {
-                    to(ReceivedTableName(ident))
+            let kind = match t.kind {
+                _ if t.kind.is_keyword(&Keyword::Lookup) => nodes::RecordKind::Lookup,
+                _ if t.kind.is_keyword(&Keyword::Update) => nodes::RecordKind::Update {
+                    set: Vec::new(),
+                    expected_rows: None,
+                },
+                _ if t.kind.is_keyword(&Keyword::Delete) => {
+                    nodes::RecordKind::Delete { expected_rows: None }
                 }
-                _ => Err(ParseError::exp_table(t)),
-            }
+                _ => return Err(ParseError::exp_record_kind(t)),
+            };
+            to(ReceivedRecordKind(record_name, kind, self.1))
         }
     }
 
-    /// State after receiving the table name during declaration.
+    /// State after receiving a record kind keyword, expecting the attributes to match.
     #[derive(Debug)]
-    struct ReceivedTableName(String);
+    pub struct ReceivedRecordKind(pub Option<String>, pub nodes::RecordKind, pub Position);
 
-    impl State for ReceivedTableName {
+    impl State for ReceivedRecordKind {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
-            let table_name = mem::take(&mut self.0);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
             match t.kind {
-                TokenKind::Keyword(Keyword::As) => to(DeclaringTableAlias(table_name)),
                 TokenKind::Symbol(Symbol::ParenLeft) => {
-                    ctx.push_table(table_name, None);
-                    to(InTableScope)
+                    let record_name = self.0.take();
+                    let kind = mem::take(&mut self.1);
+                    ctx.push_record_of_kind(record_name, kind, self.2);
+                    to(InRecordScope)
                 }
-                _ => Err(ParseError::alias_or_scope(t)),
+                _ => Err(ParseError::exp_scope(t)),
             }
         }
     }
 
+    /// State after closing the match clause of an update, with the record still
+    /// on the stack.
     #[derive(Debug)]
-    struct DeclaringTableAlias(String);
+    struct ExpectingSet;
 
-    impl State for DeclaringTableAlias {
+    impl State for ExpectingSet {
         fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
-            let table_name = mem::take(&mut self.0);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
             match t.kind {
-                TokenKind::Identifier(ident) => to(ReceivedTableAlias(table_name, ident)),
-                _ => Err(ParseError::exp_alias(t)),
+                _ if t.kind.is_keyword(&Keyword::Set) => to(ReceivedSet),
+                TokenKind::LineSep => to(ExpectingSet),
+                _ => Err(ParseError::exp_keyword(Keyword::Set, t)),
             }
         }
     }
 
     #[derive(Debug)]
-    struct ReceivedTableAlias(String, String);
+    struct ReceivedSet;
 
-    impl State for ReceivedTableAlias {
+    impl State for ReceivedSet {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
             let t = match t {
                 Some(t) => t,
@@ -339,76 +1217,62 @@ mod table_states {
             };
             match t.kind {
                 TokenKind::Symbol(Symbol::ParenLeft) => {
-                    let table_name = mem::take(&mut self.0);
-                    let alias = mem::take(&mut self.1);
-                    ctx.push_table(table_name, Some(alias));
-                    to(InTableScope)
+                    // The `set` attributes are collected in a record of their own
+                    // on top of the update, and moved into it once closed
+                    ctx.push_record(None, t.position);
+                    to(InRecordScope)
                 }
                 _ => Err(ParseError::exp_scope(t)),
             }
         }
     }
 
+    /// State after all clauses of an update or delete have been closed, with the
+    /// record still on the stack, which may be followed by an expected row count.
     #[derive(Debug)]
-    pub struct InTableScope;
+    struct ReceivedModification;
 
-    impl State for InTableScope {
+    impl State for ReceivedModification {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
-            let t = match t {
-                Some(t) => t,
-                None => return Err(ParseError::eof()),
-            };
-            match t.kind {
-                TokenKind::Symbol(Symbol::ParenRight) => {
-                    let table = ctx.pop_table_or_panic();
-
-                    match ctx.push_table_to_parent_or_panic(table) {
-                        PushedTableTo::TreeRoot => to(Root),
-                        PushedTableTo::Schema => to(schema_states::InSchemaScope),
-                    }
-                }
-                TokenKind::Identifier(ident) => to(record_states::ReceivedRecordName(ident)),
-                TokenKind::Symbol(Symbol::Underscore) => {
-                    to(record_states::ReceivedExplicitAnonymousRecord)
-                }
-                TokenKind::Symbol(Symbol::ParenLeft) => {
-                    ctx.push_record(None);
-                    to(record_states::InRecordScope)
+            match t {
+                Some(t) if t.kind.is_keyword(&Keyword::Expect) => to(ReceivedExpect),
+                t => {
+                    let record = ctx.pop_record_or_panic();
+                    ctx.push_record_to_table_or_panic(record);
+                    defer_to(&mut table_states::InTableScope, ctx, t)
                 }
-                TokenKind::LineSep => to(InTableScope),
-                _ => Err(ParseError::in_table(t)),
             }
         }
     }
-}
-
-mod record_states {
-    use super::*;
 
-    /// State after receiving a record name in the table scope.
     #[derive(Debug)]
-    pub struct ReceivedRecordName(pub String);
+    struct ReceivedExpect;
 
-    impl State for ReceivedRecordName {
+    impl State for ReceivedExpect {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
-            let record_name = mem::take(&mut self.0);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
-            match t.kind {
-                TokenKind::Symbol(Symbol::ParenLeft) => {
-                    ctx.push_record(Some(record_name));
-                    to(InRecordScope)
+            let rows = match &t.kind {
+                TokenKind::Number(n) => n.replace('_', "").parse::<u64>().ok(),
+                _ => None,
+            };
+            match rows {
+                Some(rows) => {
+                    ctx.set_expected_rows_or_panic(rows);
+                    let record = ctx.pop_record_or_panic();
+                    ctx.push_record_to_table_or_panic(record);
+                    to(table_states::InTableScope)
                 }
-                _ => Err(ParseError::exp_scope(t)),
+                None => Err(ParseError::exp_row_count(t)),
             }
         }
     }
 
     /// State after receiving an `_` in the table scope.
     #[derive(Debug)]
-    pub struct ReceivedExplicitAnonymousRecord;
+    pub struct ReceivedExplicitAnonymousRecord(pub Position);
 
     impl State for ReceivedExplicitAnonymousRecord {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
@@ -418,7 +1282,7 @@ mod record_states {
             };
             match t.kind {
                 TokenKind::Symbol(Symbol::ParenLeft) => {
-                    ctx.push_record(None);
+                    ctx.push_record(None, self.0);
                     to(InRecordScope)
                 }
                 _ => Err(ParseError::exp_scope(t)),
@@ -438,11 +1302,33 @@ mod record_states {
             match t.kind {
                 TokenKind::Symbol(Symbol::ParenRight) => {
                     let record = ctx.pop_record_or_panic();
-                    ctx.push_record_to_table_or_panic(record);
-                    to(table_states::InTableScope)
+
+                    if let Some(StackItem::Record(_)) = ctx.stack.last() {
+                        // This closes the `set` clause of the update beneath it
+                        if record.nodes.is_empty() {
+                            return Err(ParseError::in_record(t));
+                        }
+                        ctx.set_update_attributes_or_panic(record.nodes);
+                        return to(ReceivedModification);
+                    }
+
+                    match record.kind {
+                        nodes::RecordKind::Update { .. } => {
+                            ctx.stack.push(StackItem::Record(Box::new(record)));
+                            to(ExpectingSet)
+                        }
+                        nodes::RecordKind::Delete { .. } => {
+                            ctx.stack.push(StackItem::Record(Box::new(record)));
+                            to(ReceivedModification)
+                        }
+                        _ => {
+                            ctx.push_record_to_table_or_panic(record);
+                            to(table_states::InTableScope)
+                        }
+                    }
                 }
                 TokenKind::Identifier(ident) | TokenKind::QuotedIdentifier(ident) => {
-                    to(attribute_states::ReceivedAttributeName(ident))
+                    to(attribute_states::ReceivedAttributeName(ident, t.position))
                 }
                 TokenKind::LineSep => to(InRecordScope),
                 _ => Err(ParseError::in_record(t)),
@@ -453,6 +1339,7 @@ mod record_states {
 
 mod attribute_states {
     use self::record_states::InRecordScope;
+    use self::variable_states::ReceivedVariableValue;
 
     use super::*;
 
@@ -463,11 +1350,12 @@ mod attribute_states {
     }
 
     #[derive(Debug)]
-    pub struct ReceivedAttributeName(pub String);
+    pub struct ReceivedAttributeName(pub String, pub Position);
 
     impl State for ReceivedAttributeName {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
             let attribute_name = mem::take(&mut self.0);
+            let position = self.1;
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
@@ -475,23 +1363,44 @@ mod attribute_states {
             match t.kind {
                 TokenKind::Bool(b) => {
                     let value = nodes::Value::Bool(b);
-                    ctx.push_attribute(attribute_name, value);
+                    ctx.push_attribute(attribute_name, value, position);
                     to(ReceivedAttributeValue)
                 }
                 TokenKind::Number(n) => {
                     let value = nodes::Value::Number(n);
-                    ctx.push_attribute(attribute_name, value);
+                    ctx.push_attribute(attribute_name, value, position);
                     to(ReceivedAttributeValue)
                 }
                 TokenKind::SqlFragment(s) => {
                     let value = nodes::Value::SqlFragment(s);
-                    ctx.push_attribute(attribute_name, value);
+                    ctx.push_attribute(attribute_name, value, position);
+                    to(ReceivedAttributeValue)
+                }
+                TokenKind::InterpolatedSql(parts) => {
+                    let value = nodes::Value::ParameterizedSql(interpolated_segments(parts)?);
+                    ctx.push_attribute(attribute_name, value, position);
                     to(ReceivedAttributeValue)
                 }
-                TokenKind::Symbol(Symbol::AtSign) => to(ReceivedReferenceStart(attribute_name)),
+                TokenKind::Symbol(Symbol::AtSign) => {
+                    to(ReceivedReferenceStart(attribute_name, position))
+                }
+                // Generators are namespaced so that `fake` needn't be a keyword
+                TokenKind::Identifier(ident) if ident == "fake" => {
+                    to(ReceivedFake(attribute_name, position))
+                }
                 TokenKind::Text(t) => {
                     let value = nodes::Value::Text(t);
-                    ctx.push_attribute(attribute_name, value);
+                    ctx.push_attribute(attribute_name, value, position);
+                    to(ReceivedAttributeValue)
+                }
+                TokenKind::InterpolatedText(parts) => {
+                    let value = nodes::Value::Interpolated(interpolated_segments(parts)?);
+                    ctx.push_attribute(attribute_name, value, position);
+                    to(ReceivedAttributeValue)
+                }
+                TokenKind::Variable(v) => {
+                    let value = nodes::Value::Variable(v);
+                    ctx.push_attribute(attribute_name, value, position);
                     to(ReceivedAttributeValue)
                 }
                 _ => Err(ParseError::exp_value(t)),
@@ -499,8 +1408,123 @@ mod attribute_states {
         }
     }
 
+    /// State after receiving `fake` as a value, expecting the generator to call.
+    #[derive(Debug)]
+    struct ReceivedFake(String, Position);
+
+    impl State for ReceivedFake {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::Period) => {
+                    to(ExpectingGeneratorName(mem::take(&mut self.0), self.1))
+                }
+                _ => Err(ParseError::token(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ExpectingGeneratorName(String, Position);
+
+    impl State for ExpectingGeneratorName {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Identifier(name) => {
+                    let generator = nodes::Generator { name, arguments: Vec::new() };
+                    to(ReceivedGeneratorName(mem::take(&mut self.0), self.1, generator))
+                }
+                _ => Err(ParseError::exp_ident(t)),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedGeneratorName(String, Position, nodes::Generator);
+
+    impl State for ReceivedGeneratorName {
+        fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::ParenLeft) => to(InGeneratorArguments(
+                    mem::take(&mut self.0),
+                    self.1,
+                    mem::take(&mut self.2),
+                )),
+                _ => Err(ParseError::token(t)),
+            }
+        }
+    }
+
+    /// State within the parentheses of a generator call, expecting a literal
+    /// argument or the end of the call if there are no arguments yet.
+    #[derive(Debug)]
+    struct InGeneratorArguments(String, Position, nodes::Generator);
+
+    impl State for InGeneratorArguments {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            let argument = match t.kind {
+                TokenKind::Bool(b) => nodes::Value::Bool(b),
+                TokenKind::Number(n) => nodes::Value::Number(n),
+                TokenKind::Text(t) => nodes::Value::Text(t),
+                TokenKind::Symbol(Symbol::ParenRight) if self.2.arguments.is_empty() => {
+                    let value = nodes::Value::Generator(mem::take(&mut self.2));
+                    ctx.push_attribute(mem::take(&mut self.0), value, self.1);
+                    return to(ReceivedAttributeValue);
+                }
+                _ => return Err(ParseError::exp_value(t)),
+            };
+            self.2.arguments.push(argument);
+
+            to(ReceivedGeneratorArgument(
+                mem::take(&mut self.0),
+                self.1,
+                mem::take(&mut self.2),
+            ))
+        }
+    }
+
+    #[derive(Debug)]
+    struct ReceivedGeneratorArgument(String, Position, nodes::Generator);
+
+    impl State for ReceivedGeneratorArgument {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let t = match t {
+                Some(t) => t,
+                None => return Err(ParseError::eof()),
+            };
+            match t.kind {
+                TokenKind::Symbol(Symbol::Comma) => to(InGeneratorArguments(
+                    mem::take(&mut self.0),
+                    self.1,
+                    mem::take(&mut self.2),
+                )),
+                TokenKind::Symbol(Symbol::ParenRight) => {
+                    let value = nodes::Value::Generator(mem::take(&mut self.2));
+                    ctx.push_attribute(mem::take(&mut self.0), value, self.1);
+                    to(ReceivedAttributeValue)
+                }
+                _ => Err(ParseError::token(t)),
+            }
+        }
+    }
+
     #[derive(Debug)]
-    pub struct ReceivedReferenceStart(pub String);
+    pub struct ReceivedReferenceStart(pub String, pub Position);
 
     impl State for ReceivedReferenceStart {
         fn receive(&mut self, _ctx: &mut Context, t: Option<Token>) -> ParseResult {
@@ -516,7 +1540,7 @@ mod attribute_states {
                         quoted,
                         value: ident,
                     }];
-                    to(ReceivedReferenceIdentifier(attribute_name, identifiers))
+                    to(ReceivedReferenceIdentifier(attribute_name, self.1, identifiers))
                 }
                 _ => Err(ParseError::exp_ident(t)),
             }
@@ -524,19 +1548,26 @@ mod attribute_states {
     }
 
     #[derive(Debug)]
-    pub struct ReceivedReferenceIdentifier(String, Vec<Identifier>);
+    pub struct ReceivedReferenceIdentifier(String, Position, Vec<Identifier>);
 
     impl State for ReceivedReferenceIdentifier {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
             let attribute_name = mem::take(&mut self.0);
-            let identifiers = mem::take(&mut self.1);
+            let position = self.1;
+            let identifiers = mem::take(&mut self.2);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
             };
             match t.kind {
                 TokenKind::Symbol(Symbol::Period) if identifiers.len() < 4 => {
-                    to(ReceivedReferenceSeparator(attribute_name, identifiers))
+                    to(ReceivedReferenceSeparator(attribute_name, position, identifiers))
+                }
+                TokenKind::Symbol(Symbol::DoubleColon) if identifiers.len() < 5 => {
+                    let reference = identifiers_to_explicit_reference(t.position, identifiers)?;
+                    let value = nodes::Value::Reference(reference);
+                    ctx.push_attribute(attribute_name, value, position);
+                    to(ReceivingCastType::new(CastOf::Attribute))
                 }
                 TokenKind::LineSep
                 | TokenKind::Symbol(Symbol::Comma)
@@ -547,6 +1578,7 @@ mod attribute_states {
                     let attribute = nodes::Attribute {
                         name: attribute_name,
                         value: nodes::Value::Reference(reference),
+                        position,
                     };
                     ctx.push_attribute_to_record_or_panic(attribute);
 
@@ -565,7 +1597,7 @@ mod attribute_states {
     }
 
     #[derive(Debug)]
-    pub struct ReceivedReferenceSeparator(String, Vec<Identifier>);
+    pub struct ReceivedReferenceSeparator(String, Position, Vec<Identifier>);
 
     impl State for ReceivedReferenceSeparator {
         fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
@@ -574,7 +1606,8 @@ mod attribute_states {
             // panics if items are wrong, should these all just be pushing to the
             // `ctx.stack_items` and popping off each step?
             let attribute_name = mem::take(&mut self.0);
-            let mut identifiers = mem::take(&mut self.1);
+            let position = self.1;
+            let mut identifiers = mem::take(&mut self.2);
             let t = match t {
                 Some(t) => t,
                 None => return Err(ParseError::eof()),
@@ -600,7 +1633,7 @@ mod attribute_states {
                         quoted,
                         value: ident,
                     });
-                    to(ReceivedReferenceIdentifier(attribute_name, identifiers))
+                    to(ReceivedReferenceIdentifier(attribute_name, position, identifiers))
                 }
                 // This state can, however, determine if it can successfully terminate without
                 // receiving an identifier, since that is allowed for references above the
@@ -614,6 +1647,7 @@ mod attribute_states {
                     let attribute = nodes::Attribute {
                         name: attribute_name,
                         value: nodes::Value::Reference(reference),
+                        position,
                     };
                     ctx.push_attribute_to_record_or_panic(attribute);
 
@@ -624,6 +1658,12 @@ mod attribute_states {
                         _ => to(InRecordScope),
                     }
                 }
+                TokenKind::Symbol(Symbol::DoubleColon) if identifiers.len() < 4 => {
+                    let reference = identifiers_to_implicit_reference(t.position, identifiers)?;
+                    let value = nodes::Value::Reference(reference);
+                    ctx.push_attribute(attribute_name, value, position);
+                    to(ReceivingCastType::new(CastOf::Attribute))
+                }
                 _ => Err(ParseError::exp_ident(t)),
             }
         }
@@ -652,11 +1692,205 @@ mod attribute_states {
                         _ => to(record_states::InRecordScope),
                     }
                 }
+                TokenKind::Symbol(Symbol::DoubleColon) => {
+                    to(ReceivingCastType::new(CastOf::Attribute))
+                }
                 _ => Err(ParseError::exp_close_attr(t)),
             }
         }
     }
 
+    /// The last part of a type name received, which determines what can follow it.
+    #[derive(Debug)]
+    enum CastPart {
+        Start,
+        Name,
+        Period,
+        ModifiersStart,
+        Modifier,
+        ModifierComma,
+        ModifiersEnd,
+        BracketLeft,
+        BracketRight,
+    }
+
+    /// The value a type name is received for, which determines where parsing
+    /// continues once the name ends.
+    #[derive(Clone, Copy, Debug)]
+    pub enum CastOf {
+        Attribute,
+        Variable,
+    }
+
+    /// Words that can follow the first word of a multi-word type name, eg.
+    /// `double precision`, `character varying` or `timestamp with time zone`.
+    const TYPE_NAME_WORDS: &[&str] = &[
+        "char",
+        "character",
+        "day",
+        "hour",
+        "minute",
+        "month",
+        "precision",
+        "second",
+        "time",
+        "to",
+        "varying",
+        "with",
+        "without",
+        "year",
+        "zone",
+    ];
+
+    /// State after receiving `::` following a value, building the name of the type
+    /// to cast it to, eg. `varchar(20)`, `public."Mood"` or `int[]`.
+    #[derive(Debug)]
+    pub struct ReceivingCastType(String, CastPart, CastOf);
+
+    impl ReceivingCastType {
+        pub fn new(of: CastOf) -> Self {
+            Self(String::new(), CastPart::Start, of)
+        }
+
+        fn end(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            let data_type = mem::take(&mut self.0);
+            match self.2 {
+                CastOf::Attribute => {
+                    ctx.cast_attribute_or_panic(data_type);
+                    defer_to(&mut ReceivedAttributeValue, ctx, t)
+                }
+                CastOf::Variable => {
+                    ctx.cast_variable_or_panic(data_type);
+                    defer_to(&mut ReceivedVariableValue, ctx, t)
+                }
+            }
+        }
+    }
+
+    impl State for ReceivingCastType {
+        fn receive(&mut self, ctx: &mut Context, t: Option<Token>) -> ParseResult {
+            use CastPart::*;
+
+            let t = match (t, &self.1) {
+                (Some(t), _) => t,
+                // A variable's value can end the file
+                (None, Name | ModifiersEnd | BracketRight) if matches!(self.2, CastOf::Variable) => {
+                    return self.end(ctx, None);
+                }
+                (None, _) => return Err(ParseError::eof()),
+            };
+            let data_type = &mut self.0;
+
+            let part = match (&self.1, t.kind) {
+                (
+                    Start | Period,
+                    TokenKind::Identifier(name) | TokenKind::QuotedIdentifier(name),
+                ) => {
+                    data_type.push_str(&name);
+                    Name
+                }
+                (Start | Period, kind) => {
+                    return Err(ParseError::exp_type(Token { kind, position: t.position }));
+                }
+                (Name, TokenKind::Symbol(Symbol::Period)) => {
+                    data_type.push('.');
+                    Period
+                }
+                (Name, TokenKind::Symbol(Symbol::ParenLeft)) => {
+                    data_type.push('(');
+                    ModifiersStart
+                }
+                (ModifiersStart | ModifierComma, TokenKind::Number(n))
+                    if n.chars().all(|c| c.is_ascii_digit()) =>
+                {
+                    data_type.push_str(&n);
+                    Modifier
+                }
+                (Modifier, TokenKind::Symbol(Symbol::Comma)) => {
+                    data_type.push_str(", ");
+                    ModifierComma
+                }
+                (Modifier, TokenKind::Symbol(Symbol::ParenRight)) => {
+                    data_type.push(')');
+                    ModifiersEnd
+                }
+                (Name | ModifiersEnd | BracketRight, TokenKind::Symbol(Symbol::BracketLeft)) => {
+                    data_type.push('[');
+                    BracketLeft
+                }
+                (BracketLeft, TokenKind::Symbol(Symbol::BracketRight)) => {
+                    data_type.push(']');
+                    BracketRight
+                }
+                (Name | ModifiersEnd, TokenKind::Identifier(word))
+                    if TYPE_NAME_WORDS.contains(&word.to_ascii_lowercase().as_str()) =>
+                {
+                    data_type.push(' ');
+                    data_type.push_str(&word);
+                    Name
+                }
+                // Anything else ends the type name, and must be able to end the value
+                (Name | ModifiersEnd | BracketRight, kind) => {
+                    return self.end(ctx, Some(Token { kind, position: t.position }));
+                }
+                (_, kind) => return Err(ParseError::token(Token { kind, position: t.position })),
+            };
+
+            to(ReceivingCastType(mem::take(data_type), part, self.2))
+        }
+    }
+
+    /// Parses the interpolations in a string, each of which is either a variable or a
+    /// reference, such as `{$tenant}` or `{@alice.username}`, or the references in a
+    /// SQL fragment.
+    fn interpolated_segments(parts: Vec<TextPart>) -> Result<Vec<nodes::Segment>, ParseError> {
+        parts
+            .into_iter()
+            .map(|part| match part {
+                TextPart::Text(text) => Ok(nodes::Segment::Text(text)),
+                TextPart::Interpolation(tokens) => interpolation_segment(tokens),
+            })
+            .collect()
+    }
+
+    fn interpolation_segment(tokens: Vec<Token>) -> Result<nodes::Segment, ParseError> {
+        let mut tokens = tokens.into_iter();
+        let start = tokens.next().expect("interpolations start with `@` or `$`");
+
+        if let TokenKind::Variable(name) = start.kind {
+            return match tokens.next() {
+                Some(t) => Err(ParseError::token(t)),
+                None => Ok(nodes::Segment::Variable(name)),
+            };
+        }
+
+        // Same forms as references in values, where a trailing period is an implicit column
+        let mut identifiers = Vec::new();
+        let mut after_separator = true;
+
+        for t in tokens {
+            let quoted = matches!(&t.kind, TokenKind::QuotedIdentifier(_));
+            match t.kind {
+                TokenKind::Identifier(value) | TokenKind::QuotedIdentifier(value)
+                    if after_separator && identifiers.len() < 4 =>
+                {
+                    identifiers.push(Identifier { quoted, value });
+                    after_separator = false;
+                }
+                TokenKind::Symbol(Symbol::Period) if !after_separator => after_separator = true,
+                _ => return Err(ParseError::token(t)),
+            }
+        }
+
+        let reference = match (identifiers.len(), after_separator) {
+            (0, _) | (4, true) => return Err(ParseError::incomplete_interp(start.position)),
+            (_, true) => identifiers_to_implicit_reference(start.position, identifiers)?,
+            (_, false) => identifiers_to_explicit_reference(start.position, identifiers)?,
+        };
+
+        Ok(nodes::Segment::Reference(reference))
+    }
+
     fn identifiers_to_explicit_reference(position: Position, identifiers: Vec<Identifier>) -> Result<nodes::Reference, ParseError> {
         use nodes::*;
         use ReferencedColumn::Explicit;
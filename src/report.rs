@@ -0,0 +1,171 @@
+//! Machine-readable summary of a run, for downstream tooling that needs to know
+//! what was inserted (eg. generated primary keys) without querying the database.
+
+use postgres::SimpleQueryRow;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::error::{HldrError, HldrErrorKind};
+use crate::Position;
+
+/// Column values of a returned row, as their text representations.
+pub type Columns = BTreeMap<String, Option<String>>;
+
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Named records keyed by their reference scope, eg. `myschema.mytable.myrecord`
+    pub records: BTreeMap<String, Columns>,
+    pub tables: Vec<TableReport>,
+    pub outcome: Outcome,
+    /// Run to pass to `hldr teardown`, if a manifest was written
+    pub run_id: Option<String>,
+    /// Seed that reproduces anything random in the run when passed as `--seed`
+    pub seed: Option<u64>,
+    pub error: Option<ErrorReport>,
+    /// Records that failed to load when continuing on errors
+    pub failures: Vec<ErrorReport>,
+}
+
+impl Report {
+    pub(crate) fn add_record(&mut self, key: String, row: &SimpleQueryRow) {
+        let columns = row
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, column)| (column.name().to_owned(), row.get(i).map(str::to_owned)))
+            .collect();
+
+        self.records.insert(key, columns);
+    }
+
+    pub(crate) fn add_table(&mut self, table: String, counts: RowCounts, duration: Duration) {
+        self.tables.push(TableReport {
+            table,
+            rows: counts.inserted,
+            updated: counts.updated,
+            upserted: counts.upserted,
+            deleted: counts.deleted,
+            duration_ms: duration.as_secs_f64() * 1000.0,
+        });
+    }
+
+    pub(crate) fn add_error(&mut self, error: &HldrError) {
+        self.outcome = Outcome::Failed;
+        self.error = Some(ErrorReport::from(error));
+    }
+
+    pub(crate) fn add_failure(&mut self, error: &HldrError) {
+        self.failures.push(ErrorReport::from(error));
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableReport {
+    pub table: String,
+    /// Rows inserted
+    pub rows: usize,
+    pub updated: usize,
+    /// Rows inserted or updated by `on conflict ... do update`, which can't be told apart
+    pub upserted: usize,
+    pub deleted: usize,
+    pub duration_ms: f64,
+}
+
+/// Rows affected in a single table.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RowCounts {
+    pub inserted: usize,
+    pub updated: usize,
+    pub upserted: usize,
+    pub deleted: usize,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Committed,
+    #[default]
+    RolledBack,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub kind: HldrErrorKind,
+    pub message: String,
+    pub position: Option<Position>,
+    pub file: Option<PathBuf>,
+}
+
+impl From<&HldrError> for ErrorReport {
+    fn from(error: &HldrError) -> Self {
+        Self {
+            kind: error.kind,
+            message: error.error.to_string(),
+            position: error.position,
+            file: error.file.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::error::{LexError, LexErrorKind};
+    use crate::loader::error::{LoadError, LoadErrorKind};
+
+    #[test]
+    fn test_add_error() {
+        let mut report = Report::default();
+        let error = HldrError::from(LexError {
+            kind: LexErrorKind::UnclosedString,
+            position: Position { line: 3, column: 7 },
+        })
+        .in_file(std::path::Path::new("place.hldr"));
+
+        report.add_error(&error);
+
+        assert_eq!(report.outcome, Outcome::Failed);
+        assert_eq!(
+            serde_json::to_value(&report.error).unwrap(),
+            serde_json::json!({
+                "kind": "LexError",
+                "message": "unclosed string starting at line 3, column 7",
+                "position": { "line": 3, "column": 7 },
+                "file": "place.hldr",
+            }),
+        );
+    }
+
+    #[test]
+    fn test_add_failure() {
+        let mut report = Report::default();
+        let error = HldrError::from(LoadError {
+            kind: LoadErrorKind::DependencyFailed {
+                record: "person.kevin".to_owned(),
+            },
+            position: Position {
+                line: 9,
+                column: 11,
+            },
+        })
+        .in_file(std::path::Path::new("place.hldr"));
+
+        report.add_failure(&error);
+
+        // Failed records don't fail the run as a whole
+        assert_eq!(report.outcome, Outcome::RolledBack);
+        assert!(report.error.is_none());
+        assert_eq!(
+            serde_json::to_value(&report.failures).unwrap(),
+            serde_json::json!([{
+                "kind": "LoadError",
+                "message": "skipped because referenced record `person.kevin` failed to load at line 9, column 11",
+                "position": { "line": 9, "column": 11 },
+                "file": "place.hldr",
+            }]),
+        );
+    }
+}
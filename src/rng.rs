@@ -0,0 +1,80 @@
+//! The one random number generator of a run, seeded so that anything random in it
+//! (eg. generated values) can be reproduced by running it again with the same seed.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A seed for runs that aren't given one, which differs from run to run.
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+
+    Rng::new(nanos ^ u64::from(std::process::id())).next_u64()
+}
+
+/// SplitMix64, which is tiny and more than random enough for fake data while
+/// guaranteeing the same sequence for the same seed on every platform.
+#[derive(Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns an integer in the inclusive range.
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        let span = (i128::from(max) - i128::from(min) + 1) as u128;
+        let offset = u128::from(self.next_u64()) % span;
+
+        (i128::from(min) + offset as i128) as i64
+    }
+
+    pub fn choose<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.range(0, items.len() as i64 - 1) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence() {
+        // The sequence for a seed must never change, or seeded runs would stop
+        // reproducing the values of earlier runs
+        let mut rng = Rng::new(42);
+        let values: Vec<_> = (0..3).map(|_| rng.next_u64()).collect();
+
+        assert_eq!(
+            values,
+            vec![
+                13_679_457_532_755_275_413,
+                2_949_826_092_126_892_291,
+                5_139_283_748_462_763_858
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range() {
+        let mut rng = Rng::new(7);
+
+        for _ in 0..1000 {
+            assert!((-3..=3).contains(&rng.range(-3, 3)));
+        }
+        assert_eq!(rng.range(5, 5), 5);
+        assert!(rng.range(i64::MIN, i64::MAX) != rng.range(i64::MIN, i64::MAX));
+    }
+}